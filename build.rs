@@ -1,3 +1,67 @@
+use {
+    flate2::{write::GzEncoder, Compression},
+    std::{
+        env, fs,
+        io::Write,
+        path::{Path, PathBuf},
+    },
+};
+
 fn main() {
     embuild::espidf::sysenv::output();
+    embed_web_assets().expect("failed to embed web assets");
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+// fnv-1a, only used to give the browser a stable etag per asset
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Gzips everything in `web/` into OUT_DIR and generates `web_assets.rs`,
+/// which `src/web.rs` includes.
+fn embed_web_assets() -> std::io::Result<()> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR"));
+    let web_dir = Path::new("web");
+    println!("cargo:rerun-if-changed={}", web_dir.display());
+
+    let mut entries = fs::read_dir(web_dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    let mut generated = String::from("pub const ASSETS: &[Asset] = &[\n");
+    for path in entries.iter().filter(|p| p.is_file()) {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let raw = fs::read(path)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&raw)?;
+        let gz_path = out_dir.join(format!("{name}.gz"));
+        fs::write(&gz_path, encoder.finish()?)?;
+
+        let uri = match name.as_ref() {
+            "index.html" => "/".to_string(),
+            _ => format!("/{name}"),
+        };
+        generated.push_str(&format!(
+            "    Asset {{ uri: {uri:?}, content_type: {:?}, etag: \"\\\"{:016x}\\\"\", body: include_bytes!({:?}) }},\n",
+            content_type(path),
+            fnv1a(&raw),
+            gz_path.display().to_string(),
+        ));
+    }
+    generated.push_str("];\n");
+    fs::write(out_dir.join("web_assets.rs"), generated)
 }
@@ -0,0 +1,282 @@
+const API_TOKEN: &str = env!("API_TOKEN");
+const API_USER: &str = env!("API_USER");
+const API_PASS: &str = env!("API_PASS");
+const API_PUBLIC_STATE: &str = env!("API_PUBLIC_STATE");
+const CORS_ORIGINS: &str = env!("CORS_ORIGINS");
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Access policy for the http api, read from the build environment.
+pub struct Auth {
+    token: &'static str,
+    basic: Option<String>,
+    /// User & password, for checking WS-Security tokens
+    credentials: Option<(String, String)>,
+    public_state: bool,
+    origins: Vec<&'static str>,
+}
+
+impl Auth {
+    pub fn from_env() -> Self {
+        Self::new(
+            API_TOKEN,
+            API_USER,
+            API_PASS,
+            API_PUBLIC_STATE != "false",
+            CORS_ORIGINS,
+        )
+    }
+
+    pub fn new(
+        token: &'static str,
+        user: &str,
+        pass: &str,
+        public_state: bool,
+        origins: &'static str,
+    ) -> Self {
+        Self {
+            token,
+            basic: (!user.is_empty()).then(|| base64_encode(format!("{user}:{pass}").as_bytes())),
+            credentials: (!user.is_empty()).then(|| (user.to_string(), pass.to_string())),
+            public_state,
+            origins: origins
+                .split(',')
+                .map(str::trim)
+                .filter(|o| !o.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Whether any credentials are configured. Without them, mutating
+    /// endpoints are open to the whole network.
+    pub fn is_enabled(&self) -> bool {
+        !self.token.is_empty() || self.basic.is_some()
+    }
+
+    /// Checks an `Authorization` header. Accepts `Bearer <token>` or
+    /// `Basic <base64 user:pass>`, whichever is configured.
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let Some(authorization) = authorization.map(str::trim) else {
+            return false;
+        };
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return !self.token.is_empty()
+                && constant_time_eq(token.trim().as_bytes(), self.token.as_bytes());
+        }
+        if let Some(credentials) = authorization.strip_prefix("Basic ") {
+            return self
+                .basic
+                .as_ref()
+                .map(|basic| constant_time_eq(credentials.trim().as_bytes(), basic.as_bytes()))
+                .unwrap_or(false);
+        }
+        false
+    }
+
+    /// Checks a WS-Security `UsernameToken` against the configured user &
+    /// password. Freshness of `created` is left to the caller.
+    pub fn is_authorized_token(&self, token: &UsernameToken) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let Some((user, pass)) = &self.credentials else {
+            return false;
+        };
+        if !constant_time_eq(token.username.as_bytes(), user.as_bytes()) {
+            return false;
+        }
+        match token.digest {
+            false => constant_time_eq(token.password.as_bytes(), pass.as_bytes()),
+            true => {
+                let Some(nonce) = base64_decode(token.nonce) else {
+                    return false;
+                };
+                let digest = sha1(&[&nonce, token.created.as_bytes(), pass.as_bytes()].concat());
+                constant_time_eq(token.password.as_bytes(), base64_encode(&digest).as_bytes())
+            }
+        }
+    }
+
+    /// Like [Auth::is_authorized], but read-only endpoints may be public.
+    pub fn can_read(&self, authorization: Option<&str>) -> bool {
+        self.public_state || self.is_authorized(authorization)
+    }
+
+    /// The `WWW-Authenticate` challenge matching the configured scheme.
+    pub fn challenge(&self) -> &'static str {
+        match self.basic {
+            Some(_) => "Basic realm=\"gimbal\"",
+            None => "Bearer",
+        }
+    }
+
+    /// The value to echo in `Access-Control-Allow-Origin`, if the request
+    /// origin is allowed.
+    pub fn allowed_origin<'a>(&self, origin: Option<&'a str>) -> Option<&'a str> {
+        let origin = origin?;
+        self.origins
+            .iter()
+            .any(|allowed| *allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+            .then_some(origin)
+    }
+}
+
+/// A WS-Security `UsernameToken`, as ONVIF clients put in the SOAP header.
+pub struct UsernameToken<'a> {
+    pub username: &'a str,
+    /// The password, or with `digest` base64 of SHA-1 over the nonce,
+    /// `created` & the password
+    pub password: &'a str,
+    pub digest: bool,
+    /// Base64
+    pub nonce: &'a str,
+    pub created: &'a str,
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim().trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut len) = (0u32, 0);
+    for c in text.bytes() {
+        let value = BASE64_ALPHABET.iter().position(|a| *a == c)? as u32;
+        bits = (bits << 6) | value;
+        len += 6;
+        if len >= 8 {
+            len -= 8;
+            out.push((bits >> len) as u8);
+            bits &= (1 << len) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(bytes.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = (a.rotate_left(5))
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0; 20];
+    for (bytes, word) in out.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b"user:pass"), "dXNlcjpwYXNz");
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+    }
+
+    #[test]
+    fn test_authorization() {
+        let auth = Auth::new("s3cret", "user", "pass", false, "");
+        assert!(auth.is_authorized(Some("Bearer s3cret")));
+        assert!(auth.is_authorized(Some("Basic dXNlcjpwYXNz")));
+        assert!(!auth.is_authorized(Some("Bearer nope")));
+        assert!(!auth.is_authorized(None));
+        assert!(!auth.can_read(None));
+
+        let open = Auth::new("", "", "", true, "");
+        assert!(open.is_authorized(None));
+    }
+
+    #[test]
+    fn test_username_token() {
+        // from the ONVIF application programmer's guide
+        let auth = Auth::new("", "user", "userpassword", false, "");
+        let token = UsernameToken {
+            username: "user",
+            password: "tuOSpGlFlIXsozq4HFNeeGeFLEI=",
+            digest: true,
+            nonce: "LKqI6G/AikKCQrN0zqZFlg==",
+            created: "2010-09-16T07:50:45Z",
+        };
+        assert!(auth.is_authorized_token(&token));
+        assert!(!auth.is_authorized_token(&UsernameToken {
+            created: "2010-09-16T07:50:46Z",
+            ..token
+        }));
+        assert!(auth.is_authorized_token(&UsernameToken {
+            password: "userpassword",
+            digest: false,
+            ..token
+        }));
+        assert_eq!(base64_decode("YWI="), Some(b"ab".to_vec()));
+        assert_eq!(base64_encode(&sha1(b"abc")), "qZk+NkcGgWq6PiVxeFDCbJzQ2J0=");
+    }
+
+    #[test]
+    fn test_allowed_origin() {
+        let auth = Auth::new("", "", "", true, "https://a.example, https://b.example");
+        assert_eq!(
+            auth.allowed_origin(Some("https://b.example")),
+            Some("https://b.example")
+        );
+        assert_eq!(auth.allowed_origin(Some("https://evil.example")), None);
+        assert_eq!(auth.allowed_origin(None), None);
+    }
+}
@@ -0,0 +1,321 @@
+// Jobs that shoot a series of frames. For each one the gimbal moves, lets the
+// rig settle, fires the shutter & waits out the exposure, so the camera never
+// moves mid frame.
+
+use {
+    crate::{panorama::Panorama, timelapse::Timelapse},
+    log::warn,
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, Instant},
+};
+
+/// Longest interval, settle or exposure, in seconds: a day
+pub const MAX_SECS: f32 = 86_400.;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Capture {
+    Timelapse(Timelapse),
+    Panorama(Panorama),
+}
+
+impl Capture {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.validate(),
+            Capture::Panorama(panorama) => panorama.validate(),
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.frames,
+            Capture::Panorama(panorama) => panorama.shots(),
+        }
+    }
+
+    /// Where to shoot `frame`.
+    pub fn position(&self, frame: u32) -> (f32, f32) {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.position(frame),
+            Capture::Panorama(panorama) => panorama.position(frame),
+        }
+    }
+
+    /// Seconds from the start of one frame to the next, at least. Panoramas
+    /// carry straight on.
+    fn interval(&self) -> f32 {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.interval,
+            Capture::Panorama(_) => 0.,
+        }
+    }
+
+    fn settle(&self) -> f32 {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.settle,
+            Capture::Panorama(panorama) => panorama.settle,
+        }
+    }
+
+    fn exposure(&self) -> f32 {
+        match self {
+            Capture::Timelapse(timelapse) => timelapse.exposure,
+            Capture::Panorama(panorama) => panorama.exposure,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Capture::Timelapse(_) => "timelapse",
+            Capture::Panorama(_) => "panorama",
+        }
+    }
+}
+
+/// What the gimbal should do next for a running job.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Move((f32, f32)),
+    Fire,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Waiting(Instant),
+    Moving,
+    Settling(Instant),
+    Exposing(Instant),
+}
+
+/// How far a job got, saved apart from its capture so that writing it as the
+/// job goes stays small.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Bookmark {
+    pub frame: u32,
+    pub paused: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub capture: Capture,
+    /// Frames shot so far, the next one to shoot
+    pub frame: u32,
+    pub paused: bool,
+    phase: Phase,
+    frame_started: Instant,
+}
+
+impl Job {
+    pub fn new(capture: Capture, now: Instant) -> Self {
+        Self {
+            capture,
+            frame: 0,
+            paused: false,
+            phase: Phase::Waiting(now),
+            frame_started: now,
+        }
+    }
+
+    /// A saved job, paused until it's resumed whether it was running or not.
+    pub fn restore(capture: Capture, bookmark: Bookmark, now: Instant) -> Self {
+        Self {
+            frame: bookmark.frame,
+            paused: true,
+            ..Self::new(capture, now)
+        }
+    }
+
+    pub fn bookmark(&self) -> Bookmark {
+        Bookmark {
+            frame: self.frame,
+            paused: self.paused,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.capture.frames()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.paused && !self.is_finished()
+    }
+
+    /// Advances the job to `now`, returning what to do once it's due. Call
+    /// again straight after doing it.
+    pub fn poll(&mut self, now: Instant) -> Option<Action> {
+        while self.is_running() {
+            match self.phase {
+                Phase::Waiting(due) | Phase::Settling(due) | Phase::Exposing(due) if now < due => {
+                    return None
+                }
+                Phase::Waiting(_) => {
+                    self.frame_started = now;
+                    self.phase = Phase::Moving;
+                    return Some(Action::Move(self.capture.position(self.frame)));
+                }
+                Phase::Moving => {
+                    let due = self.after(now, self.capture.settle())?;
+                    self.phase = Phase::Settling(due);
+                }
+                Phase::Settling(_) => {
+                    let due = self.after(now, self.capture.exposure())?;
+                    self.phase = Phase::Exposing(due);
+                    return Some(Action::Fire);
+                }
+                Phase::Exposing(_) => {
+                    self.frame += 1;
+                    let due = self.after(self.frame_started, self.capture.interval())?;
+                    self.phase = Phase::Waiting(due);
+                }
+            }
+        }
+        None
+    }
+
+    /// `secs` after `from`. Pauses the job instead, returning `None`, if
+    /// that's more than an `Instant` holds, which `validate` doesn't allow.
+    fn after(&mut self, from: Instant, secs: f32) -> Option<Instant> {
+        let due = Duration::try_from_secs_f32(secs)
+            .ok()
+            .and_then(|secs| from.checked_add(secs));
+        if due.is_none() {
+            warn!("pausing capture: can't wait {secs}s");
+            self.paused = true;
+        }
+        due
+    }
+
+    /// Pauses after the frame being shot, if the shutter has fired.
+    pub fn pause(&mut self) {
+        if self.is_running() {
+            if let Phase::Exposing(_) = self.phase {
+                self.frame += 1;
+            }
+            self.paused = true;
+        }
+    }
+
+    /// Carries on with the next frame, moving back into place first.
+    pub fn resume(&mut self, now: Instant) {
+        self.paused = false;
+        self.phase = Phase::Waiting(now);
+    }
+
+    pub fn progress(&self) -> Progress {
+        let remaining = self.capture.frames().saturating_sub(self.frame);
+        let per_frame = f32::max(
+            self.capture.interval(),
+            self.capture.settle() + self.capture.exposure(),
+        );
+        Progress {
+            kind: self.capture.kind(),
+            frame: self.frame,
+            frames: self.capture.frames(),
+            paused: self.paused,
+            remaining_secs: remaining as f32 * per_frame,
+        }
+    }
+}
+
+/// Reported in `/api/state`.
+#[derive(Clone, Serialize)]
+pub struct Progress {
+    pub kind: &'static str,
+    /// Frames shot
+    pub frame: u32,
+    pub frames: u32,
+    pub paused: bool,
+    /// Roughly, while running. Panoramas leave out moving.
+    pub remaining_secs: f32,
+}
+
+/// Job controls, as posted to `/api/capture/control`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Control {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{timelapse::Waypoint, timeline::Easing},
+    };
+
+    fn timelapse() -> Capture {
+        let waypoint = |frame, pan, tilt| Waypoint {
+            frame,
+            pan,
+            tilt,
+            easing: Easing::Linear,
+        };
+        Capture::Timelapse(Timelapse {
+            waypoints: vec![waypoint(0, 0., 0.), waypoint(10, 100., 10.)],
+            frames: 11,
+            interval: 5.,
+            settle: 1.,
+            exposure: 2.,
+        })
+    }
+
+    #[test]
+    fn test_shoot_move_shoot() {
+        let start = Instant::now();
+        let at = |secs: f32| start + Duration::from_secs_f32(secs);
+        let mut job = Job::new(timelapse(), start);
+
+        assert_eq!(job.poll(at(0.)), Some(Action::Move((0., 0.))));
+        // the move took half a second
+        assert_eq!(job.poll(at(0.5)), None);
+        assert_eq!(job.poll(at(1.)), None);
+        assert_eq!(job.poll(at(1.5)), Some(Action::Fire));
+        assert_eq!(job.poll(at(3.)), None);
+        assert_eq!(job.poll(at(3.5)), None);
+        assert_eq!(job.frame, 1);
+        // the next frame starts an interval after the last one did
+        assert_eq!(job.poll(at(4.9)), None);
+        assert_eq!(job.poll(at(5.)), Some(Action::Move((10., 1.))));
+
+        job.pause();
+        assert_eq!(job.poll(at(100.)), None);
+        job.resume(at(100.));
+        assert_eq!(job.poll(at(100.)), Some(Action::Move((10., 1.))));
+        assert_eq!(job.progress().remaining_secs, 50.);
+    }
+
+    #[test]
+    fn test_endless_wait() {
+        let mut capture = timelapse();
+        if let Capture::Timelapse(timelapse) = &mut capture {
+            timelapse.settle = 1e30;
+        }
+        let mut job = Job::new(capture, Instant::now());
+        assert!(job.poll(Instant::now()).is_some());
+        assert_eq!(job.poll(Instant::now()), None);
+        assert!(job.paused);
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut job = Job::new(timelapse(), Instant::now());
+        job.frame = 4;
+        let json = serde_json::to_string(&job.capture).unwrap();
+        assert!(json.contains(r#""kind":"timelapse""#));
+        let bookmark = serde_json::to_string(&job.bookmark()).unwrap();
+        assert_eq!(bookmark, r#"{"frame":4,"paused":false}"#);
+
+        let mut job = Job::restore(
+            serde_json::from_str(&json).unwrap(),
+            serde_json::from_str(&bookmark).unwrap(),
+            Instant::now(),
+        );
+        assert!(job.paused);
+        assert_eq!(job.poll(Instant::now()), None);
+        job.resume(Instant::now());
+        assert_eq!(job.poll(Instant::now()), Some(Action::Move((40., 4.))));
+    }
+}
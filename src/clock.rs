@@ -0,0 +1,45 @@
+use {
+    anyhow::anyhow,
+    esp_idf_svc::{
+        sntp::EspSntp,
+        sys::{settimeofday, timeval},
+    },
+    log::info,
+    std::time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Before this, in unix seconds, the clock can't have been set: it starts at
+/// zero on boot.
+const SET_SINCE: f64 = 1_704_067_200.;
+
+/// Keeps the clock set from the network for as long as it's held.
+pub fn start() -> anyhow::Result<EspSntp<'static>> {
+    let sntp = EspSntp::new_default()?;
+    info!("sntp started");
+    Ok(sntp)
+}
+
+/// Unix seconds, once the clock has been set.
+pub fn now() -> Option<f64> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs_f64();
+    (secs >= SET_SINCE).then_some(secs)
+}
+
+/// Sets the clock, for when there's no time server to ask.
+pub fn set(unix: f64) -> anyhow::Result<()> {
+    if !(SET_SINCE..4e9).contains(&unix) {
+        return Err(anyhow!("time must be unix seconds since 2024"));
+    }
+    let time = timeval {
+        tv_sec: unix.trunc() as _,
+        tv_usec: (unix.fract() * 1e6) as _,
+    };
+    if unsafe { settimeofday(&time, std::ptr::null()) } != 0 {
+        return Err(anyhow!("failed to set the clock"));
+    }
+    info!("clock set to {unix:.0}");
+    Ok(())
+}
@@ -1,6 +1,105 @@
-use crate::gcode::Gcode;
+use {
+    crate::{
+        capture::{self, Capture},
+        ephemeris::{self, Body},
+        gcode::{Gcode, GcodeParser},
+        geo::Geo,
+        rotator,
+        satellite::Satellite,
+        timeline::{Control, Timeline},
+    },
+    std::{
+        collections::VecDeque,
+        fmt,
+        sync::{mpsc::Sender, Arc, Mutex},
+    },
+};
 
+pub type CmdQueue = Arc<Mutex<VecDeque<Cmd>>>;
+
+#[derive(Debug, PartialEq)]
 pub enum Cmd {
     ClearCmdQueue,
     ProcessGcode(Gcode),
+    /// Absolute pan & tilt, in degrees. `None` holds an axis.
+    MoveTo(Option<f32>, Option<f32>),
+    /// Relative pan & tilt, in degrees
+    MoveBy(Option<f32>, Option<f32>),
+    /// Continuous pan & tilt velocity, in degrees per second
+    Drive(f32, f32),
+    /// A move at pan & tilt velocities of its own, in degrees per second,
+    /// leaving the configured ones be
+    AtVelocity(f32, f32, Box<Cmd>),
+    /// A command whose sender wants to hear how it went
+    Reply(Box<Cmd>, Done),
+    Stop,
+    /// Stores the current position as a named preset, with an optional speed
+    SetPreset(String, Option<f32>),
+    RecallPreset(String),
+    ClearPreset(String),
+    /// Replaces the timeline, paused at its start. Validated by the sender
+    LoadTimeline(Timeline),
+    Timeline(Control),
+    /// Starts shooting, replacing any capture job. Validated by the sender
+    StartCapture(Capture),
+    Capture(capture::Control),
+    /// Aims at a target, ending any tracking
+    LookAt(Target),
+    /// Follows a target until stopped. Each one replaces the last
+    Track(Target),
+    /// Follows a satellite's passes until stopped, ending any tracking
+    TrackSatellite(Box<Satellite>, rotator::Options),
+    /// Follows the sun or moon until stopped, ending any tracking
+    TrackBody(Body, ephemeris::Options),
+}
+
+/// Answers a `Cmd::Reply` once the command's finished, motion included.
+/// Dropped unanswered if the queue's cleared first.
+pub struct Done(pub Sender<Result<(), String>>);
+
+impl Done {
+    pub fn send(self, res: Result<(), String>) {
+        // the sender may have stopped waiting
+        let _ = self.0.send(res);
+    }
+}
+
+impl fmt::Debug for Done {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Done")
+    }
+}
+
+impl PartialEq for Done {
+    // each is someone waiting on their own command
+    fn eq(&self, _: &Self) -> bool {
+        false
+    }
+}
+
+/// Somewhere to aim.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// A point in the world, as placed by the mount
+    Point([f32; 3]),
+    /// A place on or above the Earth, as seen from the site
+    Geo(Geo),
+}
+
+impl Cmd {
+    /// Parses a command line from a text channel: either g-code or the
+    /// `clear` control command.
+    pub fn of_str(str: &str) -> Result<Cmd, String> {
+        match str.trim() {
+            "clear" => Ok(Cmd::ClearCmdQueue),
+            line => GcodeParser::of_str(line).map(Cmd::ProcessGcode),
+        }
+    }
+}
+
+/// Halts motion as soon as possible, dropping anything still queued.
+pub fn stop(cmds: &CmdQueue) {
+    let mut cmds = cmds.lock().unwrap();
+    cmds.clear();
+    cmds.push_back(Cmd::Stop);
 }
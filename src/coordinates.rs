@@ -0,0 +1,125 @@
+// Work coordinate systems G54 to G59. Each stores the machine position, in
+// degrees, that reads as zero while it's active. Machine coordinates come
+// from homing and are never shifted. G92 shifts whichever system is active
+// on top of that, until cleared or restarted.
+
+use serde::{Deserialize, Serialize};
+
+pub const SYSTEMS: usize = 6;
+/// G-code number of the first system
+const FIRST_SYSTEM: usize = 54;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct WorkOffsets {
+    /// Index of the active system, 0 being G54
+    pub active: usize,
+    pub offsets: [(f32, f32); SYSTEMS],
+    /// Set by G92, in degrees. Not saved
+    #[serde(skip)]
+    pub shift: (f32, f32),
+}
+
+impl WorkOffsets {
+    pub fn offset(&self) -> (f32, f32) {
+        let (pan, tilt) = self.offsets[self.active];
+        (pan + self.shift.0, tilt + self.shift.1)
+    }
+
+    /// The active system's g-code, e.g. `G54`.
+    pub fn name(&self) -> String {
+        format!("G{}", FIRST_SYSTEM + self.active)
+    }
+
+    pub fn select(&mut self, system: usize) -> Result<(), String> {
+        if system >= SYSTEMS {
+            return Err(format!("no work coordinate system {system}"));
+        }
+        self.active = system;
+        Ok(())
+    }
+
+    /// Shifts the active system so `machine` reads as the given work position,
+    /// as G92 does, leaving the stored offsets be. `None` leaves an axis as is.
+    pub fn set_position(&mut self, machine: (f32, f32), pan: Option<f32>, tilt: Option<f32>) {
+        let offset = self.offsets[self.active];
+        if let Some(pan) = pan {
+            self.shift.0 = machine.0 - offset.0 - pan;
+        }
+        if let Some(tilt) = tilt {
+            self.shift.1 = machine.1 - offset.1 - tilt;
+        }
+    }
+
+    /// Sets where a system's zero is in machine coordinates, as G10 L2 does.
+    /// `None` leaves an axis as is.
+    pub fn set_offset(
+        &mut self,
+        system: usize,
+        pan: Option<f32>,
+        tilt: Option<f32>,
+    ) -> Result<(), String> {
+        let offset = (self.offsets.get_mut(system))
+            .ok_or_else(|| format!("no work coordinate system {system}"))?;
+        if let Some(pan) = pan {
+            offset.0 = pan;
+        }
+        if let Some(tilt) = tilt {
+            offset.1 = tilt;
+        }
+        Ok(())
+    }
+
+    pub fn to_work(&self, (pan, tilt): (f32, f32)) -> (f32, f32) {
+        let (pan_offset, tilt_offset) = self.offset();
+        (pan - pan_offset, tilt - tilt_offset)
+    }
+
+    pub fn to_machine(&self, (pan, tilt): (f32, f32)) -> (f32, f32) {
+        let (pan_offset, tilt_offset) = self.offset();
+        (pan + pan_offset, tilt + tilt_offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_position() {
+        let mut offsets = WorkOffsets::default();
+        offsets.set_position((30., -10.), Some(0.), None);
+        assert_eq!(offsets.offset(), (30., 0.));
+        assert_eq!(offsets.to_work((45., -10.)), (15., -10.));
+        assert_eq!(offsets.to_machine((15., -10.)), (45., -10.));
+        // G92 isn't saved
+        assert_eq!(offsets.offsets[0], (0., 0.));
+        let saved: WorkOffsets =
+            serde_json::from_str(&serde_json::to_string(&offsets).unwrap()).unwrap();
+        assert_eq!(saved.offset(), (0., 0.));
+    }
+
+    #[test]
+    fn test_set_offset() {
+        let mut offsets = WorkOffsets::default();
+        offsets.set_offset(1, Some(90.), None).unwrap();
+        assert_eq!(offsets.offsets[1], (90., 0.));
+        offsets.set_position((100., 0.), Some(0.), Some(0.));
+        offsets.select(1).unwrap();
+        // the shift carries over to whichever system is active
+        assert_eq!(offsets.to_work((190., 0.)), (0., 0.));
+        assert!(offsets.set_offset(SYSTEMS, Some(0.), None).is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let mut offsets = WorkOffsets::default();
+        offsets.set_offset(1, Some(90.), Some(0.)).unwrap();
+        offsets.select(1).unwrap();
+        assert_eq!(offsets.name(), "G55");
+        assert_eq!(offsets.to_work((90., 0.)), (0., 0.));
+        offsets.select(0).unwrap();
+        assert_eq!(offsets.to_work((90., 0.)), (90., 0.));
+        assert!(offsets.select(SYSTEMS).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+pub const ARTNET_PORT: u16 = 6454;
+pub const SACN_PORT: u16 = 5568;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ARTNET_OP_DMX: u16 = 0x5000;
+const ARTNET_HEADER_LEN: usize = 18;
+
+const SACN_ID: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const SACN_ROOT_VECTOR: u32 = 0x0000_0004;
+const SACN_FRAMING_VECTOR: u32 = 0x0000_0002;
+const SACN_DMP_VECTOR: u8 = 0x02;
+const SACN_OPTION_PREVIEW: u8 = 0x80;
+const SACN_OPTION_TERMINATED: u8 = 0x40;
+const SACN_HEADER_LEN: usize = 126;
+
+pub const UNIVERSE_SIZE: usize = 512;
+
+/// Control channel values at or above this trigger homing
+pub const CONTROL_HOME: u8 = 200;
+
+#[derive(Debug, PartialEq)]
+pub struct DmxFrame<'a> {
+    pub universe: u16,
+    /// Slot values, starting at channel 1
+    pub data: &'a [u8],
+}
+
+/// Multicast group sACN sources send a universe to.
+pub fn sacn_multicast_addr(universe: u16) -> [u8; 4] {
+    let [hi, lo] = universe.to_be_bytes();
+    [239, 255, hi, lo]
+}
+
+pub fn parse_artnet(buf: &[u8]) -> Option<DmxFrame<'_>> {
+    if buf.len() < ARTNET_HEADER_LEN || &buf[..8] != ARTNET_ID {
+        return None;
+    }
+    if u16::from_le_bytes([buf[8], buf[9]]) != ARTNET_OP_DMX {
+        return None;
+    }
+    let universe = u16::from_le_bytes([buf[14], buf[15]]) & 0x7FFF;
+    let len = usize::from(u16::from_be_bytes([buf[16], buf[17]])).min(UNIVERSE_SIZE);
+    let data = buf.get(ARTNET_HEADER_LEN..ARTNET_HEADER_LEN + len)?;
+    Some(DmxFrame { universe, data })
+}
+
+pub fn parse_sacn(buf: &[u8]) -> Option<DmxFrame<'_>> {
+    if buf.len() < SACN_HEADER_LEN || &buf[4..16] != SACN_ID {
+        return None;
+    }
+    let u32_at = |i: usize| u32::from_be_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+    if u32_at(18) != SACN_ROOT_VECTOR
+        || u32_at(40) != SACN_FRAMING_VECTOR
+        || buf[117] != SACN_DMP_VECTOR
+    {
+        return None;
+    }
+    let options = buf[112];
+    // preview data is for visualizers, and a terminated stream carries no levels
+    if options & (SACN_OPTION_PREVIEW | SACN_OPTION_TERMINATED) != 0 {
+        return None;
+    }
+    let universe = u16::from_be_bytes([buf[113], buf[114]]);
+    // the property count includes the start code
+    let count = usize::from(u16::from_be_bytes([buf[123], buf[124]]));
+    if count == 0 || buf[125] != 0 {
+        return None;
+    }
+    let len = (count - 1).min(UNIVERSE_SIZE);
+    let data = buf.get(SACN_HEADER_LEN..SACN_HEADER_LEN + len)?;
+    Some(DmxFrame { universe, data })
+}
+
+/// Channel layout of the gimbal as a fixture: pan, tilt (each coarse then
+/// fine in 16 bit mode), speed, control.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    /// 1 based start address
+    pub address: u16,
+    pub is_16bit: bool,
+    /// Degrees at channel values 0 and full
+    pub pan_range: (f32, f32),
+    pub tilt_range: (f32, f32),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FixtureState {
+    pub pan: f32,
+    pub tilt: f32,
+    /// Fraction of full speed, `None` at 0 to move at the configured
+    /// velocities
+    pub speed: Option<f32>,
+    pub home: bool,
+}
+
+fn scale(value: f32, (min, max): (f32, f32)) -> f32 {
+    min + value * (max - min)
+}
+
+impl Fixture {
+    pub fn footprint(&self) -> usize {
+        if self.is_16bit {
+            6
+        } else {
+            4
+        }
+    }
+
+    /// `None` when the frame doesn't reach the fixture's channels.
+    pub fn decode(&self, data: &[u8]) -> Option<FixtureState> {
+        let start = usize::from(self.address).checked_sub(1)?;
+        let channels = data.get(start..start + self.footprint())?;
+        let (pan, tilt, rest) = if self.is_16bit {
+            let fine = |i: usize| {
+                f32::from(u16::from_be_bytes([channels[i], channels[i + 1]])) / f32::from(u16::MAX)
+            };
+            (fine(0), fine(2), &channels[4..])
+        } else {
+            let coarse = |i: usize| f32::from(channels[i]) / f32::from(u8::MAX);
+            (coarse(0), coarse(1), &channels[2..])
+        };
+        Some(FixtureState {
+            pan: scale(pan, self.pan_range),
+            tilt: scale(tilt, self.tilt_range),
+            speed: (rest[0] > 0).then(|| f32::from(rest[0]) / f32::from(u8::MAX)),
+            home: rest[1] >= CONTROL_HOME,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(is_16bit: bool) -> Fixture {
+        Fixture {
+            address: 10,
+            is_16bit,
+            pan_range: (-180., 180.),
+            tilt_range: (-90., 90.),
+        }
+    }
+
+    #[test]
+    fn test_artnet() {
+        let mut buf = ARTNET_ID.to_vec();
+        buf.extend([0x00, 0x50, 0, 14, 1, 0, 0x03, 0x00, 0x00, 0x04]);
+        buf.extend([1, 2, 3, 4]);
+        assert_eq!(
+            parse_artnet(&buf),
+            Some(DmxFrame {
+                universe: 3,
+                data: &[1, 2, 3, 4]
+            })
+        );
+        buf[8] = 0x00;
+        buf[9] = 0x20;
+        assert_eq!(parse_artnet(&buf), None);
+    }
+
+    #[test]
+    fn test_sacn() {
+        let mut buf = vec![0; SACN_HEADER_LEN];
+        buf[1] = 0x10;
+        buf[4..16].copy_from_slice(SACN_ID);
+        buf[21] = 0x04;
+        buf[43] = 0x02;
+        buf[113..115].copy_from_slice(&7u16.to_be_bytes());
+        buf[117] = SACN_DMP_VECTOR;
+        buf[123..125].copy_from_slice(&3u16.to_be_bytes());
+        buf.extend([9, 8]);
+        assert_eq!(
+            parse_sacn(&buf),
+            Some(DmxFrame {
+                universe: 7,
+                data: &[9, 8]
+            })
+        );
+        buf[112] = SACN_OPTION_TERMINATED;
+        assert_eq!(parse_sacn(&buf), None);
+        assert_eq!(sacn_multicast_addr(0x0107), [239, 255, 1, 7]);
+    }
+
+    #[test]
+    fn test_fixture_decode() {
+        let mut data = [0; 16];
+        data[9..15].copy_from_slice(&[0x80, 0x00, 0xFF, 0xFF, 0, 255]);
+        let state = fixture(true).decode(&data).unwrap();
+        assert!((state.pan - 0.).abs() < 0.01);
+        assert_eq!(state.tilt, 90.);
+        assert_eq!(state.speed, None);
+        assert!(state.home);
+
+        let state = fixture(false).decode(&data).unwrap();
+        assert!((state.pan - 0.7).abs() < 0.01);
+        assert_eq!(state.tilt, -90.);
+        assert_eq!(state.speed, Some(1.));
+        assert!(state.home);
+
+        assert_eq!(fixture(true).decode(&data[..12]), None);
+    }
+}
@@ -0,0 +1,166 @@
+use {
+    crate::{
+        cmd::{Cmd, CmdQueue},
+        dmx::{self, DmxFrame, Fixture, FixtureState},
+        gcode::Gcode,
+        gimbal::MAX_VELOCITY,
+    },
+    log::{info, warn},
+    std::{
+        net::{Ipv4Addr, UdpSocket},
+        sync::{Arc, Mutex},
+        thread,
+    },
+};
+
+const DMX_UNIVERSE: &str = env!("DMX_UNIVERSE");
+const DMX_ADDRESS: &str = env!("DMX_ADDRESS");
+const DMX_16BIT: &str = env!("DMX_16BIT");
+const DMX_PAN_RANGE: &str = env!("DMX_PAN_RANGE");
+const DMX_TILT_RANGE: &str = env!("DMX_TILT_RANGE");
+
+pub struct DmxConfig {
+    pub universe: u16,
+    pub fixture: Fixture,
+}
+
+fn parse_range(range: &str) -> anyhow::Result<(f32, f32)> {
+    let (min, max) = range
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("expected a \"min,max\" range, got {range}"))?;
+    Ok((min.trim().parse()?, max.trim().parse()?))
+}
+
+impl DmxConfig {
+    /// `None` when no universe is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if DMX_UNIVERSE.is_empty() {
+            return Ok(None);
+        }
+        let fixture = Fixture {
+            address: DMX_ADDRESS.parse()?,
+            is_16bit: DMX_16BIT == "true",
+            pan_range: parse_range(DMX_PAN_RANGE)?,
+            tilt_range: parse_range(DMX_TILT_RANGE)?,
+        };
+        let max_address = dmx::UNIVERSE_SIZE - fixture.footprint() + 1;
+        if !(1..=max_address).contains(&usize::from(fixture.address)) {
+            return Err(anyhow::anyhow!(
+                "DMX_ADDRESS must be within 1..={max_address}"
+            ));
+        }
+        Ok(Some(Self {
+            universe: DMX_UNIVERSE.parse()?,
+            fixture,
+        }))
+    }
+}
+
+/// What was last handed to the command queue, shared by both receivers.
+#[derive(Default)]
+struct Output {
+    target: Option<(f32, f32)>,
+    is_home_held: bool,
+}
+
+struct Receiver {
+    universe: u16,
+    fixture: Fixture,
+    cmds: CmdQueue,
+    output: Mutex<Output>,
+}
+
+impl Receiver {
+    fn apply(&self, state: FixtureState) {
+        let mut output = self.output.lock().unwrap();
+        let mut cmds = self.cmds.lock().unwrap();
+
+        // home on the rising edge of the control channel only, as a desk
+        // keeps resending the held value
+        if state.home && !output.is_home_held {
+            cmds.push_back(Cmd::ProcessGcode(Gcode::G28Home));
+            output.target = None;
+        }
+        output.is_home_held = state.home;
+        if state.home {
+            return;
+        }
+
+        // moves block until done, so only queue the latest look once the
+        // previous one has been picked up. frames keep arriving, so the final
+        // cue still lands.
+        if !cmds.is_empty() {
+            return;
+        }
+        let target = (state.pan, state.tilt);
+        if output.target != Some(target) {
+            let to = Cmd::MoveTo(Some(target.0), Some(target.1));
+            // the speed channel is for this move, not the configured velocities
+            cmds.push_back(match state.speed {
+                Some(speed) => {
+                    let velocity = speed * MAX_VELOCITY;
+                    Cmd::AtVelocity(velocity, velocity, Box::new(to))
+                }
+                None => to,
+            });
+            output.target = Some(target);
+        }
+    }
+
+    fn handle(&self, frame: Option<DmxFrame>) {
+        let Some(frame) = frame.filter(|f| f.universe == self.universe) else {
+            return;
+        };
+        if let Some(state) = self.fixture.decode(frame.data) {
+            self.apply(state);
+        }
+    }
+}
+
+fn spawn(
+    name: &str,
+    socket: UdpSocket,
+    receiver: Arc<Receiver>,
+    parse: fn(&[u8]) -> Option<DmxFrame<'_>>,
+) -> anyhow::Result<()> {
+    let label = name.to_owned();
+    thread::Builder::new()
+        .name(name.into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut buf = [0; 640];
+            loop {
+                match socket.recv_from(&mut buf) {
+                    Ok((n, _)) => receiver.handle(parse(&buf[..n])),
+                    Err(e) => warn!("{label}: {e}"),
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Listens for the configured universe over both Art-Net and sACN.
+pub fn start(config: DmxConfig, cmds: CmdQueue) -> anyhow::Result<()> {
+    let receiver = Arc::new(Receiver {
+        universe: config.universe,
+        fixture: config.fixture,
+        cmds,
+        output: Mutex::new(Output::default()),
+    });
+
+    let artnet = UdpSocket::bind(("0.0.0.0", dmx::ARTNET_PORT))?;
+    spawn("artnet", artnet, receiver.clone(), dmx::parse_artnet)?;
+
+    let sacn = UdpSocket::bind(("0.0.0.0", dmx::SACN_PORT))?;
+    let group = Ipv4Addr::from(dmx::sacn_multicast_addr(config.universe));
+    if let Err(e) = sacn.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED) {
+        warn!("sacn: failed to join {group}, only unicast will be received: {e}");
+    }
+    spawn("sacn", sacn, receiver.clone(), dmx::parse_sacn)?;
+
+    info!(
+        "dmx: listening on universe {} at address {}",
+        receiver.universe, receiver.fixture.address
+    );
+    Ok(())
+}
@@ -0,0 +1,305 @@
+// Where the sun & moon are, from Meeus' "Astronomical Algorithms": the low
+// precision solar theory, good to about 0.01°, & the main terms of the lunar
+// one, good to about 0.1°. Both are taken through the Earth's centred
+// frame to the site, so the moon's parallax comes out of the geometry.
+
+use {
+    crate::{
+        geo::{Look, Site},
+        rotator,
+        satellite::gmst,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+/// Unix seconds at J2000.0, noon on 1 January 2000
+const J2000: f64 = 946_728_000.;
+/// Metres
+const AU: f64 = 149_597_870_700.;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Body {
+    Sun,
+    Moon,
+}
+
+/// Julian centuries since J2000.0.
+fn centuries(unix: f64) -> f64 {
+    (unix - J2000) / 86_400. / 36_525.
+}
+
+fn sin(degrees: f64) -> f64 {
+    degrees.to_radians().sin()
+}
+
+fn cos(degrees: f64) -> f64 {
+    degrees.to_radians().cos()
+}
+
+/// Right ascension & declination in degrees, and distance in metres, from
+/// ecliptic longitude & latitude.
+fn equatorial(t: f64, longitude: f64, latitude: f64, distance: f64) -> [f64; 3] {
+    let obliquity = 23.439_291 - 0.013_004_2 * t;
+    let ra = (sin(longitude) * cos(obliquity) - latitude.to_radians().tan() * sin(obliquity))
+        .atan2(cos(longitude));
+    let dec =
+        (sin(latitude) * cos(obliquity) + cos(latitude) * sin(obliquity) * sin(longitude)).asin();
+    [ra.to_degrees().rem_euclid(360.), dec.to_degrees(), distance]
+}
+
+fn sun(t: f64) -> [f64; 3] {
+    let mean_longitude = 280.466_46 + 36_000.769_83 * t;
+    let anomaly = 357.529_11 + 35_999.050_29 * t;
+    let eccentricity = 0.016_708_634 - 0.000_042_037 * t;
+    let centre = (1.914_602 - 0.004_817 * t) * sin(anomaly)
+        + (0.019_993 - 0.000_101 * t) * sin(2. * anomaly)
+        + 0.000_289 * sin(3. * anomaly);
+    let distance = 1.000_001_018 * (1. - eccentricity * eccentricity)
+        / (1. + eccentricity * cos(anomaly + centre));
+    // aberration & nutation in longitude
+    let node = 125.04 - 1934.136 * t;
+    let longitude = mean_longitude + centre - 0.005_69 - 0.004_78 * sin(node);
+    equatorial(t, longitude, 0., distance * AU)
+}
+
+fn moon(t: f64) -> [f64; 3] {
+    let mean_longitude = 218.316_447_7 + 481_267.881_234_21 * t;
+    let d = 297.850_192_1 + 445_267.111_403_4 * t;
+    let m = 357.529_109_2 + 35_999.050_290_9 * t;
+    let mm = 134.963_396_4 + 477_198.867_505_5 * t;
+    let f = 93.272_095 + 483_202.017_523_3 * t;
+    let longitude = mean_longitude
+        + 6.289 * sin(mm)
+        + 1.274 * sin(2. * d - mm)
+        + 0.658 * sin(2. * d)
+        + 0.214 * sin(2. * mm)
+        - 0.186 * sin(m)
+        - 0.114 * sin(2. * f)
+        + 0.059 * sin(2. * d - 2. * mm)
+        + 0.057 * sin(2. * d - m - mm)
+        + 0.053 * sin(2. * d + mm)
+        + 0.046 * sin(2. * d - m)
+        + 0.041 * sin(mm - m)
+        - 0.035 * sin(d)
+        - 0.030 * sin(mm + m);
+    let latitude = 5.128 * sin(f)
+        + 0.281 * sin(mm + f)
+        + 0.278 * sin(mm - f)
+        + 0.173 * sin(2. * d - f)
+        + 0.055 * sin(2. * d - mm + f)
+        + 0.046 * sin(2. * d - mm - f)
+        + 0.033 * sin(2. * d + f)
+        + 0.017 * sin(2. * mm + f);
+    let km = 385_000.56
+        - 20_905.355 * cos(mm)
+        - 3699.111 * cos(2. * d - mm)
+        - 2955.968 * cos(2. * d)
+        - 569.925 * cos(2. * mm)
+        + 48.888 * cos(m)
+        - 3.149 * cos(2. * f)
+        + 246.158 * cos(2. * d - 2. * mm)
+        - 152.138 * cos(2. * d - m - mm)
+        - 170.733 * cos(2. * d + mm)
+        - 204.586 * cos(2. * d - m)
+        - 129.62 * cos(mm - m)
+        + 108.743 * cos(d)
+        + 104.755 * cos(mm + m);
+    equatorial(t, longitude, latitude, km * 1000.)
+}
+
+/// Degrees the atmosphere lifts something seen at `elevation`, after
+/// Sæmundsson. Near the horizon that's about the sun's width.
+fn refraction(elevation: f64) -> f64 {
+    match elevation < -1. {
+        true => 0.,
+        false => 1.02 / 60. / (elevation + 10.3 / (elevation + 5.11)).to_radians().tan(),
+    }
+}
+
+impl Body {
+    /// Right ascension & declination, in degrees, & distance in metres, at
+    /// unix seconds `unix`.
+    pub fn equatorial(&self, unix: f64) -> [f64; 3] {
+        match self {
+            Body::Sun => sun(centuries(unix)),
+            Body::Moon => moon(centuries(unix)),
+        }
+    }
+
+    /// Where the body's seen from `site` at unix seconds `unix`, lifted by
+    /// refraction.
+    pub fn look(&self, site: &Site, unix: f64) -> Look {
+        let [ra, dec, distance] = self.equatorial(unix);
+        let hour_angle = ra - gmst(unix).to_degrees();
+        let ecef = [
+            distance * cos(dec) * cos(hour_angle),
+            distance * cos(dec) * sin(hour_angle),
+            distance * sin(dec),
+        ];
+        let look = site.look_ecef(ecef);
+        Look {
+            elevation: look.elevation + refraction(look.elevation),
+            ..look
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Options {
+    /// Degrees added to the body's azimuth & elevation, e.g. to keep it just
+    /// out of frame
+    pub offset: [f32; 2],
+    /// Elevation, in degrees, below which the body counts as set
+    pub horizon: f32,
+    /// Pan & tilt to wait at while the body's set, in work coordinates.
+    /// Without it, the body's followed below the horizon too
+    pub park: Option<[f32; 2]>,
+    /// Degrees pan can turn either side of work zero before the cables stop
+    /// it, unwinding a turn when following the body would go past
+    pub wrap: f32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            offset: [0., 0.],
+            horizon: 0.,
+            park: None,
+            wrap: 180.,
+        }
+    }
+}
+
+impl Options {
+    pub fn validate(&self) -> Result<(), String> {
+        let [azimuth, elevation] = self.offset;
+        if !(-180. ..=180.).contains(&azimuth) || !(-90. ..=90.).contains(&elevation) {
+            return Err("offset is ±180° azimuth & ±90° elevation".into());
+        }
+        if !(-10. ..=60.).contains(&self.horizon) {
+            return Err("horizon is -10 to 60 degrees".into());
+        }
+        if !self
+            .park
+            .map_or(true, |[pan, tilt]| pan.is_finite() && tilt.is_finite())
+        {
+            return Err("park must be numbers".into());
+        }
+        rotator::validate_wrap(self.wrap)
+    }
+}
+
+/// Follows the sun or moon across the sky.
+#[derive(Clone, Copy, Debug)]
+pub struct Sky {
+    pub body: Body,
+    pub site: Site,
+    pub options: Options,
+}
+
+/// Reported by `/api/sky`.
+#[derive(Serialize)]
+pub struct Status {
+    pub body: Body,
+    pub look: Look,
+    /// Waiting at the park position for the body to rise
+    pub parked: bool,
+}
+
+impl Sky {
+    pub fn status(&self, unix: f64) -> Status {
+        let look = self.body.look(&self.site, unix);
+        Status {
+            body: self.body,
+            look,
+            parked: self.parks(&look),
+        }
+    }
+
+    fn parks(&self, look: &Look) -> bool {
+        self.options.park.is_some() && look.elevation < f64::from(self.options.horizon)
+    }
+
+    /// Pan & tilt to aim at unix seconds `unix`, with pan at `pan`: at the
+    /// body, offset, or parked while it's set.
+    pub fn aim(&self, unix: f64, pan: f32) -> (f32, f32) {
+        let look = self.body.look(&self.site, unix);
+        if let (true, Some([pan, tilt])) = (self.parks(&look), self.options.park) {
+            return (pan, tilt);
+        }
+        let [azimuth, elevation] = self.options.offset.map(f64::from);
+        let (aim, tilt) = (self.site).pan_tilt(look.azimuth + azimuth, look.elevation + elevation);
+        (rotator::unwind(aim, pan, self.options.wrap), tilt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::geo::Geo};
+
+    fn assert_near(actual: f64, expected: f64, within: f64) {
+        assert!(
+            (actual - expected).abs() < within,
+            "{actual} isn't {expected}"
+        );
+    }
+
+    #[test]
+    fn test_equatorial() {
+        // Meeus' examples 25.a, 1992 October 13, & 47.a, 1992 April 12
+        let [ra, dec, distance] = Body::Sun.equatorial(718_934_400.);
+        assert_near(ra, 198.380_83, 0.01);
+        assert_near(dec, -7.785_07, 0.01);
+        assert_near(distance / AU, 0.997_66, 1e-4);
+
+        let [ra, dec, distance] = Body::Moon.equatorial(703_036_800.);
+        assert_near(ra, 134.688_470, 0.05);
+        assert_near(dec, 13.768_368, 0.05);
+        assert_near(distance / 1000., 368_409.7, 50.);
+    }
+
+    #[test]
+    fn test_sky() {
+        let site = Site {
+            position: Geo {
+                lat: 51.5,
+                lon: 0.,
+                alt: 0.,
+            },
+            heading: 180.,
+            level: 0.,
+        };
+        // midsummer noon, the sun's about due south at 90° - 51.5° + 23.44°,
+        // the clock running a couple of minutes ahead of it
+        let noon = 1_718_971_200.;
+        let look = Body::Sun.look(&site, noon);
+        assert_near(look.azimuth, 179.06, 0.05);
+        assert_near(look.elevation, 61.94, 0.05);
+
+        let sky = Sky {
+            body: Body::Sun,
+            site,
+            options: Options {
+                offset: [0., 5.],
+                horizon: 0.,
+                park: Some([0., -10.]),
+                wrap: 360.,
+            },
+        };
+        let (pan, tilt) = sky.aim(noon, 360.);
+        assert!((pan - 359.06).abs() < 0.05);
+        // past a tighter wrap, it's a turn unwound instead
+        let options = Options {
+            wrap: 180.,
+            ..sky.options
+        };
+        let (pan, _) = Sky { options, ..sky }.aim(noon, 360.);
+        assert!((pan + 0.94).abs() < 0.05);
+        assert!((f64::from(tilt) - 66.94).abs() < 0.05);
+        let midnight = noon + 12. * 3600.;
+        assert_eq!(sky.aim(midnight, 360.), (0., -10.));
+        assert!(sky.status(midnight).parked);
+    }
+}
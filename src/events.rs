@@ -0,0 +1,35 @@
+use {
+    serde::Serialize,
+    std::sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+};
+
+/// Things that happen on the gimbal that remote clients may want pushed to
+/// them, rather than polling `/api/state`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Completed { cmd: String },
+    Homed,
+    Fault { message: String },
+    // answers a report m-code from a channel with no reply path
+    Report { gcode: String, report: String },
+}
+
+static SUBSCRIBERS: Mutex<Vec<Sender<Event>>> = Mutex::new(Vec::new());
+
+pub fn subscribe() -> Receiver<Event> {
+    let (tx, rx) = channel();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+/// Sends the event to every live subscriber, dropping those that hung up.
+pub fn publish(event: Event) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(event.clone()).is_ok());
+}
@@ -0,0 +1,95 @@
+// FreeD D1 camera tracking packets
+
+pub const PACKET_LEN: usize = 29;
+const MESSAGE_D1: u8 = 0xD1;
+// angles are signed 24 bit with 15 fractional bits
+const ANGLE_SCALE: f32 = 32768.;
+// positions are signed 24 bit in 1/64 mm
+const POSITION_SCALE: f32 = 64.;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pose {
+    /// Degrees, wrapped into -180..180
+    pub pan: f32,
+    pub tilt: f32,
+    pub roll: f32,
+    /// Millimeters
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// Raw lens encoder counts
+    pub zoom: u32,
+    pub focus: u32,
+}
+
+fn push_i24(buf: &mut Vec<u8>, value: i32) {
+    buf.extend_from_slice(&value.to_be_bytes()[1..]);
+}
+
+fn push_u24(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.min(0xFF_FFFF).to_be_bytes()[1..]);
+}
+
+fn angle(degrees: f32) -> i32 {
+    let wrapped = (degrees + 180.).rem_euclid(360.) - 180.;
+    (wrapped * ANGLE_SCALE).round() as i32
+}
+
+fn position(mm: f32) -> i32 {
+    // the largest value a signed 24 bit field holds
+    const MAX: f32 = 0x7F_FFFF as f32;
+    (mm * POSITION_SCALE).round().clamp(-MAX, MAX) as i32
+}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0x40u8, |acc, b| acc.wrapping_sub(*b))
+}
+
+pub fn encode(camera_id: u8, pose: &Pose) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(PACKET_LEN);
+    buf.push(MESSAGE_D1);
+    buf.push(camera_id);
+    push_i24(&mut buf, angle(pose.pan));
+    push_i24(&mut buf, angle(pose.tilt));
+    push_i24(&mut buf, angle(pose.roll));
+    push_i24(&mut buf, position(pose.x));
+    push_i24(&mut buf, position(pose.y));
+    push_i24(&mut buf, position(pose.z));
+    push_u24(&mut buf, pose.zoom);
+    push_u24(&mut buf, pose.focus);
+    // spare
+    buf.extend_from_slice(&[0, 0]);
+    buf.push(checksum(&buf));
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let pose = Pose {
+            pan: 90.,
+            tilt: -45.5,
+            zoom: 0x123456,
+            ..Pose::default()
+        };
+        let buf = encode(3, &pose);
+        assert_eq!(buf.len(), PACKET_LEN);
+        assert_eq!(buf[..2], [0xD1, 3]);
+        // 90 * 32768 = 0x2D0000
+        assert_eq!(buf[2..5], [0x2D, 0x00, 0x00]);
+        // -45.5 * 32768 = -0x16C000
+        assert_eq!(buf[5..8], [0xE9, 0x40, 0x00]);
+        assert_eq!(buf[20..23], [0x12, 0x34, 0x56]);
+        let sum = buf.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        assert_eq!(sum, 0x40);
+    }
+
+    #[test]
+    fn test_angles_wrap() {
+        assert_eq!(angle(270.), angle(-90.));
+        assert_eq!(angle(-180.), -180 * 32768);
+    }
+}
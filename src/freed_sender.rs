@@ -0,0 +1,90 @@
+use {
+    crate::{
+        freed::{self, Pose},
+        gimbal::Position,
+    },
+    log::{info, warn},
+    std::{
+        net::{SocketAddr, ToSocketAddrs, UdpSocket},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+const FREED_TARGET: &str = env!("FREED_TARGET");
+const FREED_CAMERA_ID: &str = env!("FREED_CAMERA_ID");
+const FREED_RATE_HZ: &str = env!("FREED_RATE_HZ");
+
+pub struct FreedConfig {
+    pub target: SocketAddr,
+    pub camera_id: u8,
+    pub interval: Duration,
+}
+
+impl FreedConfig {
+    /// `None` when no target is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if FREED_TARGET.is_empty() {
+            return Ok(None);
+        }
+        let target = FREED_TARGET
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("FREED_TARGET {FREED_TARGET} did not resolve"))?;
+        let rate: u32 = FREED_RATE_HZ.parse()?;
+        if !(1..=1000).contains(&rate) {
+            return Err(anyhow::anyhow!("FREED_RATE_HZ must be within 1..=1000"));
+        }
+        Ok(Some(Self {
+            target,
+            camera_id: FREED_CAMERA_ID.parse()?,
+            interval: Duration::from_secs(1) / rate,
+        }))
+    }
+}
+
+/// Streams the tracked pan & tilt to a render engine at a fixed rate.
+pub fn start(config: FreedConfig, position: Arc<Position>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(config.target)?;
+    info!(
+        "freed: sending camera {} to {} every {:?}",
+        config.camera_id, config.target, config.interval
+    );
+
+    thread::Builder::new()
+        .name("freed".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut next = Instant::now();
+            let mut is_failing = false;
+            loop {
+                let (pan, tilt) = position.degrees();
+                let pose = Pose {
+                    pan,
+                    tilt,
+                    ..Pose::default()
+                };
+                match socket.send(&freed::encode(config.camera_id, &pose)) {
+                    Ok(_) => is_failing = false,
+                    // log once per outage rather than at the packet rate
+                    Err(e) if !is_failing => {
+                        warn!("freed: send failed: {e}");
+                        is_failing = true;
+                    }
+                    Err(_) => {}
+                }
+
+                // schedule from the previous deadline so the rate doesn't drift
+                next += config.interval;
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(next - now);
+                } else {
+                    next = now;
+                }
+            }
+        })?;
+    Ok(())
+}
@@ -1,17 +1,42 @@
+use crate::gimbal;
+
 #[derive(Debug, PartialEq)]
 pub enum Gcode {
     // G1 P100 T200
     G1Move(Option<f32>, Option<f32>),
+    // G10 L2 S2 P30 T0, puts G55's zero at machine pan 30, tilt 0. S1 to S6
+    // are G54 to G59, as P1 to P6 are elsewhere, P being pan here
+    G10SetWorkOffset(usize, Option<f32>, Option<f32>),
     // G28
     G28Home,
+    // G54, the first work coordinate system, through G59
+    G54SelectWorkOffset(usize),
     // G90
     G90SetAbsolute,
     // G91
     G91SetRelative,
+    // G92 P0 T0
+    G92SetPosition(Option<f32>, Option<f32>),
+    // G92.1
+    G92_1ClearPosition,
     // M1 P0.5
     // M1 T1.5
     // M1 T1.5 P20
     M1SetVelocity(Option<f32>, Option<f32>),
+    // M70 stage, stores the current position as a named preset
+    M70SavePreset(String),
+    // M71 stage
+    M71RecallPreset(String),
+    // M72 stage
+    M72DeletePreset(String),
+    // M114
+    M114ReportPosition,
+    // M115
+    M115ReportFirmware,
+    // M119
+    M119ReportEndstops,
+    // M240, fires the camera shutter
+    M240TriggerCamera,
 }
 
 pub fn invalid_gcode(str: &str) -> String {
@@ -34,12 +59,22 @@ pub struct GcodeParser;
 
 impl GcodeParser {
     pub fn of_str(str: &str) -> Result<Gcode, String> {
+        // preset commands take a name rather than numeric words
+        if let Some((code, name)) = str.trim().split_once(char::is_whitespace) {
+            let name = name.trim().to_owned();
+            match code {
+                "M70" => return Ok(Gcode::M70SavePreset(name)),
+                "M71" => return Ok(Gcode::M71RecallPreset(name)),
+                "M72" => return Ok(Gcode::M72DeletePreset(name)),
+                _ => {}
+            }
+        }
         let mut parts = str.split_whitespace().try_fold(vec![], |mut acc, part| {
             let mut chars = part.chars();
             let start_char = chars.next();
             let num = chars.as_str().parse::<f32>();
             match (start_char, num) {
-                (Some('G') | Some('M') | Some('T') | Some('P'), Ok(num)) => {
+                (Some('G' | 'M' | 'T' | 'P' | 'L' | 'S'), Ok(num)) => {
                     acc.push((start_char, num));
                     Ok(acc)
                 }
@@ -63,13 +98,41 @@ impl GcodeParser {
                 let (pan, tilt) = get_pan_tilt_floats(&parts);
                 Ok(Gcode::G1Move(pan, tilt))
             }
+            (Some('G'), 10) => {
+                let word = |letter| {
+                    (parts.iter())
+                        .find(|(char_opt, _)| *char_opt == Some(letter))
+                        .map(|(_, n)| *n)
+                };
+                match (word('L'), word('S')) {
+                    (Some(l), Some(system)) if l == 2. && (1. ..=6.).contains(&system) => {
+                        let (pan, tilt) = get_pan_tilt_floats(&parts);
+                        Ok(Gcode::G10SetWorkOffset(system as usize - 1, pan, tilt))
+                    }
+                    _ => Err(invalid_gcode(str)),
+                }
+            }
             (Some('G'), 28) => Ok(Gcode::G28Home),
+            (Some('G'), code @ 54..=59) => Ok(Gcode::G54SelectWorkOffset(code as usize - 54)),
             (Some('G'), 90) => Ok(Gcode::G90SetAbsolute),
             (Some('G'), 91) => Ok(Gcode::G91SetRelative),
+            (Some('G'), 92) if fist_num == 92.1 => Ok(Gcode::G92_1ClearPosition),
+            (Some('G'), 92) => {
+                let (pan, tilt) = get_pan_tilt_floats(&parts);
+                Ok(Gcode::G92SetPosition(pan, tilt))
+            }
             (Some('M'), 1) => {
                 let (pan, tilt) = get_pan_tilt_floats(&parts);
+                // so every channel answers with an error, not ok
+                for velocity in [pan, tilt].into_iter().flatten() {
+                    gimbal::validate_velocity(velocity)?;
+                }
                 Ok(Gcode::M1SetVelocity(pan, tilt))
             }
+            (Some('M'), 114) => Ok(Gcode::M114ReportPosition),
+            (Some('M'), 115) => Ok(Gcode::M115ReportFirmware),
+            (Some('M'), 119) => Ok(Gcode::M119ReportEndstops),
+            (Some('M'), 240) => Ok(Gcode::M240TriggerCamera),
             _ => Err(invalid_gcode(str)),
         }
     }
@@ -91,9 +154,43 @@ mod tests {
         assert_eq!(gcode, Gcode::G28Home);
     }
 
+    #[test]
+    fn test_work_offsets() {
+        assert_eq!(
+            GcodeParser::of_str("G92 P0").unwrap(),
+            Gcode::G92SetPosition(Some(0.), None)
+        );
+        assert_eq!(
+            GcodeParser::of_str("G56").unwrap(),
+            Gcode::G54SelectWorkOffset(2)
+        );
+        assert!(GcodeParser::of_str("G60").is_err());
+        assert_eq!(
+            GcodeParser::of_str("G10 L2 S2 P30").unwrap(),
+            Gcode::G10SetWorkOffset(1, Some(30.), None)
+        );
+        assert!(GcodeParser::of_str("G10 L2 S7 P30").is_err());
+        assert!(GcodeParser::of_str("G10 L20 S1 P30").is_err());
+        assert_eq!(
+            GcodeParser::of_str("G92.1").unwrap(),
+            Gcode::G92_1ClearPosition
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            GcodeParser::of_str("M71  stage-left ").unwrap(),
+            Gcode::M71RecallPreset("stage-left".into())
+        );
+        assert!(GcodeParser::of_str("M70").is_err());
+    }
+
     #[test]
     fn test_m1_set_velocity() {
-        let gcode = GcodeParser::of_str("M1 T2000.1  P1000").unwrap();
-        assert_eq!(gcode, Gcode::M1SetVelocity(Some(1000.0), Some(2000.1)));
+        let gcode = GcodeParser::of_str("M1 T20.1  P10").unwrap();
+        assert_eq!(gcode, Gcode::M1SetVelocity(Some(10.0), Some(20.1)));
+        assert!(GcodeParser::of_str("M1 P1000").is_err());
+        assert!(GcodeParser::of_str("M1 T0").is_err());
     }
 }
@@ -0,0 +1,157 @@
+use {
+    crate::{
+        cmd::CmdQueue,
+        events::{self, Event},
+        gcode_session::GcodeSession,
+        gcode_stream::{self, LineReader},
+        gimbal::Gimbal,
+    },
+    log::{info, warn},
+    std::{
+        io::{ErrorKind, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    },
+};
+
+const GCODE_TCP_PORT: &str = env!("GCODE_TCP_PORT");
+
+const MAX_CLIENTS: usize = 3;
+const MAX_LINE_LEN: usize = 96;
+/// How often a client waiting on input checks for events to push
+const EVENT_POLL: Duration = Duration::from_millis(100);
+
+/// `None` when no port is configured.
+pub fn port_from_env() -> anyhow::Result<Option<u16>> {
+    match GCODE_TCP_PORT {
+        "" => Ok(None),
+        port => Ok(Some(port.parse()?)),
+    }
+}
+
+/// Connected clients & which of them may move the gimbal. The others can
+/// still query it.
+#[derive(Default)]
+struct Control {
+    clients: usize,
+    controller: Option<u32>,
+}
+
+impl Control {
+    /// Takes control for `id` if nobody holds it, returning whether `id` has it.
+    fn claim(&mut self, id: u32) -> bool {
+        *self.controller.get_or_insert(id) == id
+    }
+
+    fn leave(&mut self, id: u32) {
+        self.clients -= 1;
+        if self.controller == Some(id) {
+            self.controller = None;
+        }
+    }
+}
+
+fn event_line(event: &Event) -> Option<String> {
+    match event {
+        Event::Homed => Some("echo:homed\n".into()),
+        Event::Fault { message } => Some(format!("Error:{message}. restart required\n")),
+        Event::Completed { .. } | Event::Report { .. } => None,
+    }
+}
+
+fn serve(
+    stream: &TcpStream,
+    id: u32,
+    mut session: GcodeSession,
+    control: &Mutex<Control>,
+) -> anyhow::Result<()> {
+    // reads time out so events can be pushed to an idle client
+    stream.set_read_timeout(Some(EVENT_POLL))?;
+    let (mut input, mut output) = (stream, stream);
+    let events = events::subscribe();
+    let role = if control.lock().unwrap().claim(id) {
+        "controller"
+    } else {
+        "observer"
+    };
+    output.write_all(format!("start\necho:connected as {role}\n").as_bytes())?;
+
+    let mut lines = LineReader::new(MAX_LINE_LEN);
+    let mut buf = [0; 64];
+    loop {
+        for line in events.try_iter().filter_map(|e| event_line(&e)) {
+            output.write_all(line.as_bytes())?;
+        }
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for received in buf[..n].iter().filter_map(|b| lines.push(*b)) {
+            let response = match received {
+                Ok(line) => {
+                    // control passes to whoever sends next once the controller leaves
+                    let may_queue = control.lock().unwrap().claim(id);
+                    session.handle(&line, may_queue, || {
+                        let _ = output.write_all(gcode_stream::BUSY.as_bytes());
+                    })
+                }
+                Err(response) => response,
+            };
+            output.write_all(response.as_bytes())?;
+        }
+    }
+}
+
+/// Streams g-code over tcp, one `ok` per line like the serial console. Only
+/// one client at a time may move the gimbal.
+pub fn start(port: u16, cmds: CmdQueue, gimbal_arc: Arc<Mutex<Gimbal>>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    info!("gcode: listening on tcp {port}");
+    let control = Arc::new(Mutex::new(Control::default()));
+
+    thread::Builder::new()
+        .name("gcode-tcp".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut next_id: u32 = 0;
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("gcode: accept failed: {e}");
+                        continue;
+                    }
+                };
+                {
+                    let mut control = control.lock().unwrap();
+                    if control.clients >= MAX_CLIENTS {
+                        let _ = stream.write_all(b"Error:too many clients\n");
+                        continue;
+                    }
+                    control.clients += 1;
+                }
+                next_id = next_id.wrapping_add(1);
+                let id = next_id;
+                let session = GcodeSession::new(cmds.clone(), gimbal_arc.clone());
+                let client_control = control.clone();
+                let spawned = thread::Builder::new()
+                    .name("gcode-client".into())
+                    .stack_size(6144)
+                    .spawn(move || {
+                        if let Err(e) = serve(&stream, id, session, &client_control) {
+                            warn!("gcode: client dropped: {e}");
+                        }
+                        client_control.lock().unwrap().leave(id);
+                    });
+                if let Err(e) = spawned {
+                    warn!("gcode: failed to spawn client thread: {e}");
+                    control.lock().unwrap().clients -= 1;
+                }
+            }
+        })?;
+    Ok(())
+}
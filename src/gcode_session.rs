@@ -0,0 +1,128 @@
+use {
+    crate::{
+        cmd::{Cmd, CmdQueue},
+        gcode::Gcode,
+        gcode_stream::{self, LineNumbers},
+        gimbal::{Endstops, Gimbal, Position},
+    },
+    std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// Lines are acknowledged only while fewer than this many commands are
+/// queued, so senders that wait for `ok` never overrun the queue.
+const MAX_QUEUED: usize = 8;
+const BUSY_INTERVAL: Duration = Duration::from_secs(2);
+const QUEUE_POLL: Duration = Duration::from_millis(20);
+
+/// Answers the report m-codes without queueing them, so they work while the
+/// gimbal is locked mid move.
+#[derive(Clone)]
+pub struct Reporter {
+    position: Arc<Position>,
+    endstops: Endstops,
+}
+
+impl Reporter {
+    pub fn new(gimbal: &Gimbal) -> Self {
+        Self {
+            position: gimbal.position(),
+            endstops: gimbal.endstops(),
+        }
+    }
+
+    /// The report text, or `None` when `gcode` isn't a report.
+    pub fn report(&self, gcode: &Gcode) -> Option<String> {
+        match gcode {
+            Gcode::M114ReportPosition => Some(gcode_stream::report_position(
+                self.position.degrees(),
+                self.position.steps(),
+                self.position.machine_degrees(),
+            )),
+            Gcode::M115ReportFirmware => {
+                Some(gcode_stream::report_firmware(env!("CARGO_PKG_VERSION")))
+            }
+            Gcode::M119ReportEndstops => Some(gcode_stream::report_endstops(
+                self.endstops.triggered(),
+                self.endstops.raw(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// One host streaming g-code, over serial or tcp.
+pub struct GcodeSession {
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+    reporter: Reporter,
+    lines: LineNumbers,
+}
+
+impl GcodeSession {
+    pub fn new(cmds: CmdQueue, gimbal_arc: Arc<Mutex<Gimbal>>) -> Self {
+        let reporter = Reporter::new(&gimbal_arc.lock().unwrap());
+        Self {
+            cmds,
+            gimbal_arc,
+            reporter,
+            lines: LineNumbers::default(),
+        }
+    }
+
+    /// Holds off acknowledging until the queue has room, calling `busy`
+    /// periodically so the sender doesn't time out meanwhile.
+    fn wait_for_space(&self, mut busy: impl FnMut()) {
+        let mut last_busy = Instant::now();
+        while self.cmds.lock().unwrap().len() >= MAX_QUEUED {
+            if last_busy.elapsed() >= BUSY_INTERVAL {
+                busy();
+                last_busy = Instant::now();
+            }
+            thread::sleep(QUEUE_POLL);
+        }
+    }
+
+    fn fault(&self) -> Option<String> {
+        // a busy lock means a move is running, which can't be faulted
+        let gimbal = self.gimbal_arc.try_lock().ok()?;
+        gimbal.last_error_message.clone()
+    }
+
+    /// Answers one received line. Reports are always answered, other commands
+    /// are queued only when `may_queue`.
+    pub fn handle(&mut self, line: &str, may_queue: bool, busy: impl FnMut()) -> String {
+        let line = gcode_stream::strip_comment(line);
+        if line.is_empty() {
+            return gcode_stream::OK.into();
+        }
+        let code = match self.lines.check(line) {
+            Ok("") => return gcode_stream::OK.into(),
+            Ok(code) => code,
+            Err(resend) => return resend.response(),
+        };
+        let cmd = match Cmd::of_str(code) {
+            Ok(cmd) => cmd,
+            Err(e) => return gcode_stream::error(&e),
+        };
+
+        // reports are answered straight away rather than queued behind moves
+        if let Cmd::ProcessGcode(gcode) = &cmd {
+            if let Some(report) = self.reporter.report(gcode) {
+                return report + gcode_stream::OK;
+            }
+        }
+        if !may_queue {
+            return gcode_stream::error("another client has control");
+        }
+        if let Some(fault) = self.fault() {
+            return gcode_stream::error(&format!("{fault}. restart required"));
+        }
+        self.wait_for_space(busy);
+        self.cmds.lock().unwrap().push_back(cmd);
+        gcode_stream::OK.into()
+    }
+}
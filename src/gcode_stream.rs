@@ -0,0 +1,257 @@
+// Marlin style line protocol for streaming g-code: every line is answered
+// with one `ok`, preceded by `Error:` when it was rejected. Senders may
+// number lines and append checksums, `N12 G1 P10*87`.
+
+pub const OK: &str = "ok\n";
+pub const BUSY: &str = "echo:busy: processing\n";
+
+/// Advertised by M115, for hosts that adapt to the firmware
+const CAPABILITIES: &[(&str, bool)] = &[
+    ("BUSY_PROTOCOL", true),
+    ("LINE_NUMBERS", true),
+    ("HOMING", true),
+    ("ENDSTOPS", true),
+    ("PRESETS", true),
+    ("CONTINUOUS_DRIVE", true),
+];
+
+/// A rejected line that the sender must resend.
+#[derive(Debug, PartialEq)]
+pub struct Resend {
+    pub line: u32,
+    pub reason: &'static str,
+}
+
+impl Resend {
+    pub fn response(&self) -> String {
+        format!(
+            "Error:{}, Last Line: {}\nResend: {}\n{OK}",
+            self.reason,
+            self.line.saturating_sub(1),
+            self.line
+        )
+    }
+}
+
+pub fn error(message: &str) -> String {
+    format!("Error:{message}\n{OK}")
+}
+
+/// Drops `;` comments and surrounding whitespace.
+pub fn strip_comment(line: &str) -> &str {
+    line.split(';').next().unwrap_or("").trim()
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |acc, b| acc ^ b)
+}
+
+/// Tracks line numbers across a stream.
+#[derive(Default)]
+pub struct LineNumbers {
+    last: u32,
+}
+
+impl LineNumbers {
+    /// Checks a comment-free line, returning the g-code it carries.
+    pub fn check<'a>(&mut self, line: &'a str) -> Result<&'a str, Resend> {
+        let expected = self.last + 1;
+        let resend = |reason| Resend {
+            line: expected,
+            reason,
+        };
+        let (number, code) = if line.starts_with('N') {
+            let (body, sum) = line
+                .rsplit_once('*')
+                .ok_or_else(|| resend("No Checksum with line number"))?;
+            let sum: u8 = sum
+                .trim()
+                .parse()
+                .map_err(|_| resend("checksum mismatch"))?;
+            if checksum(body.as_bytes()) != sum {
+                return Err(resend("checksum mismatch"));
+            }
+            let (number, code) = body[1..].split_once(' ').unwrap_or((&body[1..], ""));
+            let number: u32 = number
+                .parse()
+                .map_err(|_| resend("Line Number is not Last Line Number+1"))?;
+            (Some(number), code.trim())
+        } else {
+            (None, line)
+        };
+
+        // M110 sets the current line number rather than checking it
+        if code.starts_with("M110") {
+            self.last = code
+                .split_whitespace()
+                .find_map(|word| word.strip_prefix('N')?.parse().ok())
+                .or(number)
+                .unwrap_or(0);
+            return Ok("");
+        }
+        if let Some(number) = number {
+            if number != expected {
+                return Err(resend("Line Number is not Last Line Number+1"));
+            }
+            self.last = number;
+        }
+        Ok(code)
+    }
+}
+
+/// Splits received bytes into lines.
+pub struct LineReader {
+    line: String,
+    max_len: usize,
+    /// set when a line outgrew `max_len`, so its tail is dropped too
+    overflowed: bool,
+}
+
+impl LineReader {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            line: String::new(),
+            max_len,
+            overflowed: false,
+        }
+    }
+
+    /// Feeds one byte, returning a completed non-blank line, or the error
+    /// response for an overlong one.
+    pub fn push(&mut self, byte: u8) -> Option<Result<String, String>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = std::mem::take(&mut self.line);
+                if std::mem::take(&mut self.overflowed) {
+                    Some(Err(error("line too long")))
+                } else if line.trim().is_empty() {
+                    None
+                } else {
+                    Some(Ok(line))
+                }
+            }
+            _ if self.line.len() >= self.max_len => {
+                self.overflowed = true;
+                None
+            }
+            byte => {
+                self.line.push(char::from(byte));
+                None
+            }
+        }
+    }
+}
+
+/// The M114 report: work degrees, then machine steps & degrees.
+pub fn report_position(
+    (pan, tilt): (f32, f32),
+    (pan_steps, tilt_steps): (i32, i32),
+    (machine_pan, machine_tilt): (f32, f32),
+) -> String {
+    format!(
+        "P:{pan:.2} T:{tilt:.2} Count P:{pan_steps} T:{tilt_steps} \
+         Machine P:{machine_pan:.2} T:{machine_tilt:.2}\n"
+    )
+}
+
+/// The M115 report.
+pub fn report_firmware(version: &str) -> String {
+    let mut report = format!(
+        "FIRMWARE_NAME:gimbal-motion {version} FIRMWARE_VERSION:{version} \
+         PROTOCOL_VERSION:1.0 MACHINE_TYPE:pan-tilt AXIS_COUNT:2\n"
+    );
+    for (capability, supported) in CAPABILITIES {
+        report += &format!("Cap:{capability}:{}\n", u8::from(*supported));
+    }
+    report
+}
+
+/// The M119 report, debounced with the raw reading alongside.
+pub fn report_endstops((pan, tilt): (bool, bool), (pan_raw, tilt_raw): (bool, bool)) -> String {
+    let state = |triggered| if triggered { "TRIGGERED" } else { "open" };
+    format!(
+        "Reporting endstop status\npan_min: {} raw:{}\ntilt_min: {} raw:{}\n",
+        state(pan),
+        state(pan_raw),
+        state(tilt),
+        state(tilt_raw)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(line: &str) -> String {
+        format!("{line}*{}", checksum(line.as_bytes()))
+    }
+
+    #[test]
+    fn test_plain_lines() {
+        let mut lines = LineNumbers::default();
+        assert_eq!(strip_comment("  G1 P10 ; pan a bit"), "G1 P10");
+        assert_eq!(lines.check("G28"), Ok("G28"));
+        assert_eq!(lines.check("M110 N7"), Ok(""));
+        assert_eq!(lines.check(&numbered("N8 G28")), Ok("G28"));
+    }
+
+    #[test]
+    fn test_numbered_lines() {
+        let mut lines = LineNumbers::default();
+        assert_eq!(lines.check(&numbered("N0 M110 N0")), Ok(""));
+        assert_eq!(lines.check(&numbered("N1 G28")), Ok("G28"));
+        assert_eq!(
+            lines.check(&numbered("N3 G1 P10")),
+            Err(Resend {
+                line: 2,
+                reason: "Line Number is not Last Line Number+1"
+            })
+        );
+        assert_eq!(
+            lines.check("N2 G1 P10*1"),
+            Err(Resend {
+                line: 2,
+                reason: "checksum mismatch"
+            })
+        );
+        assert_eq!(lines.check(&numbered("N2 G1 P10")), Ok("G1 P10"));
+    }
+
+    #[test]
+    fn test_line_reader() {
+        let mut reader = LineReader::new(8);
+        let lines: Vec<_> = b"G28\r\n\nG1 P10 T20 ; long\nM114\n"
+            .iter()
+            .filter_map(|b| reader.push(*b))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                Ok("G28".into()),
+                Err("Error:line too long\nok\n".into()),
+                Ok("M114".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_responses() {
+        assert_eq!(
+            Resend {
+                line: 5,
+                reason: "checksum mismatch"
+            }
+            .response(),
+            "Error:checksum mismatch, Last Line: 4\nResend: 5\nok\n"
+        );
+        assert_eq!(
+            report_position((10., -2.5), (356, -64), (20., -2.5)),
+            "P:10.00 T:-2.50 Count P:356 T:-64 Machine P:20.00 T:-2.50\n"
+        );
+        assert!(report_endstops((true, false), (true, true))
+            .ends_with("pan_min: TRIGGERED raw:TRIGGERED\ntilt_min: open raw:TRIGGERED\n"));
+        let firmware = report_firmware("0.1.0");
+        assert!(firmware.starts_with("FIRMWARE_NAME:gimbal-motion 0.1.0 FIRMWARE_VERSION:0.1.0 "));
+        assert!(firmware.contains("\nCap:BUSY_PROTOCOL:1\n"));
+    }
+}
@@ -0,0 +1,182 @@
+// Pointing at places on or above the Earth, e.g. antennas, landmarks or
+// aircraft. Positions are taken to the WGS84 ellipsoid's centred frame and
+// back out in the site's local east, north & up, so long sight lines dip below
+// the horizon as the Earth curves away.
+
+use {
+    crate::kinematics,
+    serde::{Deserialize, Serialize},
+};
+
+/// WGS84 semi-major axis, in metres
+const A: f64 = 6_378_137.;
+/// WGS84 flattening
+const F: f64 = 1. / 298.257_223_563;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Geo {
+    /// Degrees, north & east positive
+    pub lat: f64,
+    pub lon: f64,
+    /// Metres above the ellipsoid, or sea level for nearby targets
+    #[serde(default)]
+    pub alt: f64,
+}
+
+impl Geo {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-90. ..=90.).contains(&self.lat) || !(-180. ..=180.).contains(&self.lon) {
+            return Err("latitude is -90 to 90 degrees, longitude -180 to 180".into());
+        }
+        if !self.alt.is_finite() {
+            return Err("altitude must be a number".into());
+        }
+        Ok(())
+    }
+
+    /// Metres from the Earth's centre: x through lat & lon 0, z through the
+    /// north pole.
+    pub fn ecef(&self) -> [f64; 3] {
+        let e2 = F * (2. - F);
+        let (sin_lat, cos_lat) = self.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.lon.to_radians().sin_cos();
+        let n = A / (1. - e2 * sin_lat * sin_lat).sqrt();
+        [
+            (n + self.alt) * cos_lat * cos_lon,
+            (n + self.alt) * cos_lat * sin_lon,
+            (n * (1. - e2) + self.alt) * sin_lat,
+        ]
+    }
+}
+
+/// Where the gimbal stands and which way it faces.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Site {
+    #[serde(flatten)]
+    pub position: Geo,
+    /// Azimuth pan zero faces, in degrees clockwise from true north
+    #[serde(default)]
+    pub heading: f32,
+    /// Elevation tilt zero faces, in degrees above the horizon
+    #[serde(default)]
+    pub level: f32,
+}
+
+/// A direction from the site.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Look {
+    /// Degrees clockwise from true north, 0 to 360
+    pub azimuth: f64,
+    /// Degrees above the horizon
+    pub elevation: f64,
+    /// Metres in a straight line
+    pub range: f64,
+}
+
+impl Site {
+    pub fn validate(&self) -> Result<(), String> {
+        self.position.validate()?;
+        if !self.heading.is_finite() || !self.level.is_finite() {
+            return Err("heading & level must be numbers".into());
+        }
+        Ok(())
+    }
+
+    /// The direction to a point `ecef` metres from the Earth's centre.
+    pub fn look_ecef(&self, ecef: [f64; 3]) -> Look {
+        let site = self.position.ecef();
+        let [dx, dy, dz] = [ecef[0] - site[0], ecef[1] - site[1], ecef[2] - site[2]];
+        let (sin_lat, cos_lat) = self.position.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.position.lon.to_radians().sin_cos();
+        let east = -sin_lon * dx + cos_lon * dy;
+        let north = -sin_lat * cos_lon * dx - sin_lat * sin_lon * dy + cos_lat * dz;
+        let up = cos_lat * cos_lon * dx + cos_lat * sin_lon * dy + sin_lat * dz;
+        Look {
+            azimuth: east.atan2(north).to_degrees().rem_euclid(360.),
+            elevation: up.atan2(east.hypot(north)).to_degrees(),
+            range: (dx * dx + dy * dy + dz * dz).sqrt(),
+        }
+    }
+
+    pub fn look(&self, target: &Geo) -> Look {
+        self.look_ecef(target.ecef())
+    }
+
+    /// Pan & tilt, in degrees, facing `azimuth` & `elevation`. Pan is within
+    /// ±180°.
+    pub fn pan_tilt(&self, azimuth: f64, elevation: f64) -> (f32, f32) {
+        let pan = kinematics::wrap(azimuth as f32 - self.heading);
+        (pan, elevation as f32 - self.level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(lat: f64, lon: f64, alt: f64) -> Site {
+        Site {
+            position: Geo { lat, lon, alt },
+            heading: 0.,
+            level: 0.,
+        }
+    }
+
+    #[test]
+    fn test_look() {
+        // 10km due north drops about 8m below the horizon
+        let look = site(45., 0., 0.).look(&Geo {
+            lat: 45. + 10_000. / 111_132.,
+            lon: 0.,
+            alt: 0.,
+        });
+        assert!(look.azimuth.abs() < 1e-6 || (look.azimuth - 360.).abs() < 1e-6);
+        assert!((look.elevation + 0.045).abs() < 1e-3);
+        assert!((look.range - 10_000.).abs() < 1.);
+
+        let look = site(0., 0., 0.).look(&Geo {
+            lat: 0.,
+            lon: 0.1,
+            alt: 0.,
+        });
+        assert!((look.azimuth - 90.).abs() < 1e-6);
+
+        let overhead = site(10., 20., 50.).look(&Geo {
+            lat: 10.,
+            lon: 20.,
+            alt: 1050.,
+        });
+        assert!((overhead.elevation - 90.).abs() < 1e-6);
+        assert!((overhead.range - 1000.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_aircraft() {
+        let look = site(47.6, -122.3, 50.).look(&Geo {
+            lat: 47.7,
+            lon: -122.2,
+            alt: 11_000.,
+        });
+        assert!((look.azimuth - 34.011).abs() < 1e-3);
+        assert!((look.elevation - 39.131).abs() < 1e-3);
+        assert!((look.range - 17_328.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_pan_tilt() {
+        let site = Site {
+            heading: 350.,
+            level: 2.,
+            ..site(0., 0., 0.)
+        };
+        assert_eq!(site.pan_tilt(10., 12.), (20., 10.));
+        assert_eq!(site.pan_tilt(340., 0.), (-10., -2.));
+        assert!(Geo {
+            lat: 91.,
+            lon: 0.,
+            alt: 0.
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -1,8 +1,12 @@
 use {
-    serde::Serialize,
+    serde::{Serialize, Serializer},
     std::{
         num::NonZeroU32,
-        sync::{Arc, Mutex},
+        sync::{
+            atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
     },
 };
 
@@ -10,15 +14,58 @@ use {anyhow::anyhow, libm::floorf};
 
 use derive_more::Display;
 
-use log::info;
+use log::{info, warn};
 
-use esp_idf_svc::hal::{
-    delay::Delay,
-    gpio::{Level, Pull},
-    task::notification::Notification,
+use esp_idf_svc::{
+    hal::{
+        delay::Delay,
+        gpio::{Level, Pull},
+        task::notification::Notification,
+    },
+    sys::gpio_get_level,
+};
+
+use crate::{
+    capture::{self, Action, Bookmark, Capture, Job},
+    clock,
+    cmd::{Cmd, Target},
+    coordinates::WorkOffsets,
+    ephemeris::{self, Body, Sky},
+    gcode::Gcode,
+    geo::Site,
+    gimbal_pins::GimbalPins,
+    kinematics::Mount,
+    motor::steps_per_degree,
+    mv::Move,
+    presets::{self, Preset, Presets},
+    rotator::{self, Rotator},
+    satellite::Satellite,
+    shutter::Shutter,
+    timeline::{self, Control, Limits, Playback, Timeline},
+};
+
+/// Fastest either axis is asked to turn, in degrees per second.
+pub const MAX_VELOCITY: f32 = 90.;
+/// Hardest either axis is asked to speed up or slow down, in degrees per
+/// second per second.
+pub const MAX_ACCELERATION: f32 = 500.;
+pub const AXIS_LIMITS: Limits = Limits {
+    velocity: MAX_VELOCITY,
+    acceleration: MAX_ACCELERATION,
 };
 
-use crate::{gcode::Gcode, gimbal_pins::GimbalPins, motor::steps_per_degree, mv::Move};
+/// Checks an axis velocity to move at, in degrees per second.
+pub fn validate_velocity(velocity: f32) -> Result<(), String> {
+    if !(velocity > 0. && velocity <= MAX_VELOCITY) {
+        return Err(format!(
+            "velocity is above 0 & up to {MAX_VELOCITY} degrees per second"
+        ));
+    }
+    Ok(())
+}
+
+/// Consecutive 1ms endstop reads that must agree before M119 reports a trigger.
+const DEBOUNCE_SAMPLES: u32 = 5;
 
 #[derive(Copy, Clone, Debug, Display)]
 pub enum Axis {
@@ -28,11 +75,121 @@ pub enum Axis {
     Tilt,
 }
 
+/// Step counts of both axes, updated step by step so other tasks can read
+/// the position while the gimbal is locked mid move.
+pub struct Position {
+    pan_steps: AtomicI32,
+    tilt_steps: AtomicI32,
+    steps_per_degree: (f32, f32),
+    /// The active work offset in degrees, as f32 bits
+    pan_offset: AtomicU32,
+    tilt_offset: AtomicU32,
+    homing: AtomicBool,
+}
+
+impl Position {
+    fn new(steps_per_degree: (f32, f32)) -> Self {
+        Self {
+            pan_steps: AtomicI32::new(0),
+            tilt_steps: AtomicI32::new(0),
+            steps_per_degree,
+            pan_offset: AtomicU32::new(0),
+            tilt_offset: AtomicU32::new(0),
+            homing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn steps(&self) -> (i32, i32) {
+        (
+            self.pan_steps.load(Ordering::Relaxed),
+            self.tilt_steps.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Degrees from home.
+    pub fn machine_degrees(&self) -> (f32, f32) {
+        let (pan, tilt) = self.steps();
+        (
+            pan as f32 / self.steps_per_degree.0,
+            tilt as f32 / self.steps_per_degree.1,
+        )
+    }
+
+    /// Degrees in the active work coordinate system.
+    pub fn degrees(&self) -> (f32, f32) {
+        let (pan, tilt) = self.machine_degrees();
+        (
+            pan - f32::from_bits(self.pan_offset.load(Ordering::Relaxed)),
+            tilt - f32::from_bits(self.tilt_offset.load(Ordering::Relaxed)),
+        )
+    }
+
+    fn set_offset(&self, (pan, tilt): (f32, f32)) {
+        self.pan_offset.store(pan.to_bits(), Ordering::Relaxed);
+        self.tilt_offset.store(tilt.to_bits(), Ordering::Relaxed);
+    }
+
+    fn add(&self, pan: i32, tilt: i32) {
+        self.pan_steps.fetch_add(pan, Ordering::Relaxed);
+        self.tilt_steps.fetch_add(tilt, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.pan_steps.store(0, Ordering::Relaxed);
+        self.tilt_steps.store(0, Ordering::Relaxed);
+    }
+
+    /// Whether the gimbal's seeking its endstops, which it does locked.
+    pub fn is_homing(&self) -> bool {
+        self.homing.load(Ordering::Relaxed)
+    }
+}
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.steps().serialize(serializer)
+    }
+}
+
+/// Reads the endstop inputs directly, so they can be reported while the
+/// gimbal is locked mid move.
+#[derive(Clone, Copy)]
+pub struct Endstops {
+    pan_pin: i32,
+    tilt_pin: i32,
+}
+
+impl Endstops {
+    /// Whether the pan & tilt endstops read triggered right now. They pull low.
+    pub fn raw(&self) -> (bool, bool) {
+        // the pins are configured as inputs for the life of the gimbal
+        unsafe {
+            (
+                gpio_get_level(self.pan_pin) == 0,
+                gpio_get_level(self.tilt_pin) == 0,
+            )
+        }
+    }
+
+    /// Whether the endstops are triggered, ignoring switch bounce: each must
+    /// read triggered for `DEBOUNCE_SAMPLES` consecutive milliseconds.
+    pub fn triggered(&self) -> (bool, bool) {
+        let (mut pan, mut tilt) = self.raw();
+        for _ in 1..DEBOUNCE_SAMPLES {
+            Delay::new_default().delay_ms(1);
+            let (pan_now, tilt_now) = self.raw();
+            pan &= pan_now;
+            tilt &= tilt_now;
+        }
+        (pan, tilt)
+    }
+}
+
 #[derive(Serialize)]
 pub struct Gimbal {
     #[serde(skip)]
     pub pins: GimbalPins,
-    pub pos_steps: (u32, u32),
+    pub pos_steps: Arc<Position>,
     pan_teeth: u16,
     tilt_teeth: u16,
     pan_drive_teeth: u16,
@@ -41,6 +198,31 @@ pub struct Gimbal {
     tilt_velocity: f32,
     is_home_referenced: bool,
     is_homing: bool,
+    is_absolute: bool,
+    /// Continuous pan & tilt velocity, degrees per second, signed
+    drive: (f32, f32),
+    #[serde(skip)]
+    drive_remainder_steps: (f32, f32),
+    presets: Arc<Mutex<Presets>>,
+    work_offsets: WorkOffsets,
+    #[serde(skip)]
+    playback: Option<Playback>,
+    #[serde(skip)]
+    shutter: Option<Shutter>,
+    #[serde(skip)]
+    capture: Option<Job>,
+    #[serde(skip)]
+    mount: Mount,
+    #[serde(skip)]
+    site: Option<Site>,
+    /// Pan & tilt being followed, in the active work coordinate system
+    tracking: Option<(f32, f32)>,
+    /// Satellite whose passes set what's tracked
+    #[serde(skip)]
+    rotator: Option<Rotator>,
+    /// Sun or moon followed
+    #[serde(skip)]
+    sky: Option<Sky>,
     pub last_error_message: Option<String>,
 }
 
@@ -64,7 +246,10 @@ impl Gimbal {
             .expect("pullup failed");
         Self {
             pins,
-            pos_steps: (0, 0),
+            pos_steps: Arc::new(Position::new((
+                steps_per_degree(pan_drive_teeth, pan_teeth),
+                steps_per_degree(tilt_drive_teeth, tilt_teeth),
+            ))),
             pan_teeth,
             tilt_teeth,
             pan_drive_teeth,
@@ -74,6 +259,19 @@ impl Gimbal {
             is_homing: false,
             // @todo NO NO NO NO
             is_home_referenced: true,
+            is_absolute: false,
+            drive: (0., 0.),
+            drive_remainder_steps: (0., 0.),
+            presets: Arc::new(Mutex::new(Presets::new())),
+            work_offsets: WorkOffsets::default(),
+            playback: None,
+            shutter: None,
+            capture: None,
+            mount: Mount::default(),
+            site: None,
+            tracking: None,
+            rotator: None,
+            sky: None,
             last_error_message: None,
         }
     }
@@ -86,8 +284,546 @@ impl Gimbal {
         steps_per_degree(self.tilt_drive_teeth, self.tilt_teeth)
     }
 
-    pub fn fire() {
-        todo!()
+    pub fn is_homing(&self) -> bool {
+        self.is_homing
+    }
+
+    fn set_homing(&mut self, homing: bool) {
+        self.is_homing = homing;
+        self.pos_steps.homing.store(homing, Ordering::Relaxed);
+    }
+
+    pub fn is_home_referenced(&self) -> bool {
+        self.is_home_referenced
+    }
+
+    /// Position in the active work coordinate system.
+    pub fn pos_degrees(&self) -> (f32, f32) {
+        self.pos_steps.degrees()
+    }
+
+    pub fn machine_pos_degrees(&self) -> (f32, f32) {
+        self.pos_steps.machine_degrees()
+    }
+
+    pub fn work_offsets(&self) -> &WorkOffsets {
+        &self.work_offsets
+    }
+
+    pub fn set_work_offsets(&mut self, work_offsets: WorkOffsets) {
+        self.pos_steps.set_offset(work_offsets.offset());
+        self.work_offsets = work_offsets;
+    }
+
+    /// A handle for editing presets without locking the gimbal.
+    pub fn presets(&self) -> Arc<Mutex<Presets>> {
+        self.presets.clone()
+    }
+
+    pub fn set_presets(&mut self, presets: Presets) {
+        *self.presets.lock().unwrap() = presets;
+    }
+
+    pub fn put_preset(&mut self, name: &str, preset: Preset) -> Result<(), String> {
+        presets::insert(&mut self.presets.lock().unwrap(), name, preset)
+    }
+
+    /// Stores the current position as a preset.
+    fn save_preset(&mut self, name: &str, speed: Option<f32>) {
+        let (pan, tilt) = self.machine_pos_degrees();
+        // a bad name or a full store shouldn't fault the gimbal
+        if let Err(e) = self.put_preset(name, Preset { pan, tilt, speed }) {
+            warn!("not saving preset: {e}");
+        }
+    }
+
+    pub fn remove_preset(&mut self, name: &str) -> Option<Preset> {
+        self.presets.lock().unwrap().remove(name)
+    }
+
+    /// Moves to a preset, at its own speed when it has one.
+    pub fn recall_preset(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(preset) = self.presets.lock().unwrap().get(name).copied() else {
+            warn!("no preset {name}");
+            return Ok(());
+        };
+        let (pan, tilt) = self.work_offsets.to_work((preset.pan, preset.tilt));
+        match preset.speed {
+            Some(speed) => self.at_velocity(speed, speed, |g| g.move_to(Some(pan), Some(tilt))),
+            None => self.move_to(Some(pan), Some(tilt)),
+        }
+    }
+
+    /// Runs `f` with pan & tilt velocities of its own, in degrees per second,
+    /// putting the configured ones back after.
+    pub fn at_velocity<T>(&mut self, pan: f32, tilt: f32, f: impl FnOnce(&mut Self) -> T) -> T {
+        let velocities = (self.pan_velocity, self.tilt_velocity);
+        (self.pan_velocity, self.tilt_velocity) = (pan, tilt);
+        let res = f(self);
+        (self.pan_velocity, self.tilt_velocity) = velocities;
+        res
+    }
+
+    pub fn endstops(&self) -> Endstops {
+        Endstops {
+            pan_pin: self.pins.pan_endstop.pd.pin(),
+            tilt_pin: self.pins.tilt_endstop.pd.pin(),
+        }
+    }
+
+    /// A handle for reading the position without locking the gimbal.
+    pub fn position(&self) -> Arc<Position> {
+        self.pos_steps.clone()
+    }
+
+    pub fn is_driving(&self) -> bool {
+        self.drive != (0., 0.)
+    }
+
+    pub fn playback(&self) -> Option<&Playback> {
+        self.playback.as_ref()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback
+            .as_ref()
+            .is_some_and(|playback| playback.playing)
+    }
+
+    /// Loads a timeline its sender checked against the limits that apply.
+    fn load_timeline(&mut self, timeline: Timeline) {
+        self.playback = Some(Playback::new(timeline));
+    }
+
+    fn control_timeline(&mut self, control: Control) -> anyhow::Result<()> {
+        if matches!(control, Control::Play) && self.playback.is_some() {
+            self.stop_tracking();
+        }
+        let Some(playback) = self.playback.as_mut() else {
+            warn!("no timeline loaded");
+            return Ok(());
+        };
+        let ticks = playback.timeline.ticks();
+        let cue = match control {
+            Control::Play => {
+                if playback.tick >= ticks {
+                    playback.tick = 0;
+                }
+                playback.playing = true;
+                playback.timeline.position_at(playback.tick)
+            }
+            Control::Pause => {
+                playback.playing = false;
+                return Ok(());
+            }
+            Control::Seek { t } => {
+                playback.tick = timeline::tick_at(t).min(ticks);
+                playback.timeline.position_at(playback.tick)
+            }
+            Control::Loop { looping } => {
+                playback.timeline.looping = looping;
+                return Ok(());
+            }
+        };
+        // cue up at the usual velocity, then play from there on time
+        let res = self.move_to(Some(cue.0), Some(cue.1));
+        if let Some(playback) = self.playback.as_mut() {
+            playback.playing &= res.is_ok();
+            playback.deadline = Instant::now();
+        }
+        res
+    }
+
+    /// Plays one tick of the timeline, stepping to exactly where it says to be
+    /// so takes repeat. Blocks for about `timeline::TICK`.
+    pub fn timeline_tick(&mut self) {
+        let Some(playback) = self.playback.as_mut().filter(|playback| playback.playing) else {
+            return;
+        };
+        if playback.tick >= playback.timeline.ticks() {
+            if !playback.timeline.looping {
+                playback.playing = false;
+                info!("timeline finished");
+                return;
+            }
+            // a loop that ends elsewhere returns to the start at the usual velocity
+            playback.tick = 0;
+            let (pan, tilt) = playback.timeline.position_at(0);
+            if let Err(e) = self.move_to(Some(pan), Some(tilt)) {
+                warn!("timeline stopped: {e}");
+                self.stop();
+            }
+            if let Some(playback) = self.playback.as_mut() {
+                playback.deadline = Instant::now();
+            }
+            return;
+        }
+
+        playback.tick += 1;
+        let target = self
+            .work_offsets
+            .to_machine(playback.timeline.position_at(playback.tick));
+        let now = Instant::now();
+        playback.deadline += timeline::TICK;
+        if playback.deadline < now + timeline::TICK / 2 {
+            // too far behind to catch up without rushing, so carry on from now
+            playback.deadline = now + timeline::TICK;
+        }
+        let duration = playback.deadline - now;
+        let (pan_steps, tilt_steps) = self.pos_steps.steps();
+        self.step_both(
+            (target.0 * self.steps_per_degree_pan()).round() as i32 - pan_steps,
+            (target.1 * self.steps_per_degree_tilt()).round() as i32 - tilt_steps,
+            duration,
+        );
+    }
+
+    pub fn set_shutter(&mut self, shutter: Shutter) {
+        self.shutter = Some(shutter);
+    }
+
+    pub fn has_shutter(&self) -> bool {
+        self.shutter.is_some()
+    }
+
+    /// Takes a picture.
+    pub fn fire(&mut self) -> anyhow::Result<()> {
+        let shutter = self
+            .shutter
+            .as_mut()
+            .ok_or_else(|| anyhow!("no camera shutter configured"))?;
+        shutter.fire();
+        Ok(())
+    }
+
+    /// Seconds firing takes, including focusing.
+    pub fn fire_secs(&self) -> f32 {
+        self.shutter
+            .as_ref()
+            .map_or(0., |shutter| shutter.duration().as_secs_f32())
+    }
+
+    /// Degrees per second that moves use.
+    pub fn velocities(&self) -> (f32, f32) {
+        (self.pan_velocity, self.tilt_velocity)
+    }
+
+    pub fn capture(&self) -> Option<&Job> {
+        self.capture.as_ref()
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.as_ref().is_some_and(Job::is_running)
+    }
+
+    /// Picks up a job saved before a restart, paused.
+    pub fn restore_capture(&mut self, capture: Capture, bookmark: Bookmark) {
+        if let Err(e) = capture.validate() {
+            warn!("discarding saved capture: {e}");
+            return;
+        }
+        self.capture = Some(Job::restore(capture, bookmark, Instant::now()));
+    }
+
+    fn start_capture(&mut self, capture: Capture) -> anyhow::Result<()> {
+        // checked by whoever queued it, but not worth faulting the gimbal over
+        if let Err(e) = capture.validate() {
+            warn!("not starting capture: {e}");
+            return Ok(());
+        }
+        if !self.has_shutter() {
+            warn!("not starting capture: no camera shutter configured");
+            return Ok(());
+        }
+        if !self.is_referenced() {
+            warn!("not starting capture: gimbal not homed");
+            return Ok(());
+        }
+        self.stop_tracking();
+        self.capture = Some(Job::new(capture, Instant::now()));
+        Ok(())
+    }
+
+    fn control_capture(&mut self, control: capture::Control) {
+        let Some(job) = self.capture.as_mut() else {
+            warn!("no capture running");
+            return;
+        };
+        match control {
+            capture::Control::Pause => job.pause(),
+            capture::Control::Resume => job.resume(Instant::now()),
+            capture::Control::Cancel => self.capture = None,
+        }
+    }
+
+    /// Does whatever the capture job is due to do. Blocks while moving &
+    /// firing, but not while settling or exposing.
+    pub fn capture_tick(&mut self) {
+        let Some(mut job) = self.capture.take() else {
+            return;
+        };
+        while let Some(action) = job.poll(Instant::now()) {
+            let res = match action {
+                Action::Move((pan, tilt)) => self.move_to(Some(pan), Some(tilt)),
+                Action::Fire => self.fire(),
+            };
+            if let Err(e) = res {
+                warn!("capture paused at frame {}: {e}", job.frame);
+                job.pause();
+            }
+        }
+        if job.is_finished() {
+            info!("capture finished, {} frames", job.frame);
+        }
+        self.capture = Some(job);
+    }
+
+    pub fn set_mount(&mut self, mount: Mount) {
+        self.mount = mount;
+    }
+
+    pub fn set_site(&mut self, site: Option<Site>) {
+        self.site = site;
+    }
+
+    pub fn site(&self) -> Option<Site> {
+        self.site
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        self.tracking.is_some() || self.rotator.is_some() || self.sky.is_some()
+    }
+
+    fn stop_tracking(&mut self) {
+        self.tracking = None;
+        self.rotator = None;
+        self.sky = None;
+    }
+
+    pub fn rotator(&self) -> Option<&Rotator> {
+        self.rotator.as_ref()
+    }
+
+    pub fn sky(&self) -> Option<&Sky> {
+        self.sky.as_ref()
+    }
+
+    /// Work pan & tilt that aim at `target`, turning the shorter way round
+    /// unless that winds pan past the wrap.
+    fn aim(&self, target: Target) -> Option<(f32, f32)> {
+        let aim = match (target, self.site) {
+            (Target::Point(point), _) => self.mount.look_at(point),
+            (Target::Geo(geo), Some(site)) => {
+                let look = site.look(&geo);
+                Ok(site.pan_tilt(look.azimuth, look.elevation))
+            }
+            (Target::Geo(_), None) => Err("no site configured".into()),
+        };
+        match aim {
+            Ok((pan, tilt)) => {
+                let pan = rotator::unwind(pan, self.pos_degrees().0, rotator::DEFAULT_WRAP);
+                Some((pan, tilt))
+            }
+            Err(e) => {
+                warn!("not looking at {target:?}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Moves to aim at a target, ending any tracking.
+    fn look_at(&mut self, target: Target) -> anyhow::Result<()> {
+        self.stop_tracking();
+        match self.aim(target) {
+            Some((pan, tilt)) => self.move_to(Some(pan), Some(tilt)),
+            None => Ok(()),
+        }
+    }
+
+    /// Follows a target, replacing the one followed so far.
+    fn track(&mut self, target: Target) -> anyhow::Result<()> {
+        if !self.is_referenced() {
+            warn!("not tracking: gimbal not homed");
+            return Ok(());
+        }
+        if let Some(aim) = self.aim(target) {
+            self.stop_tracking();
+            self.tracking = Some(aim);
+        }
+        Ok(())
+    }
+
+    /// Follows a satellite's passes, waiting between them where the next
+    /// rises.
+    fn track_satellite(
+        &mut self,
+        satellite: Satellite,
+        options: rotator::Options,
+    ) -> anyhow::Result<()> {
+        let Some(site) = self.site else {
+            warn!("not tracking {}: no site configured", satellite.name);
+            return Ok(());
+        };
+        if !self.is_referenced() {
+            warn!("not tracking {}: gimbal not homed", satellite.name);
+            return Ok(());
+        }
+        info!("tracking satellite {}", satellite.name);
+        self.stop_tracking();
+        self.rotator = Some(Rotator::new(satellite, site, options));
+        Ok(())
+    }
+
+    /// Follows the sun or moon, parking while it's set if asked to.
+    fn track_body(&mut self, body: Body, options: ephemeris::Options) -> anyhow::Result<()> {
+        let Some(site) = self.site else {
+            warn!("not tracking the {body:?}: no site configured");
+            return Ok(());
+        };
+        if !self.is_referenced() {
+            warn!("not tracking the {body:?}: gimbal not homed");
+            return Ok(());
+        }
+        info!("tracking the {body:?}");
+        self.stop_tracking();
+        self.sky = Some(Sky {
+            body,
+            site,
+            options,
+        });
+        Ok(())
+    }
+
+    /// Steps towards the tracked target at up to the usual velocities. Blocks
+    /// for `timeline::TICK`, holding still while a satellite is out of reach
+    /// or the clock isn't set.
+    pub fn track_tick(&mut self) {
+        let (pan, tilt) = self.pos_degrees();
+        let now = clock::now();
+        if let Some(rotator) = self.rotator.as_mut() {
+            self.tracking = now.and_then(|now| rotator.aim(now, pan));
+        }
+        if let Some(sky) = self.sky {
+            self.tracking = now.map(|now| sky.aim(now, pan));
+        }
+        let Some(target) = self.tracking else {
+            self.step_both(0, 0, timeline::TICK);
+            return;
+        };
+        let secs = timeline::TICK.as_secs_f32();
+        let (max_pan, max_tilt) = (self.pan_velocity * secs, self.tilt_velocity * secs);
+        let pan = (target.0 - pan).clamp(-max_pan, max_pan);
+        let tilt = (target.1 - tilt).clamp(-max_tilt, max_tilt);
+        self.step_both(
+            (pan * self.steps_per_degree_pan()).round() as i32,
+            (tilt * self.steps_per_degree_tilt()).round() as i32,
+            timeline::TICK,
+        );
+    }
+
+    /// Whether positions mean anything yet. Jobs that follow or aim from
+    /// positions warn & ignore being started before, instead of faulting.
+    fn is_referenced(&self) -> bool {
+        self.is_home_referenced || self.is_homing
+    }
+
+    fn ensure_referenced(&self) -> anyhow::Result<()> {
+        if !self.is_referenced() {
+            return Err(anyhow!("gimbal not homed"));
+        }
+        Ok(())
+    }
+
+    pub fn process_cmd(&mut self, cmd: Cmd) -> anyhow::Result<()> {
+        match cmd {
+            Cmd::ClearCmdQueue => {}
+            Cmd::ProcessGcode(gcode) => self.process_gcode(gcode)?,
+            Cmd::MoveTo(pan, tilt) => self.move_to(pan, tilt)?,
+            Cmd::MoveBy(pan, tilt) => self.move_by(pan, tilt)?,
+            Cmd::Drive(pan, tilt) => self.set_drive(pan, tilt),
+            Cmd::AtVelocity(pan, tilt, cmd) => {
+                match validate_velocity(pan).and(validate_velocity(tilt)) {
+                    Ok(_) => self.at_velocity(pan, tilt, |g| g.process_cmd(*cmd))?,
+                    Err(e) => warn!("not moving: {e}"),
+                }
+            }
+            // answered by whoever took it off the queue
+            Cmd::Reply(cmd, _) => self.process_cmd(*cmd)?,
+            Cmd::Stop => self.stop(),
+            Cmd::SetPreset(name, speed) => self.save_preset(&name, speed),
+            Cmd::ClearPreset(name) => {
+                self.remove_preset(&name);
+            }
+            Cmd::RecallPreset(name) => self.recall_preset(&name)?,
+            Cmd::LoadTimeline(timeline) => self.load_timeline(timeline),
+            Cmd::Timeline(control) => self.control_timeline(control)?,
+            Cmd::StartCapture(capture) => self.start_capture(capture)?,
+            Cmd::Capture(control) => self.control_capture(control),
+            Cmd::LookAt(target) => self.look_at(target)?,
+            Cmd::Track(target) => self.track(target)?,
+            Cmd::TrackSatellite(satellite, options) => self.track_satellite(*satellite, options)?,
+            Cmd::TrackBody(body, options) => self.track_body(body, options)?,
+        };
+        Ok(())
+    }
+
+    /// Moves both axes to absolute positions in the active work coordinate
+    /// system, in degrees. `None` holds an axis.
+    pub fn move_to(&mut self, pan: Option<f32>, tilt: Option<f32>) -> anyhow::Result<()> {
+        self.ensure_referenced()?;
+        let (cur_pan, cur_tilt) = self.pos_degrees();
+        self.move_by(pan.map(|p| p - cur_pan), tilt.map(|t| t - cur_tilt))
+    }
+
+    /// Moves both axes by relative amounts, in degrees, arriving together.
+    pub fn move_by(&mut self, pan: Option<f32>, tilt: Option<f32>) -> anyhow::Result<()> {
+        self.ensure_referenced()?;
+        let pan_steps = (pan.unwrap_or(0.) * self.steps_per_degree_pan()) as i32;
+        let tilt_steps = (tilt.unwrap_or(0.) * self.steps_per_degree_tilt()) as i32;
+        let seconds = f32::max(
+            pan.unwrap_or(0.).abs() / self.pan_velocity,
+            tilt.unwrap_or(0.).abs() / self.tilt_velocity,
+        );
+        info!("move by // pan: {pan:?}, tilt: {tilt:?}, seconds_to_move: {seconds:.2}");
+        self.step_both(
+            pan_steps,
+            tilt_steps,
+            Duration::from_micros((seconds * 1_000_000.) as u64),
+        );
+        Ok(())
+    }
+
+    /// Sets continuous velocities, in degrees per second. Zero stops an axis.
+    pub fn set_drive(&mut self, pan: f32, tilt: f32) {
+        self.drive = (
+            pan.clamp(-MAX_VELOCITY, MAX_VELOCITY),
+            tilt.clamp(-MAX_VELOCITY, MAX_VELOCITY),
+        );
+        if !self.is_driving() {
+            self.drive_remainder_steps = (0., 0.);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.set_drive(0., 0.);
+        if let Some(playback) = self.playback.as_mut() {
+            playback.playing = false;
+        }
+        if let Some(job) = self.capture.as_mut() {
+            job.pause();
+        }
+        self.stop_tracking();
+    }
+
+    /// Advances continuous motion by one slice of time. Blocks for `slice`.
+    pub fn drive_tick(&mut self, slice: Duration) {
+        let secs = slice.as_secs_f32();
+        let pan = self.drive.0 * secs * self.steps_per_degree_pan() + self.drive_remainder_steps.0;
+        let tilt =
+            self.drive.1 * secs * self.steps_per_degree_tilt() + self.drive_remainder_steps.1;
+        let (pan_steps, tilt_steps) = (pan.trunc() as i32, tilt.trunc() as i32);
+        self.drive_remainder_steps = (pan.fract(), tilt.fract());
+        self.step_both(pan_steps, tilt_steps, slice);
     }
 
     fn try_home(&mut self) -> anyhow::Result<()> {
@@ -99,38 +835,64 @@ impl Gimbal {
     pub fn process_gcode(&mut self, gcode: Gcode) -> anyhow::Result<()> {
         info!("processing gcode [START]: {gcode:?}");
         match gcode {
-            Gcode::G1Move(opan, otilt) => {
-                if !self.is_home_referenced && !self.is_homing {
-                    return Err(anyhow!("gimbal not homed"));
-                }
-                let pan = opan.unwrap_or(0.);
-                let tilt = otilt.unwrap_or(0.);
-
-                self.moov(Move {
-                    axis: Axis::Pan,
-                    degrees: pan,
-                });
-
-                self.moov(Move {
-                    axis: Axis::Tilt,
-                    degrees: tilt,
-                });
-            }
+            Gcode::G1Move(opan, otilt) => match self.is_absolute {
+                true => self.move_to(opan, otilt)?,
+                false => self.move_by(opan, otilt)?,
+            },
             Gcode::G28Home => {
-                self.is_homing = true;
+                self.set_homing(true);
                 let res = self.try_home();
-                self.is_homing = false;
+                self.set_homing(false);
                 self.is_home_referenced = res.is_ok();
                 res?;
+                self.pos_steps.reset();
+            }
+            Gcode::G10SetWorkOffset(system, pan, tilt) => {
+                let mut work_offsets = self.work_offsets.clone();
+                work_offsets
+                    .set_offset(system, pan, tilt)
+                    .map_err(|e| anyhow!(e))?;
+                self.set_work_offsets(work_offsets);
+            }
+            Gcode::G54SelectWorkOffset(system) => {
+                let mut work_offsets = self.work_offsets.clone();
+                work_offsets.select(system).map_err(|e| anyhow!(e))?;
+                self.set_work_offsets(work_offsets);
+            }
+            Gcode::G90SetAbsolute => self.is_absolute = true,
+            Gcode::G91SetRelative => self.is_absolute = false,
+            Gcode::G92SetPosition(pan, tilt) => {
+                let mut work_offsets = self.work_offsets.clone();
+                work_offsets.set_position(self.machine_pos_degrees(), pan, tilt);
+                self.set_work_offsets(work_offsets);
+            }
+            Gcode::G92_1ClearPosition => {
+                let mut work_offsets = self.work_offsets.clone();
+                work_offsets.shift = (0., 0.);
+                self.set_work_offsets(work_offsets);
             }
-            Gcode::G90SetAbsolute => todo!(),
-            Gcode::G91SetRelative => todo!(),
             Gcode::M1SetVelocity(opan, otilt) => {
                 let pan = opan.unwrap_or(self.pan_velocity);
                 let tilt = otilt.unwrap_or(self.tilt_velocity);
-                self.pan_velocity = pan;
-                self.tilt_velocity = tilt;
+                // every channel sets velocities through here, so check them once
+                match validate_velocity(pan).and_then(|()| validate_velocity(tilt)) {
+                    Ok(()) => (self.pan_velocity, self.tilt_velocity) = (pan, tilt),
+                    Err(e) => warn!("not setting velocity: {e}"),
+                }
+            }
+            Gcode::M70SavePreset(name) => self.save_preset(&name, None),
+            Gcode::M71RecallPreset(name) => self.recall_preset(&name)?,
+            Gcode::M72DeletePreset(name) => {
+                self.remove_preset(&name);
+            }
+            Gcode::M240TriggerCamera => {
+                // a missing shutter isn't worth faulting the gimbal over
+                if let Err(e) = self.fire() {
+                    warn!("{e}");
+                }
             }
+            // reports are answered by the channel that received them
+            Gcode::M114ReportPosition | Gcode::M115ReportFirmware | Gcode::M119ReportEndstops => {}
         };
 
         Ok(())
@@ -218,7 +980,7 @@ impl Gimbal {
     }
 
     fn is_tilt_home(&self) -> bool {
-        self.pins.pan_endstop.pd.get_level() == Level::Low
+        self.pins.tilt_endstop.pd.get_level() == Level::Low
     }
 
     fn moov(&mut self, mv: Move) {
@@ -266,16 +1028,75 @@ impl Gimbal {
 
         info!("move // axis: {axis}, degrees: {degrees}, steps: {num_steps}, delay_micros: {delay_micros}, seconds_to_move: {seconds_to_move:.2}");
 
+        let step = if is_fwd { 1 } else { -1 };
+        let (pan_step, tilt_step) = match &axis {
+            Axis::Pan => (step, 0),
+            Axis::Tilt => (0, step),
+        };
+
         for _i in 0..num_steps {
             step_pin.high();
             Delay::new_default().delay_us(delay_micros);
             step_pin.low();
             Delay::new_default().delay_us(delay_micros);
+            self.pos_steps.add(pan_step, tilt_step);
+        }
+    }
+
+    /// Steps both axes at once, spreading the steps of each evenly over
+    /// `duration` so they start and finish together.
+    fn step_both(&mut self, pan_steps: i32, tilt_steps: i32, duration: Duration) {
+        let delay = Delay::new_default();
+        let pan_n = pan_steps.unsigned_abs();
+        let tilt_n = tilt_steps.unsigned_abs();
+        let total = pan_n.max(tilt_n);
+        if total == 0 {
+            delay.delay_us(duration.as_micros() as u32);
+            return;
         }
-        self.pos_steps = match &axis {
-            Axis::Pan => (self.pos_steps.0 + num_steps, self.pos_steps.1),
-            Axis::Tilt => (self.pos_steps.0, self.pos_steps.1 + num_steps),
+
+        match pan_steps > 0 {
+            true => self.pins.pan_dir.high(),
+            false => self.pins.pan_dir.low(),
+        };
+        match tilt_steps > 0 {
+            true => self.pins.tilt_dir.high(),
+            false => self.pins.tilt_dir.low(),
         };
+
+        let half_period_micros = ((duration.as_micros() as u64) / u64::from(total) / 2) as u32;
+
+        // bresenham: the busier axis steps every tick, the other one when its
+        // error term rolls over
+        let pan_step = pan_steps.signum();
+        let tilt_step = tilt_steps.signum();
+        let (mut pan_acc, mut tilt_acc) = (0, 0);
+        for _ in 0..total {
+            pan_acc += pan_n;
+            tilt_acc += tilt_n;
+            let step_pan = pan_acc >= total;
+            let step_tilt = tilt_acc >= total;
+            if step_pan {
+                pan_acc -= total;
+                self.pins.pan_step.high();
+            }
+            if step_tilt {
+                tilt_acc -= total;
+                self.pins.tilt_step.high();
+            }
+            delay.delay_us(half_period_micros);
+            if step_pan {
+                self.pins.pan_step.low();
+            }
+            if step_tilt {
+                self.pins.tilt_step.low();
+            }
+            delay.delay_us(half_period_micros);
+            self.pos_steps.add(
+                if step_pan { pan_step } else { 0 },
+                if step_tilt { tilt_step } else { 0 },
+            );
+        }
     }
 
     pub fn home(&mut self) -> anyhow::Result<()> {
@@ -0,0 +1,161 @@
+#[derive(Debug, PartialEq)]
+pub enum Gs232 {
+    // C
+    ReadAzimuth,
+    // B
+    ReadElevation,
+    // C2
+    ReadPosition,
+    // M180
+    MoveAzimuth(f32),
+    // W180 045
+    MoveTo(f32, f32),
+    // R, L
+    RotateAzimuth(Direction),
+    // U, D
+    RotateElevation(Direction),
+    // X1..X4
+    SetSpeed(u8),
+    // A
+    StopAzimuth,
+    // E
+    StopElevation,
+    // S
+    Stop,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Negative,
+    Positive,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Variant {
+    /// Replies like `+0180+0045`
+    A,
+    /// Replies like `AZ=180  EL=045`
+    B,
+}
+
+pub const MAX_AZIMUTH: f32 = 450.;
+pub const MAX_ELEVATION: f32 = 180.;
+pub const MAX_SPEED: u8 = 4;
+pub const ERROR_REPLY: &str = "?>\r\n";
+
+pub fn invalid_gs232(str: &str) -> String {
+    format!("invalid gs-232 command: {}", str)
+}
+
+fn angle(str: &str, max: f32) -> Option<f32> {
+    let angle = str.trim().parse::<u16>().ok()? as f32;
+    (angle <= max).then_some(angle)
+}
+
+pub struct Gs232Parser;
+
+impl Gs232Parser {
+    pub fn of_str(str: &str) -> Result<Gs232, String> {
+        let line = str.trim().to_ascii_uppercase();
+        let mut chars = line.chars();
+        let cmd = chars.next().ok_or_else(|| invalid_gs232(str))?;
+        let args = chars.as_str();
+        let invalid = || invalid_gs232(str);
+
+        match (cmd, args) {
+            ('C', "") => Ok(Gs232::ReadAzimuth),
+            ('C', "2") => Ok(Gs232::ReadPosition),
+            ('B', "") => Ok(Gs232::ReadElevation),
+            ('M', az) => angle(az, MAX_AZIMUTH)
+                .map(Gs232::MoveAzimuth)
+                .ok_or_else(invalid),
+            ('W', args) => {
+                let mut parts = args.split_whitespace();
+                let az = parts.next().and_then(|a| angle(a, MAX_AZIMUTH));
+                let el = parts.next().and_then(|e| angle(e, MAX_ELEVATION));
+                match (az, el, parts.next()) {
+                    (Some(az), Some(el), None) => Ok(Gs232::MoveTo(az, el)),
+                    _ => Err(invalid()),
+                }
+            }
+            ('R', "") => Ok(Gs232::RotateAzimuth(Direction::Positive)),
+            ('L', "") => Ok(Gs232::RotateAzimuth(Direction::Negative)),
+            ('U', "") => Ok(Gs232::RotateElevation(Direction::Positive)),
+            ('D', "") => Ok(Gs232::RotateElevation(Direction::Negative)),
+            ('X', speed) => match speed.parse::<u8>() {
+                Ok(speed @ 1..=MAX_SPEED) => Ok(Gs232::SetSpeed(speed)),
+                _ => Err(invalid()),
+            },
+            ('A', "") => Ok(Gs232::StopAzimuth),
+            ('E', "") => Ok(Gs232::StopElevation),
+            ('S', "") => Ok(Gs232::Stop),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Rounds into the controller's range, wrapping azimuths it can't show.
+fn report_angles(az: f32, el: f32) -> (u16, u16) {
+    let az = az.round();
+    let az = if (0. ..=MAX_AZIMUTH).contains(&az) {
+        az
+    } else {
+        az.rem_euclid(360.)
+    };
+    (az as u16, el.round().clamp(0., MAX_ELEVATION) as u16)
+}
+
+pub fn format_azimuth(variant: Variant, az: f32) -> String {
+    let (az, _) = report_angles(az, 0.);
+    match variant {
+        Variant::A => format!("+0{az:03}\r\n"),
+        Variant::B => format!("AZ={az:03}\r\n"),
+    }
+}
+
+pub fn format_elevation(variant: Variant, el: f32) -> String {
+    let (_, el) = report_angles(0., el);
+    match variant {
+        Variant::A => format!("+0{el:03}\r\n"),
+        Variant::B => format!("EL={el:03}\r\n"),
+    }
+}
+
+pub fn format_position(variant: Variant, az: f32, el: f32) -> String {
+    let (az, el) = report_angles(az, el);
+    match variant {
+        Variant::A => format!("+0{az:03}+0{el:03}\r\n"),
+        Variant::B => format!("AZ={az:03}  EL={el:03}\r\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_to() {
+        let cmd = Gs232Parser::of_str("W180 045").unwrap();
+        assert_eq!(cmd, Gs232::MoveTo(180., 45.));
+        assert!(Gs232Parser::of_str("W180 190").is_err());
+        assert!(Gs232Parser::of_str("M460").is_err());
+    }
+
+    #[test]
+    fn test_queries_and_motion() {
+        assert_eq!(Gs232Parser::of_str("c2").unwrap(), Gs232::ReadPosition);
+        assert_eq!(
+            Gs232Parser::of_str("L").unwrap(),
+            Gs232::RotateAzimuth(Direction::Negative)
+        );
+        assert_eq!(Gs232Parser::of_str("X3").unwrap(), Gs232::SetSpeed(3));
+        assert!(Gs232Parser::of_str("X5").is_err());
+    }
+
+    #[test]
+    fn test_format_position() {
+        assert_eq!(format_position(Variant::A, 5.4, 30.), "+0005+0030\r\n");
+        assert_eq!(format_position(Variant::B, -90., -3.), "AZ=270  EL=000\r\n");
+        assert_eq!(format_azimuth(Variant::B, 450.), "AZ=450\r\n");
+    }
+}
@@ -0,0 +1,245 @@
+use {
+    crate::{
+        cmd::{self, Cmd, CmdQueue},
+        gimbal::{Gimbal, Position, MAX_VELOCITY},
+        gs232::{self, Direction, Gs232, Gs232Parser, Variant},
+    },
+    esp_idf_svc::hal::{
+        delay::BLOCK,
+        gpio::{AnyIOPin, InputPin, OutputPin},
+        peripheral::Peripheral,
+        uart::{config::Config, Uart, UartDriver},
+        units::Hertz,
+    },
+    log::{info, warn},
+    std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    },
+};
+
+const GS232_VARIANT: &str = env!("GS232_VARIANT");
+const GS232_BAUD: &str = env!("GS232_BAUD");
+const GS232_TCP_PORT: &str = env!("GS232_TCP_PORT");
+
+const MAX_LINE_LEN: usize = 32;
+
+pub struct Gs232Config {
+    pub variant: Variant,
+    /// `None` leaves the uart unused
+    pub baud: Option<u32>,
+    /// `None` disables the tcp listener
+    pub tcp_port: Option<u16>,
+}
+
+impl Gs232Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let variant = match GS232_VARIANT.to_ascii_lowercase().as_str() {
+            "a" => Variant::A,
+            "b" => Variant::B,
+            other => return Err(anyhow::anyhow!("unknown GS232_VARIANT {other}")),
+        };
+        let optional = |value: &str| (!value.is_empty()).then_some(value.to_owned());
+        Ok(Self {
+            variant,
+            baud: optional(GS232_BAUD).map(|b| b.parse()).transpose()?,
+            tcp_port: optional(GS232_TCP_PORT).map(|p| p.parse()).transpose()?,
+        })
+    }
+}
+
+/// One controller connection. Commands are CR terminated.
+struct Session {
+    variant: Variant,
+    cmds: CmdQueue,
+    position: Arc<Position>,
+    speed: u8,
+    /// Drive velocity last requested by this controller, so one axis can be
+    /// stopped without stopping the other
+    drive: (f32, f32),
+    line: String,
+}
+
+impl Session {
+    fn new(variant: Variant, cmds: CmdQueue, position: Arc<Position>) -> Self {
+        Self {
+            variant,
+            cmds,
+            position,
+            speed: gs232::MAX_SPEED,
+            drive: (0., 0.),
+            line: String::new(),
+        }
+    }
+
+    fn velocity(&self, direction: Direction) -> f32 {
+        let velocity = f32::from(self.speed) / f32::from(gs232::MAX_SPEED) * MAX_VELOCITY;
+        match direction {
+            Direction::Negative => -velocity,
+            Direction::Positive => velocity,
+        }
+    }
+
+    fn drive(&mut self, drive: (f32, f32)) {
+        self.drive = drive;
+        self.cmds
+            .lock()
+            .unwrap()
+            .push_back(Cmd::Drive(drive.0, drive.1));
+    }
+
+    fn execute(&mut self, command: Gs232) -> Option<String> {
+        let (az, el) = self.position.degrees();
+        match command {
+            Gs232::ReadAzimuth => return Some(gs232::format_azimuth(self.variant, az)),
+            Gs232::ReadElevation => return Some(gs232::format_elevation(self.variant, el)),
+            Gs232::ReadPosition => return Some(gs232::format_position(self.variant, az, el)),
+            Gs232::MoveAzimuth(az) => self
+                .cmds
+                .lock()
+                .unwrap()
+                .push_back(Cmd::MoveTo(Some(az), None)),
+            Gs232::MoveTo(az, el) => self
+                .cmds
+                .lock()
+                .unwrap()
+                .push_back(Cmd::MoveTo(Some(az), Some(el))),
+            Gs232::RotateAzimuth(direction) => self.drive((self.velocity(direction), self.drive.1)),
+            Gs232::RotateElevation(direction) => {
+                self.drive((self.drive.0, self.velocity(direction)))
+            }
+            Gs232::SetSpeed(speed) => self.speed = speed,
+            Gs232::StopAzimuth => self.drive((0., self.drive.1)),
+            Gs232::StopElevation => self.drive((self.drive.0, 0.)),
+            Gs232::Stop => {
+                self.drive = (0., 0.);
+                cmd::stop(&self.cmds);
+            }
+        }
+        None
+    }
+
+    /// Feeds one received byte, returning the reply to a completed command.
+    fn push(&mut self, byte: u8) -> Option<String> {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = std::mem::take(&mut self.line);
+                if line.trim().is_empty() {
+                    return None;
+                }
+                match Gs232Parser::of_str(&line) {
+                    Ok(command) => self.execute(command),
+                    Err(e) => {
+                        warn!("gs232: {e}");
+                        Some(gs232::ERROR_REPLY.into())
+                    }
+                }
+            }
+            _ if self.line.len() >= MAX_LINE_LEN => {
+                self.line.clear();
+                Some(gs232::ERROR_REPLY.into())
+            }
+            byte => {
+                self.line.push(char::from(byte));
+                None
+            }
+        }
+    }
+}
+
+/// Runs a GS-232 interpreter on a uart, e.g. behind a usb-serial adapter.
+pub fn start_uart<UART: Uart>(
+    uart: impl Peripheral<P = UART> + 'static,
+    tx: impl Peripheral<P = impl OutputPin> + 'static,
+    rx: impl Peripheral<P = impl InputPin> + 'static,
+    variant: Variant,
+    baud: u32,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+) -> anyhow::Result<()> {
+    let uart_config = Config::default().baudrate(Hertz(baud));
+    let driver = UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &uart_config,
+    )?;
+    let position = gimbal_arc.lock().unwrap().position();
+    info!("gs232: listening for gs-232{variant:?} at {baud} baud");
+
+    thread::Builder::new()
+        .name("gs232-uart".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut session = Session::new(variant, cmds, position);
+            let mut buf = [0; 32];
+            loop {
+                let n = match driver.read(&mut buf, BLOCK) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("gs232: read failed: {e}");
+                        continue;
+                    }
+                };
+                for reply in buf[..n].iter().filter_map(|b| session.push(*b)) {
+                    if let Err(e) = driver.write(reply.as_bytes()) {
+                        warn!("gs232: write failed: {e}");
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Serves GS-232 over tcp, for controllers reaching the rotator through a
+/// virtual serial port.
+pub fn start_tcp(
+    port: u16,
+    variant: Variant,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    info!("gs232: listening on tcp {port}");
+    let position = gimbal_arc.lock().unwrap().position();
+    thread::Builder::new()
+        .name("gs232-tcp".into())
+        .stack_size(6144)
+        .spawn(move || {
+            // one controller at a time, like a serial port
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("gs232: accept failed: {e}");
+                        continue;
+                    }
+                };
+                let mut session = Session::new(variant, cmds.clone(), position.clone());
+                let mut buf = [0; 32];
+                loop {
+                    let n = match stream.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) => {
+                            warn!("gs232: client dropped: {e}");
+                            break;
+                        }
+                    };
+                    let replies: String =
+                        buf[..n].iter().filter_map(|b| session.push(*b)).collect();
+                    if !replies.is_empty() {
+                        if let Err(e) = stream.write_all(replies.as_bytes()) {
+                            warn!("gs232: client dropped: {e}");
+                            break;
+                        }
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// Pointing at a place rather than in a direction: turns a point in the world
+// into the pan & tilt that aim the camera through it, given how the gimbal is
+// mounted.
+//
+// The world & the base are right handed with z up. The base's x axis points
+// where pan zero does and its y axis to the left. Pan turns clockwise seen
+// from above, like an azimuth, and tilt up.
+
+use serde::{Deserialize, Serialize};
+
+/// How the gimbal sits in the world.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Mount {
+    /// Where the base is, in any unit as long as targets use it too
+    pub position: [f32; 3],
+    /// Degrees. Yaw turns the base clockwise seen from above, like pan, then
+    /// pitch raises its front and roll lowers its right side.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub offsets: Offsets,
+}
+
+/// Where the camera's line of sight runs, relative to the base at pan zero.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Offsets {
+    /// Height of the tilt axis above the base, along the pan axis
+    pub up: f32,
+    /// How far the tilt axis sits in front of the pan axis
+    pub forward: f32,
+    /// How far the line of sight runs to the right of the pan axis
+    pub right: f32,
+}
+
+impl Mount {
+    pub fn validate(&self) -> Result<(), String> {
+        let Offsets { up, forward, right } = self.offsets;
+        let [x, y, z] = self.position;
+        let values = [x, y, z, self.yaw, self.pitch, self.roll, up, forward, right];
+        if !values.iter().all(|v| v.is_finite()) {
+            return Err("mount position, angles & offsets must be numbers".into());
+        }
+        Ok(())
+    }
+
+    /// `v`, a direction in the world, in the base's axes.
+    fn in_base(&self, v: [f32; 3]) -> [f32; 3] {
+        // undoes yaw, pitch & roll in turn
+        let v = rotate_z(v, self.yaw.to_radians());
+        let v = rotate_y(v, self.pitch.to_radians());
+        rotate_x(v, -self.roll.to_radians())
+    }
+
+    /// Pan & tilt, in degrees, that aim the camera at `target`. Pan is
+    /// within ±180°.
+    pub fn look_at(&self, target: [f32; 3]) -> Result<(f32, f32), String> {
+        let [x, y, z] = self.position;
+        let [x, y, z] = self.in_base([target[0] - x, target[1] - y, target[2] - z]);
+        let z = z - self.offsets.up;
+        let right = self.offsets.right;
+        let reach = x.hypot(y);
+        if right != 0. && reach <= right.abs() {
+            return Err("target is too close to the pan axis to aim at".into());
+        }
+        // the line of sight runs beside the pan axis, so turn a little further
+        // for it to meet the target
+        let bearing = (-y).atan2(x);
+        let pan = match right {
+            0. => bearing,
+            _ => bearing - (right / reach).asin(),
+        };
+        let ahead = (reach * reach - right * right).sqrt() - self.offsets.forward;
+        if ahead == 0. && z == 0. {
+            return Err("target is on the tilt axis".into());
+        }
+        let tilt = z.atan2(ahead);
+        Ok((wrap(pan.to_degrees()), tilt.to_degrees()))
+    }
+}
+
+fn rotate_x([x, y, z]: [f32; 3], a: f32) -> [f32; 3] {
+    let (sin, cos) = a.sin_cos();
+    [x, y * cos - z * sin, y * sin + z * cos]
+}
+
+fn rotate_y([x, y, z]: [f32; 3], a: f32) -> [f32; 3] {
+    let (sin, cos) = a.sin_cos();
+    [x * cos + z * sin, y, z * cos - x * sin]
+}
+
+fn rotate_z([x, y, z]: [f32; 3], a: f32) -> [f32; 3] {
+    let (sin, cos) = a.sin_cos();
+    [x * cos - y * sin, x * sin + y * cos, z]
+}
+
+/// `degrees` within ±180°.
+pub fn wrap(degrees: f32) -> f32 {
+    let wrapped = degrees.rem_euclid(360.);
+    if wrapped > 180. {
+        wrapped - 360.
+    } else {
+        wrapped
+    }
+}
+
+/// The turn equivalent to `degrees` that's closest to `from`, so following a
+/// target across ±180° doesn't swing the long way round.
+pub fn nearest_turn(degrees: f32, from: f32) -> f32 {
+    from + wrap(degrees - from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((pan, tilt): (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (pan - expected.0).abs() < 1e-3 && (tilt - expected.1).abs() < 1e-3,
+            "{:?} isn't {expected:?}",
+            (pan, tilt)
+        );
+    }
+
+    #[test]
+    fn test_level() {
+        let mount = Mount::default();
+        assert_near(mount.look_at([10., 0., 0.]).unwrap(), (0., 0.));
+        // right of the base, then behind it
+        assert_near(mount.look_at([0., -10., 0.]).unwrap(), (90., 0.));
+        assert_near(mount.look_at([-10., 0., 0.]).unwrap(), (180., 0.));
+        assert_near(mount.look_at([10., 0., 10.]).unwrap(), (0., 45.));
+        assert_near(mount.look_at([0., 0., 10.]).unwrap(), (0., 90.));
+    }
+
+    #[test]
+    fn test_pose() {
+        let mount = Mount {
+            position: [1., 2., 3.],
+            yaw: 30.,
+            ..Mount::default()
+        };
+        let (sin, cos) = (-30f32).to_radians().sin_cos();
+        let ahead = [1. + 10. * cos, 2. + 10. * sin, 3.];
+        assert_near(mount.look_at(ahead).unwrap(), (0., 0.));
+
+        let pitched = Mount {
+            pitch: 10.,
+            ..Mount::default()
+        };
+        assert_near(pitched.look_at([10., 0., 0.]).unwrap(), (0., -10.));
+
+        // rolled right side down, a target to the right sits level with the
+        // tilt axis once panned round
+        let rolled = Mount {
+            roll: 20.,
+            ..Mount::default()
+        };
+        let (sin, cos) = 20f32.to_radians().sin_cos();
+        assert_near(
+            rolled.look_at([0., -10. * cos, -10. * sin]).unwrap(),
+            (90., 0.),
+        );
+    }
+
+    #[test]
+    fn test_offsets() {
+        let mount = Mount {
+            offsets: Offsets {
+                up: 1.,
+                forward: 0.5,
+                right: 1.,
+            },
+            ..Mount::default()
+        };
+        let (pan, tilt) = mount.look_at([10., 0., 1.]).unwrap();
+        assert_near((pan, tilt), (-(0.1f32).asin().to_degrees(), 0.));
+        assert!(mount.look_at([0.5, 0.5, 0.]).is_err());
+
+        // on the line of sight at pan zero, a step ahead of & above the tilt axis
+        assert_near(mount.look_at([1.5, -1., 2.]).unwrap(), (0., 45.));
+    }
+
+    #[test]
+    fn test_nearest_turn() {
+        assert_eq!(nearest_turn(-170., 170.), 190.);
+        assert_eq!(nearest_turn(10., 370.), 370.);
+        assert_eq!(wrap(540.), 180.);
+        assert_eq!(wrap(-190.), 170.);
+    }
+}
@@ -1,9 +1,51 @@
+pub mod auth;
+pub mod capture;
+pub mod clock;
 pub mod cmd;
+pub mod coordinates;
+pub mod dmx;
+pub mod dmx_server;
+pub mod ephemeris;
+pub mod events;
+pub mod freed;
+pub mod freed_sender;
 pub mod gcode;
+pub mod gcode_server;
+pub mod gcode_session;
+pub mod gcode_stream;
+pub mod geo;
 pub mod gimbal;
 pub mod gimbal_pins;
+pub mod gs232;
+pub mod gs232_server;
+pub mod kinematics;
 pub mod motor;
+pub mod mqtt;
 pub mod mv;
+pub mod onvif;
+pub mod onvif_server;
+pub mod osc;
+pub mod osc_server;
+pub mod ota;
+pub mod panorama;
+pub mod pelco;
+pub mod pelco_uart;
+pub mod presets;
+pub mod rotator;
+pub mod rotctld;
+pub mod rotctld_server;
+pub mod satellite;
+pub mod serial_console;
 pub mod server;
 pub mod server_response;
+pub mod settings;
+pub mod shutter;
+pub mod state;
+pub mod takes;
+pub mod teach;
+pub mod timelapse;
+pub mod timeline;
+pub mod visca;
+pub mod visca_server;
+pub mod web;
 pub mod wifi;
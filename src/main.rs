@@ -2,21 +2,42 @@ use std::{
     borrow::BorrowMut,
     collections::VecDeque,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use esp_idf_svc::hal::gpio::IOPin;
 
-use gimbal_motion::{cmd::Cmd, gimbal_pins::GimbalBuilder};
+use gimbal_motion::{
+    clock,
+    cmd::{Cmd, CmdQueue},
+    dmx_server::{self, DmxConfig},
+    events::{self, Event},
+    freed_sender::{self, FreedConfig},
+    gcode::Gcode,
+    gcode_server,
+    gimbal_pins::GimbalBuilder,
+    gs232_server::{self, Gs232Config},
+    mqtt::{self, MqttConfig},
+    osc_server::{self, OscConfig},
+    ota::FirmwareInfo,
+    pelco_uart::{self, PelcoConfig},
+    rotctld_server, serial_console,
+    settings::Store,
+    shutter::{Shutter, ShutterConfig},
+    visca_server,
+};
 
 use {
+    embedded_svc::ipv4::IpInfo,
     esp_idf_svc::{
         hal::{delay::FreeRtos, gpio::OutputPin, peripherals::Peripherals, sys},
-        log::EspLogger,
+        http::server::EspHttpServer,
+        nvs::EspDefaultNvsPartition,
     },
     futures::executor::block_on,
     gimbal_motion::{
         gimbal::Gimbal,
-        server,
+        ota, server,
         wifi::{connect_wifi, create_wifi},
     },
 };
@@ -31,14 +52,22 @@ const DRIVE_TEETH: u16 = 16;
 const TILT_TEETH: u16 = 160;
 const PAN_TEETH: u16 = 128;
 
+const DRIVE_SLICE: Duration = Duration::from_millis(50);
+/// Longest a running capture job goes between saving how far it got
+const BOOKMARK_INTERVAL: Duration = Duration::from_secs(60);
+
 fn main() -> anyhow::Result<()> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
     // implemented by esp-idf-sys might not link properly. See https://github.com/esp-rs/esp-idf-template/issues/71
     sys::link_patches();
-    EspLogger::initialize_default();
+    serial_console::initialize_logger();
 
     let peripherals = Peripherals::take()?;
     let pins = peripherals.pins;
+    let nvs = EspDefaultNvsPartition::take()?;
+    let store = Store::new(nvs.clone())?;
+    let settings = store.settings();
+    let store_arc = Arc::new(Mutex::new(store));
 
     let gimbal_pins = GimbalBuilder::pan_dir(pins.gpio14.downgrade_output().into())
         .pan_step(pins.gpio15.downgrade_output().into())
@@ -47,47 +76,357 @@ fn main() -> anyhow::Result<()> {
         .pan_endstop(pins.gpio25.downgrade().into())
         .tilt_endstop(pins.gpio26.downgrade().into());
 
-    let cmds_arc: Arc<Mutex<VecDeque<Cmd>>> = Arc::new(Mutex::new(VecDeque::new()));
+    let cmds_arc: CmdQueue = Arc::new(Mutex::new(VecDeque::new()));
     let cmds_reader = cmds_arc.clone();
 
-    let gimbal_arc: Arc<Mutex<Gimbal>> = Arc::new(Mutex::new(Gimbal::new(
+    let mut gimbal = Gimbal::new(
         gimbal_pins,
         PAN_TEETH,
         DRIVE_TEETH,
         TILT_TEETH,
         DRIVE_TEETH,
-        30.,
-        30.,
-    )));
+        settings.pan_velocity,
+        settings.tilt_velocity,
+    );
+    gimbal.set_mount(settings.mount);
+    gimbal.set_site(settings.site);
+    {
+        let store = store_arc.lock().unwrap();
+        gimbal.set_work_offsets(store.work_offsets());
+        gimbal.set_presets(store.presets());
+        if let Some((capture, bookmark)) = store.capture() {
+            log::info!("capture restored at frame {}, paused", bookmark.frame);
+            gimbal.restore_capture(capture, bookmark);
+        }
+    }
+    match ShutterConfig::from_env() {
+        Ok(Some(shutter_config)) => match Shutter::new(&shutter_config) {
+            Ok(shutter) => gimbal.set_shutter(shutter),
+            Err(e) => log::error!("failed to start shutter: {e}"),
+        },
+        Ok(None) => {}
+        Err(e) => log::error!("invalid shutter config: {e}"),
+    }
+    let gimbal_arc: Arc<Mutex<Gimbal>> = Arc::new(Mutex::new(gimbal));
 
-    let mut wifi = create_wifi(peripherals.modem)?;
-    let ip_info = block_on(connect_wifi(&mut wifi, SSID, PASSWORD))?;
-    let _server = server::start(ip_info, cmds_arc.clone(), gimbal_arc.clone())?;
+    match PelcoConfig::from_env() {
+        Ok(Some(pelco_config)) => pelco_uart::start(
+            peripherals.uart2,
+            pins.gpio17,
+            pins.gpio16,
+            pelco_config,
+            cmds_arc.clone(),
+            gimbal_arc.clone(),
+        )?,
+        Ok(None) => {}
+        Err(e) => log::error!("invalid pelco config: {e}"),
+    }
 
+    let gs232_config = Gs232Config::from_env();
+    if let Err(e) = &gs232_config {
+        log::error!("invalid gs232 config: {e}");
+    }
+    if let Ok(Gs232Config {
+        variant,
+        baud: Some(baud),
+        ..
+    }) = gs232_config
+    {
+        gs232_server::start_uart(
+            peripherals.uart1,
+            pins.gpio4,
+            pins.gpio5,
+            variant,
+            baud,
+            cmds_arc.clone(),
+            gimbal_arc.clone(),
+        )?;
+    }
+
+    match serial_console::baud_from_env() {
+        Ok(Some(baud)) => serial_console::start(
+            peripherals.uart0,
+            pins.gpio1,
+            pins.gpio3,
+            baud,
+            cmds_arc.clone(),
+            gimbal_arc.clone(),
+        )?,
+        Ok(None) => {}
+        Err(e) => log::error!("invalid serial config: {e}"),
+    }
+
+    let mut wifi = create_wifi(peripherals.modem, nvs)?;
+    // the serial console still works without a network, so carry on
+    let _server = match block_on(connect_wifi(&mut wifi, SSID, PASSWORD)) {
+        Ok(ip_info) => Some(start_network(
+            ip_info,
+            &cmds_arc,
+            &gimbal_arc,
+            &store_arc,
+            gs232_config.as_ref().ok(),
+        )?),
+        Err(e) => {
+            log::error!("wifi unavailable, network services disabled: {e}");
+            None
+        }
+    };
+
+    // keeps time for following satellites & the sky. it can also be set over http
+    let _sntp = match clock::start() {
+        Ok(sntp) => Some(sntp),
+        Err(e) => {
+            log::error!("failed to start sntp: {e}");
+            None
+        }
+    };
+
+    let mut bookmarked = Instant::now();
     loop {
         let cmd_opt = { cmds_reader.lock().unwrap().borrow_mut().pop_front() };
 
-        if let Some(cmd) = cmd_opt {
-            match cmd {
-                Cmd::ClearCmdQueue => {
-                    let mut cmds = cmds_reader.lock().unwrap();
-                    cmds.clear();
+        match cmd_opt {
+            Some(Cmd::Reply(cmd, done)) => {
+                done.send(process_cmd(*cmd, &cmds_reader, &gimbal_arc, &store_arc));
+            }
+            Some(cmd) => {
+                let _ = process_cmd(cmd, &cmds_reader, &gimbal_arc, &store_arc);
+            }
+            None => {}
+        }
+
+        {
+            let mut gimbal = gimbal_arc.lock().unwrap();
+            let may_move = gimbal.last_error_message.is_none() && !ota::is_updating();
+            if gimbal.is_driving() && may_move {
+                // short slices keep continuous motion responsive to new commands
+                gimbal.drive_tick(DRIVE_SLICE);
+                continue;
+            }
+            if gimbal.is_playing() && may_move {
+                gimbal.timeline_tick();
+                continue;
+            }
+            if gimbal.is_tracking() && may_move {
+                gimbal.track_tick();
+                continue;
+            }
+            if gimbal.is_capturing() && may_move {
+                let progress = |gimbal: &Gimbal| {
+                    (gimbal.capture()).map(|job| (job.paused || job.is_finished(), job.frame))
+                };
+                let before = progress(&*gimbal);
+                // frames are seconds apart, so the usual poll rate will do
+                gimbal.capture_tick();
+                let after = progress(&*gimbal);
+                // reshooting a few frames after a restart beats wearing out the
+                // flash, so only stopping is saved straight away
+                let stopped = |progress: Option<(bool, u32)>| progress.map(|(stopped, _)| stopped);
+                let is_due = bookmarked.elapsed() >= BOOKMARK_INTERVAL;
+                if after != before && (is_due || stopped(after) != stopped(before)) {
+                    save_capture(&gimbal, &store_arc, false);
+                    bookmarked = Instant::now();
                 }
-                Cmd::ProcessGcode(mv) => {
-                    let mut gimbal = gimbal_arc.lock().unwrap();
-                    if gimbal.last_error_message.is_none() {
-                        match gimbal.process_gcode(mv) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                gimbal.last_error_message = Some(e.to_string());
-                                log::error!("failed to process gcode: {e}. restart required");
-                            }
+            }
+        }
+
+        FreeRtos::delay_ms(100);
+    }
+}
+
+fn start_network(
+    ip_info: IpInfo,
+    cmds: &CmdQueue,
+    gimbal_arc: &Arc<Mutex<Gimbal>>,
+    store_arc: &Arc<Mutex<Store>>,
+    gs232_config: Option<&Gs232Config>,
+) -> anyhow::Result<EspHttpServer<'static>> {
+    let firmware = FirmwareInfo::read()?;
+    log::info!("firmware: {firmware:?}");
+    let server = server::start(
+        ip_info,
+        cmds.clone(),
+        gimbal_arc.clone(),
+        store_arc.clone(),
+        firmware.clone(),
+    )?;
+
+    if let Some(mqtt_config) = MqttConfig::from_env() {
+        if let Err(e) = mqtt::start(
+            mqtt_config,
+            cmds.clone(),
+            gimbal_arc.clone(),
+            firmware.clone(),
+        ) {
+            log::error!("failed to start mqtt: {e}");
+        }
+    }
+
+    match visca_server::port_from_env() {
+        Ok(Some(port)) => {
+            if let Err(e) = visca_server::start(port, cmds.clone(), gimbal_arc.clone()) {
+                log::error!("failed to start visca: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid visca config: {e}"),
+    }
+
+    match DmxConfig::from_env() {
+        Ok(Some(dmx_config)) => {
+            if let Err(e) = dmx_server::start(dmx_config, cmds.clone()) {
+                log::error!("failed to start dmx: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid dmx config: {e}"),
+    }
+
+    match FreedConfig::from_env() {
+        Ok(Some(freed_config)) => {
+            let position = gimbal_arc.lock().unwrap().position();
+            if let Err(e) = freed_sender::start(freed_config, position) {
+                log::error!("failed to start freed: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid freed config: {e}"),
+    }
+
+    match OscConfig::from_env() {
+        Ok(Some(osc_config)) => {
+            let position = gimbal_arc.lock().unwrap().position();
+            if let Err(e) = osc_server::start(osc_config, cmds.clone(), position) {
+                log::error!("failed to start osc: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid osc config: {e}"),
+    }
+
+    match rotctld_server::port_from_env() {
+        Ok(Some(port)) => {
+            if let Err(e) = rotctld_server::start(port, cmds.clone(), gimbal_arc.clone()) {
+                log::error!("failed to start rotctld: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid rotctld config: {e}"),
+    }
+
+    match gcode_server::port_from_env() {
+        Ok(Some(port)) => {
+            if let Err(e) = gcode_server::start(port, cmds.clone(), gimbal_arc.clone()) {
+                log::error!("failed to start gcode tcp: {e}");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("invalid gcode tcp config: {e}"),
+    }
+
+    if let Some(Gs232Config {
+        variant,
+        tcp_port: Some(port),
+        ..
+    }) = gs232_config
+    {
+        if let Err(e) = gs232_server::start_tcp(*port, *variant, cmds.clone(), gimbal_arc.clone()) {
+            log::error!("failed to start gs232 tcp: {e}");
+        }
+    }
+
+    // made it onto the network, so this image is good. without this, the
+    // bootloader rolls back to the previous image on the next reset.
+    ota::mark_running_slot_valid()?;
+
+    Ok(server)
+}
+
+/// Runs a command off the queue, returning why when it didn't, for those that
+/// asked with `Cmd::Reply`.
+fn process_cmd(
+    cmd: Cmd,
+    cmds: &CmdQueue,
+    gimbal_arc: &Arc<Mutex<Gimbal>>,
+    store_arc: &Arc<Mutex<Store>>,
+) -> Result<(), String> {
+    match cmd {
+        Cmd::ClearCmdQueue => {
+            let mut cmds = cmds.lock().unwrap();
+            cmds.clear();
+        }
+        Cmd::Stop => gimbal_arc.lock().unwrap().stop(),
+        cmd if ota::is_updating() => {
+            log::warn!("firmware update in progress, dropping command: {cmd:?}");
+            return Err("firmware update in progress".into());
+        }
+        cmd => {
+            let mut gimbal = gimbal_arc.lock().unwrap();
+            if let Some(message) = &gimbal.last_error_message {
+                return Err(message.clone());
+            }
+            let is_home = cmd == Cmd::ProcessGcode(Gcode::G28Home);
+            let sets_work_offsets = matches!(
+                cmd,
+                Cmd::ProcessGcode(Gcode::G10SetWorkOffset(..) | Gcode::G54SelectWorkOffset(_))
+            );
+            let sets_presets = matches!(
+                cmd,
+                Cmd::SetPreset(..)
+                    | Cmd::ClearPreset(_)
+                    | Cmd::ProcessGcode(Gcode::M70SavePreset(_) | Gcode::M72DeletePreset(_))
+            );
+            let sets_capture = matches!(cmd, Cmd::StartCapture(_) | Cmd::Capture(_));
+            let starts_capture = matches!(cmd, Cmd::StartCapture(_));
+            let description = format!("{cmd:?}");
+            match gimbal.process_cmd(cmd) {
+                Ok(_) => {
+                    events::publish(Event::Completed { cmd: description });
+                    if sets_work_offsets {
+                        let work_offsets = gimbal.work_offsets();
+                        if let Err(e) = store_arc.lock().unwrap().set_work_offsets(work_offsets) {
+                            log::error!("failed to save work offsets: {e}");
+                        }
+                    }
+                    if sets_presets {
+                        // copied out, as the http api takes the presets lock before the store's
+                        let presets = gimbal.presets().lock().unwrap().clone();
+                        if let Err(e) = store_arc.lock().unwrap().set_presets(&presets) {
+                            log::error!("failed to save presets: {e}");
                         }
                     }
+                    if sets_capture {
+                        save_capture(&gimbal, store_arc, starts_capture);
+                    }
+                    if is_home {
+                        events::publish(Event::Homed);
+                    }
+                }
+                Err(e) => {
+                    gimbal.last_error_message = Some(e.to_string());
+                    log::error!("failed to process {description}: {e}. restart required");
+                    events::publish(Event::Fault {
+                        message: e.to_string(),
+                    });
+                    return Err(e.to_string());
                 }
             }
         }
+    }
+    Ok(())
+}
 
-        FreeRtos::delay_ms(100);
+/// Keeps the capture job in nvs so it can resume after a restart: the whole
+/// job once as it starts, then just how far it got.
+fn save_capture(gimbal: &Gimbal, store_arc: &Arc<Mutex<Store>>, started: bool) {
+    let job = gimbal.capture().filter(|job| !job.is_finished());
+    let mut store = store_arc.lock().unwrap();
+    let res = match job {
+        Some(job) if started => store.set_capture(Some(&job.capture)),
+        Some(job) => store.set_bookmark(&job.bookmark()),
+        None => store.set_capture(None),
+    };
+    if let Err(e) = res {
+        log::error!("failed to save capture progress: {e}");
     }
 }
@@ -0,0 +1,280 @@
+use {
+    crate::{
+        cmd::{Cmd, CmdQueue},
+        events::{self, Event},
+        gcode_session::Reporter,
+        gimbal::Gimbal,
+        ota::{self, FirmwareInfo},
+        server_response::Response,
+        state::{Moving, State},
+    },
+    embedded_svc::mqtt::client::{Event as MqttEvent, Message, QoS},
+    esp_idf_svc::mqtt::client::{EspMqttClient, LwtConfiguration, MqttClientConfiguration},
+    log::{info, warn},
+    serde::Deserialize,
+    serde_json::json,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex, TryLockError,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+const MQTT_URL: &str = env!("MQTT_URL");
+const MQTT_CLIENT_ID: &str = env!("MQTT_CLIENT_ID");
+const MQTT_USER: &str = env!("MQTT_USER");
+const MQTT_PASS: &str = env!("MQTT_PASS");
+const MQTT_TOPIC_PREFIX: &str = env!("MQTT_TOPIC_PREFIX");
+const MQTT_HA_DISCOVERY: &str = env!("MQTT_HA_DISCOVERY");
+
+const STATE_INTERVAL: Duration = Duration::from_millis(500);
+const ONLINE: &[u8] = b"online";
+const OFFLINE: &[u8] = b"offline";
+
+pub struct MqttConfig {
+    pub url: &'static str,
+    pub client_id: &'static str,
+    pub user: Option<&'static str>,
+    pub pass: Option<&'static str>,
+    pub topic_prefix: &'static str,
+    pub ha_discovery: bool,
+}
+
+impl MqttConfig {
+    /// `None` when no broker is configured.
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |v: &'static str| (!v.is_empty()).then_some(v);
+        Some(Self {
+            url: non_empty(MQTT_URL)?,
+            client_id: MQTT_CLIENT_ID,
+            user: non_empty(MQTT_USER),
+            pass: non_empty(MQTT_PASS),
+            topic_prefix: MQTT_TOPIC_PREFIX.trim_end_matches('/'),
+            ha_discovery: MQTT_HA_DISCOVERY == "true",
+        })
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{name}", self.topic_prefix)
+    }
+}
+
+#[derive(Deserialize)]
+struct GcodeMessage {
+    gcode: String,
+}
+
+/// Handles a message on the command topic. Accepts the same g-code `/api/gcode`
+/// does, either bare (one command per line) or as `{"gcode": "..."}`, plus the
+/// `clear` and `restart` control commands. Reports are published as events.
+fn handle_command(payload: &[u8], cmds: &CmdQueue, reporter: &Reporter) {
+    let Ok(text) = std::str::from_utf8(payload) else {
+        warn!("mqtt: ignoring non utf-8 command");
+        return;
+    };
+    let text = match serde_json::from_str::<GcodeMessage>(text) {
+        Ok(msg) => msg.gcode,
+        Err(_) => text.to_string(),
+    };
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line == "restart" {
+            esp_idf_svc::hal::reset::restart();
+        }
+        if ota::is_updating() {
+            warn!("mqtt: firmware update in progress, dropping command: {line}");
+            continue;
+        }
+        match Cmd::of_str(line) {
+            Ok(cmd) => {
+                let report = match &cmd {
+                    Cmd::ProcessGcode(gcode) => reporter.report(gcode),
+                    _ => None,
+                };
+                match report {
+                    Some(report) => events::publish(Event::Report {
+                        gcode: line.to_string(),
+                        report,
+                    }),
+                    None => cmds.lock().unwrap().push_back(cmd),
+                }
+            }
+            Err(err) => {
+                warn!("mqtt: {err}");
+                events::publish(Event::Fault { message: err });
+            }
+        }
+    }
+}
+
+fn publish_discovery(client: &mut EspMqttClient, config: &MqttConfig) {
+    let id = config.client_id;
+    let device = json!({ "identifiers": [id], "name": id, "manufacturer": "gimbal-motion" });
+    let availability = config.topic("status");
+    let state = config.topic("state");
+    let entities = [
+        (
+            "sensor",
+            "pan",
+            json!({
+                "name": "Pan",
+                "unit_of_measurement": "°",
+                "state_topic": state,
+                "value_template": "{{ value_json.data.pos_degrees[0] | round(2) }}",
+            }),
+        ),
+        (
+            "sensor",
+            "tilt",
+            json!({
+                "name": "Tilt",
+                "unit_of_measurement": "°",
+                "state_topic": state,
+                "value_template": "{{ value_json.data.pos_degrees[1] | round(2) }}",
+            }),
+        ),
+        (
+            "sensor",
+            "lifecycle",
+            json!({
+                "name": "State",
+                "state_topic": state,
+                "value_template": "{{ value_json.data.lifecycle }}",
+            }),
+        ),
+        (
+            "button",
+            "home",
+            json!({
+                "name": "Home",
+                "command_topic": config.topic("cmd"),
+                "payload_press": "G28",
+            }),
+        ),
+    ];
+    for (component, object_id, mut payload) in entities {
+        payload["unique_id"] = json!(format!("{id}_{object_id}"));
+        payload["availability_topic"] = json!(availability);
+        payload["device"] = device.clone();
+        let topic = format!("homeassistant/{component}/{id}/{object_id}/config");
+        if let Err(e) = client.publish(
+            &topic,
+            QoS::AtLeastOnce,
+            true,
+            payload.to_string().as_bytes(),
+        ) {
+            warn!("mqtt: failed publishing discovery to {topic}: {e}");
+        }
+    }
+}
+
+/// Connects to the broker and runs the telemetry loop on its own thread.
+///
+/// Topics, under the configured prefix:
+/// - `cmd`: commands in
+/// - `state`: retained state, as served by `/api/state`
+/// - `events`: job events, faults & reports
+/// - `status`: retained `online`/`offline`, the latter as last will
+pub fn start(
+    config: MqttConfig,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+    firmware: FirmwareInfo,
+) -> anyhow::Result<()> {
+    let status_topic = config.topic("status");
+    let cmd_topic = config.topic("cmd");
+    let state_topic = config.topic("state");
+    let events_topic = config.topic("events");
+
+    let client_configuration = MqttClientConfiguration {
+        client_id: Some(config.client_id),
+        username: config.user,
+        password: config.pass,
+        lwt: Some(LwtConfiguration {
+            topic: &status_topic,
+            payload: OFFLINE,
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }),
+        ..Default::default()
+    };
+
+    // set by the event callback, cleared by the telemetry loop once it has
+    // (re)subscribed and announced itself
+    let needs_hello = Arc::new(AtomicBool::new(false));
+    let needs_hello_cb = needs_hello.clone();
+    let cb_cmd_topic = cmd_topic.clone();
+    let reporter = Reporter::new(&gimbal_arc.lock().unwrap());
+    let position = gimbal_arc.lock().unwrap().position();
+
+    info!("mqtt: connecting to {}", config.url);
+    let mut client = EspMqttClient::new(
+        config.url,
+        &client_configuration,
+        move |event| match event {
+            Ok(MqttEvent::Connected(_)) => {
+                info!("mqtt: connected");
+                needs_hello_cb.store(true, Ordering::SeqCst);
+            }
+            Ok(MqttEvent::Disconnected) => warn!("mqtt: disconnected"),
+            Ok(MqttEvent::Received(msg)) if msg.topic() == Some(cb_cmd_topic.as_str()) => {
+                handle_command(msg.data(), &cmds, &reporter);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("mqtt: {e}"),
+        },
+    )?;
+
+    let events_rx = events::subscribe();
+
+    thread::Builder::new()
+        .name("mqtt".into())
+        .stack_size(8192)
+        .spawn(move || {
+            let mut last_state = String::new();
+            loop {
+                if needs_hello.swap(false, Ordering::SeqCst) {
+                    let _ = client.subscribe(&cmd_topic, QoS::AtLeastOnce);
+                    let _ = client.publish(&status_topic, QoS::AtLeastOnce, true, ONLINE);
+                    if config.ha_discovery {
+                        publish_discovery(&mut client, &config);
+                    }
+                    // force a fresh state publish
+                    last_state.clear();
+                }
+
+                while let Ok(event) = events_rx.try_recv() {
+                    if let Ok(payload) = serde_json::to_string(&event) {
+                        let _ = client.publish(
+                            &events_topic,
+                            QoS::AtLeastOnce,
+                            false,
+                            payload.as_bytes(),
+                        );
+                    }
+                }
+
+                let firmware = firmware.current();
+                // report the position rather than stall while the gimbal is busy moving
+                let state = match gimbal_arc.try_lock() {
+                    Ok(gimbal) => Response::ok(State::new(&gimbal, &firmware)).json().ok(),
+                    Err(TryLockError::WouldBlock) => {
+                        Response::ok(Moving::new(&position, &firmware)).json().ok()
+                    }
+                    Err(TryLockError::Poisoned(_)) => None,
+                };
+                if let Some(state) = state.filter(|s| *s != last_state) {
+                    match client.publish(&state_topic, QoS::AtMostOnce, true, state.as_bytes()) {
+                        Ok(_) => last_state = state,
+                        Err(e) => warn!("mqtt: failed publishing state: {e}"),
+                    }
+                }
+
+                thread::sleep(STATE_INTERVAL);
+            }
+        })?;
+
+    Ok(())
+}
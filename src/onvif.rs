@@ -0,0 +1,705 @@
+// Minimal ONVIF device, media & PTZ services plus WS-Discovery, as SOAP text.
+// Pan & tilt use the generic spaces, -1..1 spanning ±PAN_RANGE and ±TILT_RANGE.
+
+use crate::auth::UsernameToken;
+
+pub const DISCOVERY_PORT: u16 = 3702;
+pub const DISCOVERY_MULTICAST_ADDR: [u8; 4] = [239, 255, 255, 250];
+
+pub const DEVICE_PATH: &str = "/onvif/device_service";
+pub const MEDIA_PATH: &str = "/onvif/media_service";
+pub const PTZ_PATH: &str = "/onvif/ptz_service";
+
+pub const PAN_RANGE: f32 = 180.;
+pub const TILT_RANGE: f32 = 90.;
+
+pub const PROFILE_TOKEN: &str = "gimbal_profile";
+const NODE_TOKEN: &str = "gimbal_node";
+const CONFIGURATION_TOKEN: &str = "gimbal_ptz";
+const MAX_PRESETS: u8 = 255;
+
+const NS: &str = concat!(
+    r#"xmlns:env="http://www.w3.org/2003/05/soap-envelope" "#,
+    r#"xmlns:ter="http://www.onvif.org/ver10/error" "#,
+    r#"xmlns:tt="http://www.onvif.org/ver10/schema" "#,
+    r#"xmlns:tds="http://www.onvif.org/ver10/device/wsdl" "#,
+    r#"xmlns:trt="http://www.onvif.org/ver10/media/wsdl" "#,
+    r#"xmlns:tptz="http://www.onvif.org/ver20/ptz/wsdl""#
+);
+const DISCOVERY_NS: &str = concat!(
+    r#"xmlns:env="http://www.w3.org/2003/05/soap-envelope" "#,
+    r#"xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing" "#,
+    r#"xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery" "#,
+    r#"xmlns:dn="http://www.onvif.org/ver10/network/wsdl""#
+);
+const SPACES: &str = "http://www.onvif.org/ver10/tptz/PanTiltSpaces";
+const SCOPES: &[&str] = &[
+    "onvif://www.onvif.org/type/ptz",
+    "onvif://www.onvif.org/name/gimbal-motion",
+    "onvif://www.onvif.org/hardware/esp32",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    GetDeviceInformation,
+    GetCapabilities,
+    GetServices,
+    GetSystemDateAndTime,
+    GetScopes,
+    GetProfiles,
+    GetProfile,
+    GetNodes,
+    GetNode,
+    GetConfigurations,
+    GetConfiguration,
+    GetStatus,
+    /// Signed fractions of full speed
+    ContinuousMove {
+        pan: f32,
+        tilt: f32,
+    },
+    /// Degrees, with an optional speed as a fraction of full speed
+    AbsoluteMove {
+        pan: f32,
+        tilt: f32,
+        speed: Option<(f32, f32)>,
+    },
+    RelativeMove {
+        pan: f32,
+        tilt: f32,
+        speed: Option<(f32, f32)>,
+    },
+    Stop,
+    GotoHomePosition,
+    GetPresets,
+    GotoPreset(u8),
+    /// Overwrites the given preset, or picks a free one
+    SetPreset(Option<u8>),
+    RemovePreset(u8),
+}
+
+impl Request {
+    /// Whether the request moves the gimbal or changes its state.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Request::ContinuousMove { .. }
+                | Request::AbsoluteMove { .. }
+                | Request::RelativeMove { .. }
+                | Request::Stop
+                | Request::GotoHomePosition
+                | Request::GotoPreset(_)
+                | Request::SetPreset(_)
+                | Request::RemovePreset(_)
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Fault {
+    /// The sender, rather than the device, is at fault
+    pub is_sender: bool,
+    pub subcode: &'static str,
+    pub reason: String,
+}
+
+impl Fault {
+    pub fn sender(subcode: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            is_sender: true,
+            subcode,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn receiver(subcode: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            is_sender: false,
+            subcode,
+            reason: reason.into(),
+        }
+    }
+
+    /// SOAP 1.2 over http reports sender faults as 400, the rest as 500.
+    pub fn http_status(&self) -> u16 {
+        if self.is_sender {
+            400
+        } else {
+            500
+        }
+    }
+}
+
+pub struct Element<'a> {
+    pub name: &'a str,
+    attrs: &'a str,
+    pub content: &'a str,
+}
+
+impl<'a> Element<'a> {
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        let needle = format!("{name}=");
+        let mut from = 0;
+        while let Some(i) = self.attrs[from..].find(&needle) {
+            let start = from + i;
+            let value_start = start + needle.len();
+            from = value_start;
+            let preceded_by_space = self.attrs[..start]
+                .chars()
+                .next_back()
+                .map_or(true, char::is_whitespace);
+            let quote = self.attrs[value_start..].chars().next();
+            if let (true, Some(quote @ ('"' | '\''))) = (preceded_by_space, quote) {
+                let value = &self.attrs[value_start + 1..];
+                return value.find(quote).map(|end| &value[..end]);
+            }
+        }
+        None
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.content.trim()
+    }
+}
+
+fn local_name(qualified: &str) -> &str {
+    qualified.rsplit(':').next().unwrap_or(qualified)
+}
+
+/// Finds the first element with the given local name, whatever its prefix.
+/// An empty name matches the first element.
+pub fn element<'a>(xml: &'a str, name: &str) -> Option<Element<'a>> {
+    let mut from = 0;
+    while let Some(i) = xml[from..].find('<') {
+        let start = from + i + 1;
+        from = start;
+        let tag = &xml[start..];
+        let name_len = tag
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(tag.len());
+        let qualified = &tag[..name_len];
+        if qualified.is_empty() || qualified.starts_with(['?', '!']) {
+            continue;
+        }
+        if !name.is_empty() && local_name(qualified) != name {
+            continue;
+        }
+        let tag_end = tag.find('>')?;
+        let attrs = &tag[name_len..tag_end];
+        if let Some(attrs) = attrs.strip_suffix('/') {
+            return Some(Element {
+                name: local_name(qualified),
+                attrs,
+                content: "",
+            });
+        }
+        let body = &tag[tag_end + 1..];
+        let end = body.find(&format!("</{qualified}>"))?;
+        return Some(Element {
+            name: local_name(qualified),
+            attrs,
+            content: &body[..end],
+        });
+    }
+    None
+}
+
+fn pan_tilt(xml: &str, within: &str) -> Result<Option<(f32, f32)>, Fault> {
+    let Some(pan_tilt) = element(xml, within).and_then(|e| element(e.content, "PanTilt")) else {
+        return Ok(None);
+    };
+    let value = |axis: &str| -> Result<f32, Fault> {
+        let raw = pan_tilt.attr(axis).unwrap_or("0");
+        raw.parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .map(|v| v.clamp(-1., 1.))
+            .ok_or_else(|| Fault::sender("ter:InvalidArgVal", format!("invalid {axis} {raw}")))
+    };
+    Ok(Some((value("x")?, value("y")?)))
+}
+
+fn required_pan_tilt(xml: &str, within: &str) -> Result<(f32, f32), Fault> {
+    pan_tilt(xml, within)?
+        .ok_or_else(|| Fault::sender("ter:InvalidArgVal", format!("missing {within} PanTilt")))
+}
+
+fn preset_token(xml: &str) -> Result<Option<u8>, Fault> {
+    let Some(token) = element(xml, "PresetToken") else {
+        return Ok(None);
+    };
+    match token.text().parse::<u8>() {
+        Ok(id @ 1..=MAX_PRESETS) => Ok(Some(id)),
+        _ => Err(Fault::sender(
+            "ter:NoToken",
+            format!("no preset {}", token.text()),
+        )),
+    }
+}
+
+fn required_preset_token(xml: &str) -> Result<u8, Fault> {
+    preset_token(xml)?.ok_or_else(|| Fault::sender("ter:NoToken", "missing PresetToken"))
+}
+
+fn to_degrees((pan, tilt): (f32, f32)) -> (f32, f32) {
+    (pan * PAN_RANGE, tilt * TILT_RANGE)
+}
+
+/// The WS-Security `UsernameToken` in the SOAP header, if there is one.
+pub fn username_token(xml: &str) -> Option<UsernameToken<'_>> {
+    let header = element(xml, "Header")?;
+    let token = element(header.content, "UsernameToken")?.content;
+    let password = element(token, "Password")?;
+    let text = |name| element(token, name).map_or("", |element| element.text());
+    Some(UsernameToken {
+        username: text("Username"),
+        password: password.text(),
+        digest: (password.attr("Type")).is_some_and(|kind| kind.ends_with("#PasswordDigest")),
+        nonce: text("Nonce"),
+        created: text("Created"),
+    })
+}
+
+pub fn parse_request(xml: &str) -> Result<Request, Fault> {
+    let body =
+        element(xml, "Body").ok_or_else(|| Fault::sender("ter:WellFormed", "missing soap body"))?;
+    let action = element(body.content, "")
+        .ok_or_else(|| Fault::sender("ter:WellFormed", "empty soap body"))?;
+    let args = action.content;
+
+    Ok(match action.name {
+        "GetDeviceInformation" => Request::GetDeviceInformation,
+        "GetCapabilities" => Request::GetCapabilities,
+        "GetServices" => Request::GetServices,
+        "GetSystemDateAndTime" => Request::GetSystemDateAndTime,
+        "GetScopes" => Request::GetScopes,
+        "GetProfiles" => Request::GetProfiles,
+        "GetProfile" => Request::GetProfile,
+        "GetNodes" => Request::GetNodes,
+        "GetNode" => Request::GetNode,
+        "GetConfigurations" => Request::GetConfigurations,
+        "GetConfiguration" => Request::GetConfiguration,
+        "GetStatus" => Request::GetStatus,
+        "ContinuousMove" => {
+            let (pan, tilt) = required_pan_tilt(args, "Velocity")?;
+            Request::ContinuousMove { pan, tilt }
+        }
+        "AbsoluteMove" => {
+            let (pan, tilt) = to_degrees(required_pan_tilt(args, "Position")?);
+            Request::AbsoluteMove {
+                pan,
+                tilt,
+                speed: pan_tilt(args, "Speed")?,
+            }
+        }
+        "RelativeMove" => {
+            let (pan, tilt) = to_degrees(required_pan_tilt(args, "Translation")?);
+            Request::RelativeMove {
+                pan,
+                tilt,
+                speed: pan_tilt(args, "Speed")?,
+            }
+        }
+        "Stop" => Request::Stop,
+        "GotoHomePosition" => Request::GotoHomePosition,
+        "GetPresets" => Request::GetPresets,
+        "GotoPreset" => Request::GotoPreset(required_preset_token(args)?),
+        "SetPreset" => Request::SetPreset(preset_token(args)?),
+        "RemovePreset" => Request::RemovePreset(required_preset_token(args)?),
+        other => {
+            return Err(Fault::receiver(
+                "ter:ActionNotSupported",
+                format!("{other} is not supported"),
+            ))
+        }
+    })
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn envelope(body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><env:Envelope {NS}><env:Body>{body}</env:Body></env:Envelope>"#
+    )
+}
+
+pub fn fault(fault: &Fault) -> String {
+    let code = if fault.is_sender {
+        "env:Sender"
+    } else {
+        "env:Receiver"
+    };
+    envelope(&format!(
+        r#"<env:Fault><env:Code><env:Value>{code}</env:Value><env:Subcode><env:Value>{}</env:Value></env:Subcode></env:Code><env:Reason><env:Text xml:lang="en">{}</env:Text></env:Reason></env:Fault>"#,
+        fault.subcode,
+        escape(&fault.reason)
+    ))
+}
+
+/// A response element with no content, like `<tptz:StopResponse/>`.
+pub fn empty_response(prefix: &str, action: &str) -> String {
+    envelope(&format!("<{prefix}:{action}Response/>"))
+}
+
+pub fn device_information(firmware_version: &str, serial: &str) -> String {
+    envelope(&format!(
+        "<tds:GetDeviceInformationResponse><tds:Manufacturer>cdaringe</tds:Manufacturer><tds:Model>gimbal-motion</tds:Model><tds:FirmwareVersion>{}</tds:FirmwareVersion><tds:SerialNumber>{}</tds:SerialNumber><tds:HardwareId>esp32</tds:HardwareId></tds:GetDeviceInformationResponse>",
+        escape(firmware_version),
+        escape(serial)
+    ))
+}
+
+pub fn capabilities(base_url: &str) -> String {
+    envelope(&format!(
+        "<tds:GetCapabilitiesResponse><tds:Capabilities><tt:Device><tt:XAddr>{base_url}{DEVICE_PATH}</tt:XAddr></tt:Device><tt:Media><tt:XAddr>{base_url}{MEDIA_PATH}</tt:XAddr></tt:Media><tt:PTZ><tt:XAddr>{base_url}{PTZ_PATH}</tt:XAddr></tt:PTZ></tds:Capabilities></tds:GetCapabilitiesResponse>"
+    ))
+}
+
+pub fn services(base_url: &str) -> String {
+    let service = |namespace: &str, path: &str, major: u8| {
+        format!("<tds:Service><tds:Namespace>{namespace}</tds:Namespace><tds:XAddr>{base_url}{path}</tds:XAddr><tds:Version><tt:Major>{major}</tt:Major><tt:Minor>0</tt:Minor></tds:Version></tds:Service>")
+    };
+    envelope(&format!(
+        "<tds:GetServicesResponse>{}{}{}</tds:GetServicesResponse>",
+        service("http://www.onvif.org/ver10/device/wsdl", DEVICE_PATH, 1),
+        service("http://www.onvif.org/ver10/media/wsdl", MEDIA_PATH, 1),
+        service("http://www.onvif.org/ver20/ptz/wsdl", PTZ_PATH, 2),
+    ))
+}
+
+/// Calendar date & time of a unix timestamp, in UTC.
+pub fn civil_time(unix_secs: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = (unix_secs / 86400) as i64;
+    let secs = (unix_secs % 86400) as u32;
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Unix seconds of a UTC `xsd:dateTime` like `2010-09-16T07:50:45Z`, with
+/// any fraction of a second dropped.
+pub fn parse_utc(text: &str) -> Option<u64> {
+    let text = text.trim().strip_suffix('Z')?;
+    let (date, time) = text.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let time = time.split('.').next()?;
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+pub fn format_utc(unix_secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(unix_secs);
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+pub fn system_date_and_time(unix_secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(unix_secs);
+    envelope(&format!(
+        "<tds:GetSystemDateAndTimeResponse><tds:SystemDateAndTime><tt:DateTimeType>Manual</tt:DateTimeType><tt:DaylightSavings>false</tt:DaylightSavings><tt:UTCDateTime><tt:Time><tt:Hour>{hour}</tt:Hour><tt:Minute>{minute}</tt:Minute><tt:Second>{second}</tt:Second></tt:Time><tt:Date><tt:Year>{year}</tt:Year><tt:Month>{month}</tt:Month><tt:Day>{day}</tt:Day></tt:Date></tt:UTCDateTime></tds:SystemDateAndTime></tds:GetSystemDateAndTimeResponse>"
+    ))
+}
+
+pub fn scopes() -> String {
+    let scopes: String = SCOPES
+        .iter()
+        .map(|scope| format!("<tds:Scopes><tt:ScopeDef>Fixed</tt:ScopeDef><tt:ScopeItem>{scope}</tt:ScopeItem></tds:Scopes>"))
+        .collect();
+    envelope(&format!(
+        "<tds:GetScopesResponse>{scopes}</tds:GetScopesResponse>"
+    ))
+}
+
+// "PantTilt" is how the schema spells it
+fn ptz_configuration(tag: &str) -> String {
+    format!(
+        r#"<{tag} token="{CONFIGURATION_TOKEN}"><tt:Name>{CONFIGURATION_TOKEN}</tt:Name><tt:UseCount>1</tt:UseCount><tt:NodeToken>{NODE_TOKEN}</tt:NodeToken><tt:DefaultAbsolutePantTiltPositionSpace>{SPACES}/PositionGenericSpace</tt:DefaultAbsolutePantTiltPositionSpace><tt:DefaultRelativePanTiltTranslationSpace>{SPACES}/TranslationGenericSpace</tt:DefaultRelativePanTiltTranslationSpace><tt:DefaultContinuousPanTiltVelocitySpace>{SPACES}/VelocityGenericSpace</tt:DefaultContinuousPanTiltVelocitySpace><tt:DefaultPTZSpeed><tt:PanTilt x="1" y="1" space="{SPACES}/GenericSpeedSpace"/></tt:DefaultPTZSpeed><tt:DefaultPTZTimeout>PT10S</tt:DefaultPTZTimeout></{tag}>"#
+    )
+}
+
+fn profile(tag: &str) -> String {
+    format!(
+        r#"<{tag} token="{PROFILE_TOKEN}" fixed="true"><tt:Name>gimbal</tt:Name>{}</{tag}>"#,
+        ptz_configuration("tt:PTZConfiguration")
+    )
+}
+
+pub fn profiles() -> String {
+    envelope(&format!(
+        "<trt:GetProfilesResponse>{}</trt:GetProfilesResponse>",
+        profile("trt:Profiles")
+    ))
+}
+
+pub fn profile_response() -> String {
+    envelope(&format!(
+        "<trt:GetProfileResponse>{}</trt:GetProfileResponse>",
+        profile("trt:Profile")
+    ))
+}
+
+fn node(tag: &str) -> String {
+    let range = |space: &str, min: i8| {
+        format!("<tt:URI>{SPACES}/{space}</tt:URI><tt:XRange><tt:Min>{min}</tt:Min><tt:Max>1</tt:Max></tt:XRange><tt:YRange><tt:Min>{min}</tt:Min><tt:Max>1</tt:Max></tt:YRange>")
+    };
+    format!(
+        r#"<{tag} token="{NODE_TOKEN}" FixedHomePosition="true"><tt:Name>gimbal</tt:Name><tt:SupportedPTZSpaces><tt:AbsolutePanTiltPositionSpace>{}</tt:AbsolutePanTiltPositionSpace><tt:RelativePanTiltTranslationSpace>{}</tt:RelativePanTiltTranslationSpace><tt:ContinuousPanTiltVelocitySpace>{}</tt:ContinuousPanTiltVelocitySpace><tt:PanTiltSpeedSpace><tt:URI>{SPACES}/GenericSpeedSpace</tt:URI><tt:XRange><tt:Min>0</tt:Min><tt:Max>1</tt:Max></tt:XRange></tt:PanTiltSpeedSpace></tt:SupportedPTZSpaces><tt:MaximumNumberOfPresets>{MAX_PRESETS}</tt:MaximumNumberOfPresets><tt:HomeSupported>true</tt:HomeSupported></{tag}>"#,
+        range("PositionGenericSpace", -1),
+        range("TranslationGenericSpace", -1),
+        range("VelocityGenericSpace", -1),
+    )
+}
+
+pub fn nodes() -> String {
+    envelope(&format!(
+        "<tptz:GetNodesResponse>{}</tptz:GetNodesResponse>",
+        node("tptz:PTZNode")
+    ))
+}
+
+pub fn node_response() -> String {
+    envelope(&format!(
+        "<tptz:GetNodeResponse>{}</tptz:GetNodeResponse>",
+        node("tptz:PTZNode")
+    ))
+}
+
+pub fn configurations() -> String {
+    envelope(&format!(
+        "<tptz:GetConfigurationsResponse>{}</tptz:GetConfigurationsResponse>",
+        ptz_configuration("tptz:PTZConfiguration")
+    ))
+}
+
+pub fn configuration_response() -> String {
+    envelope(&format!(
+        "<tptz:GetConfigurationResponse>{}</tptz:GetConfigurationResponse>",
+        ptz_configuration("tptz:PTZConfiguration")
+    ))
+}
+
+fn position_vector(tag: &str, (pan, tilt): (f32, f32)) -> String {
+    let x = (pan / PAN_RANGE).clamp(-1., 1.);
+    let y = (tilt / TILT_RANGE).clamp(-1., 1.);
+    format!(
+        r#"<{tag}><tt:PanTilt x="{x:.6}" y="{y:.6}" space="{SPACES}/PositionGenericSpace"/></{tag}>"#
+    )
+}
+
+pub struct Status<'a> {
+    /// Degrees
+    pub position: (f32, f32),
+    pub is_moving: bool,
+    pub error: Option<&'a str>,
+    pub unix_secs: u64,
+}
+
+pub fn status(status: &Status) -> String {
+    let move_status = if status.is_moving { "MOVING" } else { "IDLE" };
+    let error = status
+        .error
+        .map(|e| format!("<tt:Error>{}</tt:Error>", escape(e)))
+        .unwrap_or_default();
+    envelope(&format!(
+        "<tptz:GetStatusResponse><tptz:PTZStatus>{}<tt:MoveStatus><tt:PanTilt>{move_status}</tt:PanTilt></tt:MoveStatus>{error}<tt:UtcTime>{}</tt:UtcTime></tptz:PTZStatus></tptz:GetStatusResponse>",
+        position_vector("tt:Position", status.position),
+        format_utc(status.unix_secs)
+    ))
+}
+
+/// Presets as (id, degrees). Tokens are the numeric ids shared with the other
+/// protocols.
+pub fn presets(presets: &[(u8, (f32, f32))]) -> String {
+    let presets: String = presets
+        .iter()
+        .map(|(id, position)| {
+            format!(
+                r#"<tptz:Preset token="{id}"><tt:Name>{id}</tt:Name>{}</tptz:Preset>"#,
+                position_vector("tt:PTZPosition", *position)
+            )
+        })
+        .collect();
+    envelope(&format!(
+        "<tptz:GetPresetsResponse>{presets}</tptz:GetPresetsResponse>"
+    ))
+}
+
+pub fn set_preset_response(id: u8) -> String {
+    envelope(&format!(
+        "<tptz:SetPresetResponse><tptz:PresetToken>{id}</tptz:PresetToken></tptz:SetPresetResponse>"
+    ))
+}
+
+/// A device uuid derived from its mac. The counter makes message ids unique.
+pub fn uuid(mac: &[u8; 6], counter: u32) -> String {
+    let mac: String = mac.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{counter:08x}-0000-4000-8000-{mac}")
+}
+
+/// The MessageID of a WS-Discovery probe for devices like this one.
+pub fn parse_probe(xml: &str) -> Option<&str> {
+    let body = element(xml, "Body")?;
+    let probe = element(body.content, "Probe")?;
+    // no types matches any device
+    if let Some(types) = element(probe.content, "Types") {
+        let types = types.text();
+        let is_match = types.is_empty()
+            || types
+                .split_whitespace()
+                .any(|t| matches!(local_name(t), "NetworkVideoTransmitter" | "Device"));
+        if !is_match {
+            return None;
+        }
+    }
+    Some(element(xml, "MessageID")?.text())
+}
+
+pub fn probe_match(message_id: &str, relates_to: &str, device_uuid: &str, xaddr: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><env:Envelope {DISCOVERY_NS}><env:Header><wsa:MessageID>uuid:{message_id}</wsa:MessageID><wsa:RelatesTo>{}</wsa:RelatesTo><wsa:To>http://schemas.xmlsoap.org/ws/2004/08/addressing/role/anonymous</wsa:To><wsa:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/ProbeMatches</wsa:Action></env:Header><env:Body><d:ProbeMatches><d:ProbeMatch><wsa:EndpointReference><wsa:Address>urn:uuid:{device_uuid}</wsa:Address></wsa:EndpointReference><d:Types>dn:NetworkVideoTransmitter</d:Types><d:Scopes>{}</d:Scopes><d:XAddrs>{xaddr}</d:XAddrs><d:MetadataVersion>1</d:MetadataVersion></d:ProbeMatch></d:ProbeMatches></env:Body></env:Envelope>"#,
+        escape(relates_to),
+        SCOPES.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // as sent by ONVIF Device Manager
+    const CONTINUOUS_MOVE: &str = r#"<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope"><s:Body xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema"><ContinuousMove xmlns="http://www.onvif.org/ver20/ptz/wsdl"><ProfileToken>gimbal_profile</ProfileToken><Velocity><PanTilt x="-0.5" y="0.25" xmlns="http://www.onvif.org/ver10/schema"/></Velocity></ContinuousMove></s:Body></s:Envelope>"#;
+
+    const ABSOLUTE_MOVE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" xmlns:tptz="http://www.onvif.org/ver20/ptz/wsdl" xmlns:tt="http://www.onvif.org/ver10/schema">
+  <soap:Header/>
+  <soap:Body>
+    <tptz:AbsoluteMove>
+      <tptz:ProfileToken>gimbal_profile</tptz:ProfileToken>
+      <tptz:Position>
+        <tt:PanTilt x="0.5" y="-1" space="http://www.onvif.org/ver10/tptz/PanTiltSpaces/PositionGenericSpace"/>
+      </tptz:Position>
+      <tptz:Speed>
+        <tt:PanTilt x="0.2" y="0.2"/>
+      </tptz:Speed>
+    </tptz:AbsoluteMove>
+  </soap:Body>
+</soap:Envelope>"#;
+
+    const PROBE: &str = r#"<?xml version="1.0" encoding="utf-8"?><Envelope xmlns:dn="http://www.onvif.org/ver10/network/wsdl" xmlns="http://www.w3.org/2003/05/soap-envelope"><Header><wsa:MessageID xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing">uuid:6b6d0a2b-2c1b-4a3b-9a3b-0f2f5c6a1e01</wsa:MessageID><wsa:To xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing">urn:schemas-xmlsoap-org:ws:2005:04:discovery</wsa:To><wsa:Action xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing">http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</wsa:Action></Header><Body><Probe xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns="http://schemas.xmlsoap.org/ws/2005/04/discovery"><Types>dn:NetworkVideoTransmitter</Types><Scopes /></Probe></Body></Envelope>"#;
+
+    #[test]
+    fn test_continuous_move() {
+        assert_eq!(
+            parse_request(CONTINUOUS_MOVE),
+            Ok(Request::ContinuousMove {
+                pan: -0.5,
+                tilt: 0.25
+            })
+        );
+    }
+
+    #[test]
+    fn test_absolute_move() {
+        assert_eq!(
+            parse_request(ABSOLUTE_MOVE),
+            Ok(Request::AbsoluteMove {
+                pan: 90.,
+                tilt: -90.,
+                speed: Some((0.2, 0.2)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_presets_and_faults() {
+        let goto = ABSOLUTE_MOVE.replace("AbsoluteMove", "GotoPreset").replace(
+            "<tptz:Position>",
+            "<tptz:PresetToken>3</tptz:PresetToken><tptz:Position>",
+        );
+        assert_eq!(parse_request(&goto), Ok(Request::GotoPreset(3)));
+        let set = CONTINUOUS_MOVE.replace("ContinuousMove", "SetPreset");
+        assert_eq!(parse_request(&set), Ok(Request::SetPreset(None)));
+
+        let unsupported = CONTINUOUS_MOVE.replace("ContinuousMove", "GetImagingSettings");
+        let err = parse_request(&unsupported).unwrap_err();
+        assert_eq!(err.subcode, "ter:ActionNotSupported");
+        assert_eq!(err.http_status(), 500);
+        let missing = CONTINUOUS_MOVE.replace("PanTilt", "Zoom");
+        assert_eq!(parse_request(&missing).unwrap_err().http_status(), 400);
+    }
+
+    #[test]
+    fn test_status() {
+        let xml = status(&Status {
+            position: (-90., 45.),
+            is_moving: true,
+            error: None,
+            unix_secs: 1_700_000_000,
+        });
+        assert!(xml.contains(r#"<tt:PanTilt x="-0.500000" y="0.500000""#));
+        assert!(xml.contains("<tt:PanTilt>MOVING</tt:PanTilt>"));
+        assert!(xml.contains("<tt:UtcTime>2023-11-14T22:13:20Z</tt:UtcTime>"));
+    }
+
+    #[test]
+    fn test_username_token() {
+        let xml = ABSOLUTE_MOVE.replace(
+            "<soap:Header/>",
+            r#"<soap:Header><wsse:Security xmlns:wsse="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd" xmlns:wsu="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd"><wsse:UsernameToken><wsse:Username>user</wsse:Username><wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">tuOSpGlFlIXsozq4HFNeeGeFLEI=</wsse:Password><wsse:Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary">LKqI6G/AikKCQrN0zqZFlg==</wsse:Nonce><wsu:Created>2010-09-16T07:50:45Z</wsu:Created></wsse:UsernameToken></wsse:Security></soap:Header>"#,
+        );
+        let token = username_token(&xml).unwrap();
+        assert_eq!(token.username, "user");
+        assert_eq!(token.password, "tuOSpGlFlIXsozq4HFNeeGeFLEI=");
+        assert!(token.digest);
+        assert_eq!(token.nonce, "LKqI6G/AikKCQrN0zqZFlg==");
+        assert_eq!(parse_utc(token.created), Some(1_284_623_445));
+        assert!(username_token(ABSOLUTE_MOVE).is_none());
+
+        assert_eq!(parse_utc("2023-11-14T22:13:20.123Z"), Some(1_700_000_000));
+        assert_eq!(parse_utc(&format_utc(951_782_400)), Some(951_782_400));
+        assert_eq!(parse_utc("2023-11-14 22:13:20"), None);
+    }
+
+    #[test]
+    fn test_discovery() {
+        let message_id = parse_probe(PROBE).unwrap();
+        assert_eq!(message_id, "uuid:6b6d0a2b-2c1b-4a3b-9a3b-0f2f5c6a1e01");
+        assert_eq!(
+            parse_probe(&PROBE.replace("dn:NetworkVideoTransmitter", "tds:Printer")),
+            None
+        );
+        let device = uuid(&[0x24, 0x0a, 0xc4, 0x01, 0x02, 0x03], 0);
+        assert_eq!(device, "00000000-0000-4000-8000-240ac4010203");
+        let reply = probe_match(
+            &device,
+            message_id,
+            &device,
+            "http://10.0.0.2/onvif/device_service",
+        );
+        assert!(reply.contains(&format!("<wsa:RelatesTo>{message_id}</wsa:RelatesTo>")));
+    }
+}
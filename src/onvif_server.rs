@@ -0,0 +1,266 @@
+use {
+    crate::{
+        auth::Auth,
+        cmd::{self, Cmd, CmdQueue},
+        gimbal::{Gimbal, Position, MAX_VELOCITY},
+        onvif::{self, Fault, Request, Status},
+        presets::{self, Presets},
+    },
+    esp_idf_svc::sys::{esp, esp_efuse_mac_get_default},
+    log::{info, warn},
+    std::{
+        net::{Ipv4Addr, UdpSocket},
+        sync::{Arc, Mutex},
+        thread,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Answers ONVIF SOAP requests for all three services. Clients pick the
+/// service by path, but actions are unique across them, so one dispatcher
+/// serves every path.
+pub struct Onvif {
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+    position: Arc<Position>,
+    presets: Arc<Mutex<Presets>>,
+    auth: Arc<Auth>,
+    firmware_version: String,
+    base_url: String,
+    mac: [u8; 6],
+}
+
+/// Seconds a WS-Security token's `Created` may be from our clock, either way
+const MAX_TOKEN_AGE: u64 = 300;
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_mac() -> anyhow::Result<[u8; 6]> {
+    let mut mac = [0; 6];
+    esp!(unsafe { esp_efuse_mac_get_default(mac.as_mut_ptr()) })?;
+    Ok(mac)
+}
+
+impl Onvif {
+    pub fn new(
+        ip: Ipv4Addr,
+        cmds: CmdQueue,
+        gimbal_arc: Arc<Mutex<Gimbal>>,
+        auth: Arc<Auth>,
+        firmware_version: String,
+    ) -> anyhow::Result<Self> {
+        let (position, presets) = {
+            let gimbal = gimbal_arc.lock().unwrap();
+            (gimbal.position(), gimbal.presets())
+        };
+        Ok(Self {
+            cmds,
+            gimbal_arc,
+            position,
+            presets,
+            auth,
+            firmware_version,
+            base_url: format!("http://{ip}"),
+            mac: read_mac()?,
+        })
+    }
+
+    fn enqueue(&self, cmds: impl IntoIterator<Item = Cmd>) {
+        self.cmds.lock().unwrap().extend(cmds);
+    }
+
+    /// `cmd` at the request's own speed, if it has one, leaving the
+    /// configured velocities alone.
+    fn at_speed(speed: Option<(f32, f32)>, cmd: Cmd) -> Cmd {
+        match speed {
+            Some((pan, tilt)) => Cmd::AtVelocity(
+                pan.abs().clamp(0.01, 1.) * MAX_VELOCITY,
+                tilt.abs().clamp(0.01, 1.) * MAX_VELOCITY,
+                Box::new(cmd),
+            ),
+            None => cmd,
+        }
+    }
+
+    fn status(&self) -> String {
+        let queued = !self.cmds.lock().unwrap().is_empty();
+        // moves hold the gimbal, so a busy lock means it's moving
+        let (is_moving, error) = match self.gimbal_arc.try_lock() {
+            Ok(gimbal) => (
+                queued || gimbal.is_driving(),
+                gimbal.last_error_message.clone(),
+            ),
+            Err(_) => (true, None),
+        };
+        onvif::status(&Status {
+            position: self.position.degrees(),
+            is_moving,
+            error: error.as_deref(),
+            unix_secs: unix_secs(),
+        })
+    }
+
+    fn execute(&self, request: Request) -> Result<String, Fault> {
+        let serial: String = self.mac.iter().map(|b| format!("{b:02X}")).collect();
+        Ok(match request {
+            Request::GetDeviceInformation => {
+                onvif::device_information(&self.firmware_version, &serial)
+            }
+            Request::GetCapabilities => onvif::capabilities(&self.base_url),
+            Request::GetServices => onvif::services(&self.base_url),
+            Request::GetSystemDateAndTime => onvif::system_date_and_time(unix_secs()),
+            Request::GetScopes => onvif::scopes(),
+            Request::GetProfiles => onvif::profiles(),
+            Request::GetProfile => onvif::profile_response(),
+            Request::GetNodes => onvif::nodes(),
+            Request::GetNode => onvif::node_response(),
+            Request::GetConfigurations => onvif::configurations(),
+            Request::GetConfiguration => onvif::configuration_response(),
+            Request::GetStatus => self.status(),
+            Request::ContinuousMove { pan, tilt } => {
+                self.enqueue([Cmd::Drive(pan * MAX_VELOCITY, tilt * MAX_VELOCITY)]);
+                onvif::empty_response("tptz", "ContinuousMove")
+            }
+            Request::AbsoluteMove { pan, tilt, speed } => {
+                self.enqueue([Self::at_speed(speed, Cmd::MoveTo(Some(pan), Some(tilt)))]);
+                onvif::empty_response("tptz", "AbsoluteMove")
+            }
+            Request::RelativeMove { pan, tilt, speed } => {
+                self.enqueue([Self::at_speed(speed, Cmd::MoveBy(Some(pan), Some(tilt)))]);
+                onvif::empty_response("tptz", "RelativeMove")
+            }
+            Request::Stop => {
+                cmd::stop(&self.cmds);
+                onvif::empty_response("tptz", "Stop")
+            }
+            Request::GotoHomePosition => {
+                self.enqueue([Cmd::MoveTo(Some(0.), Some(0.))]);
+                onvif::empty_response("tptz", "GotoHomePosition")
+            }
+            Request::GetPresets => {
+                // only numbered presets have ONVIF tokens
+                let presets: Vec<_> = {
+                    let work_offsets = self.gimbal_arc.lock().unwrap().work_offsets().clone();
+                    (self.presets.lock().unwrap().iter())
+                        .filter_map(|(name, preset)| {
+                            let position = (preset.pan, preset.tilt);
+                            Some((name.parse().ok()?, work_offsets.to_work(position)))
+                        })
+                        .collect()
+                };
+                onvif::presets(&presets)
+            }
+            Request::GotoPreset(id) => {
+                let name = id.to_string();
+                if !self.presets.lock().unwrap().contains_key(&name) {
+                    return Err(Fault::sender("ter:NoToken", format!("no preset {id}")));
+                }
+                self.enqueue([Cmd::RecallPreset(name)]);
+                onvif::empty_response("tptz", "GotoPreset")
+            }
+            Request::SetPreset(id) => {
+                let id = {
+                    let taken = self.presets.lock().unwrap();
+                    let is_free = |id: &u8| !taken.contains_key(&id.to_string());
+                    let id = match id {
+                        Some(id) => Some(id),
+                        None => (1..=u8::MAX).find(is_free),
+                    };
+                    id.filter(|id| !is_free(id) || taken.len() < presets::MAX_PRESETS)
+                        .ok_or_else(|| Fault::receiver("ter:TooManyPresets", "no free preset"))?
+                };
+                self.enqueue([Cmd::SetPreset(id.to_string(), None)]);
+                onvif::set_preset_response(id)
+            }
+            Request::RemovePreset(id) => {
+                self.enqueue([Cmd::ClearPreset(id.to_string())]);
+                onvif::empty_response("tptz", "RemovePreset")
+            }
+        })
+    }
+
+    /// Handles a SOAP request, returning the http status and response body.
+    /// `None` means the caller must authenticate.
+    pub fn handle(&self, body: &str, authorization: Option<&str>) -> Option<(u16, String)> {
+        let request = match onvif::parse_request(body) {
+            Ok(request) => request,
+            Err(fault) => return Some((fault.http_status(), onvif::fault(&fault))),
+        };
+        if request.is_mutating() && !self.is_authorized(body, authorization) {
+            return None;
+        }
+        Some(match self.execute(request) {
+            Ok(response) => (200, response),
+            Err(fault) => (fault.http_status(), onvif::fault(&fault)),
+        })
+    }
+
+    /// Takes an http `Authorization` header or, as ONVIF clients send, a
+    /// WS-Security `UsernameToken` created within a few minutes of our clock.
+    /// Clients read that clock with `GetSystemDateAndTime` first.
+    fn is_authorized(&self, body: &str, authorization: Option<&str>) -> bool {
+        if self.auth.is_authorized(authorization) {
+            return true;
+        }
+        let Some(token) = onvif::username_token(body) else {
+            return false;
+        };
+        let is_fresh = onvif::parse_utc(token.created)
+            .is_some_and(|created| created.abs_diff(unix_secs()) <= MAX_TOKEN_AGE);
+        if token.digest && !is_fresh {
+            warn!("onvif: rejecting a stale or undated token");
+            return false;
+        }
+        self.auth.is_authorized_token(&token)
+    }
+
+    /// Answers WS-Discovery probes so VMS software finds the gimbal.
+    pub fn start_discovery(self: &Arc<Self>) -> anyhow::Result<()> {
+        let socket = UdpSocket::bind(("0.0.0.0", onvif::DISCOVERY_PORT))?;
+        socket.join_multicast_v4(
+            &Ipv4Addr::from(onvif::DISCOVERY_MULTICAST_ADDR),
+            &Ipv4Addr::UNSPECIFIED,
+        )?;
+        info!(
+            "onvif: answering discovery on udp {}",
+            onvif::DISCOVERY_PORT
+        );
+        let onvif = self.clone();
+        thread::Builder::new()
+            .name("onvif-discovery".into())
+            .stack_size(6144)
+            .spawn(move || {
+                let device_uuid = onvif::uuid(&onvif.mac, 0);
+                let xaddr = format!("{}{}", onvif.base_url, onvif::DEVICE_PATH);
+                let mut counter: u32 = 0;
+                let mut buf = vec![0; 4096];
+                loop {
+                    let (n, peer) = match socket.recv_from(&mut buf) {
+                        Ok(received) => received,
+                        Err(e) => {
+                            warn!("onvif: discovery: {e}");
+                            continue;
+                        }
+                    };
+                    let Ok(xml) = std::str::from_utf8(&buf[..n]) else {
+                        continue;
+                    };
+                    let Some(relates_to) = onvif::parse_probe(xml) else {
+                        continue;
+                    };
+                    counter = counter.wrapping_add(1);
+                    let message_id = onvif::uuid(&onvif.mac, counter);
+                    let reply = onvif::probe_match(&message_id, relates_to, &device_uuid, &xaddr);
+                    if let Err(e) = socket.send_to(reply.as_bytes(), peer) {
+                        warn!("onvif: failed answering probe from {peer}: {e}");
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
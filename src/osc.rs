@@ -0,0 +1,305 @@
+// Open Sound Control 1.0 messages & bundles
+
+use crate::gimbal;
+
+pub const PREFIX: &str = "/gimbal";
+pub const POSITION_ADDRESS: &str = "/gimbal/position";
+
+const BUNDLE_ID: &[u8] = b"#bundle\0";
+// bundles nest, but show control software never goes deep
+const MAX_BUNDLE_DEPTH: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+impl Arg {
+    /// Faders send floats, but cue software often sends ints.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Arg::Int(i) => Some(*i as f32),
+            Arg::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub address: String,
+    pub args: Vec<Arg>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Pan(f32),
+    Tilt(f32),
+    MoveTo(f32, f32),
+    MoveBy(f32, f32),
+    Velocity(f32, f32),
+    Drive(f32, f32),
+    Home,
+    Stop,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OscError {
+    Truncated,
+    Malformed,
+    UnsupportedType(char),
+    UnknownAddress(String),
+    BadArgs(String),
+}
+
+fn padded_len(len: usize) -> usize {
+    (len + 4) & !3
+}
+
+/// Reads a null terminated, 4 byte aligned string.
+fn read_str(buf: &[u8]) -> Result<(&str, &[u8]), OscError> {
+    let end = buf
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(OscError::Truncated)?;
+    let s = std::str::from_utf8(&buf[..end]).map_err(|_| OscError::Malformed)?;
+    let rest = buf.get(padded_len(end)..).ok_or(OscError::Truncated)?;
+    Ok((s, rest))
+}
+
+fn read_u32(buf: &[u8]) -> Result<([u8; 4], &[u8]), OscError> {
+    let bytes = buf.get(..4).ok_or(OscError::Truncated)?;
+    Ok(([bytes[0], bytes[1], bytes[2], bytes[3]], &buf[4..]))
+}
+
+fn decode_message(buf: &[u8]) -> Result<Message, OscError> {
+    let (address, rest) = read_str(buf)?;
+    if !address.starts_with('/') {
+        return Err(OscError::Malformed);
+    }
+    // type tags are optional in old senders, meaning no arguments
+    if rest.is_empty() {
+        return Ok(Message {
+            address: address.into(),
+            args: vec![],
+        });
+    }
+    let (tags, mut rest) = read_str(rest)?;
+    let tags = tags.strip_prefix(',').ok_or(OscError::Malformed)?;
+    let mut args = Vec::with_capacity(tags.len());
+    for tag in tags.chars() {
+        let arg = match tag {
+            'i' => {
+                let (bytes, next) = read_u32(rest)?;
+                rest = next;
+                Arg::Int(i32::from_be_bytes(bytes))
+            }
+            'f' => {
+                let (bytes, next) = read_u32(rest)?;
+                rest = next;
+                Arg::Float(f32::from_be_bytes(bytes))
+            }
+            's' => {
+                let (s, next) = read_str(rest)?;
+                rest = next;
+                Arg::Str(s.into())
+            }
+            'T' => Arg::Bool(true),
+            'F' => Arg::Bool(false),
+            other => return Err(OscError::UnsupportedType(other)),
+        };
+        args.push(arg);
+    }
+    Ok(Message {
+        address: address.into(),
+        args,
+    })
+}
+
+fn decode_into(buf: &[u8], depth: usize, messages: &mut Vec<Message>) -> Result<(), OscError> {
+    if !buf.starts_with(BUNDLE_ID) {
+        messages.push(decode_message(buf)?);
+        return Ok(());
+    }
+    if depth >= MAX_BUNDLE_DEPTH {
+        return Err(OscError::Malformed);
+    }
+    // elements follow the id & time tag. time tags are ignored, everything
+    // runs immediately.
+    let mut rest = buf.get(16..).ok_or(OscError::Truncated)?;
+    while !rest.is_empty() {
+        let (len, next) = read_u32(rest)?;
+        let len = u32::from_be_bytes(len) as usize;
+        let element = next.get(..len).ok_or(OscError::Truncated)?;
+        decode_into(element, depth + 1, messages)?;
+        rest = &next[len..];
+    }
+    Ok(())
+}
+
+/// Decodes a packet, flattening bundles into their messages.
+pub fn decode(buf: &[u8]) -> Result<Vec<Message>, OscError> {
+    let mut messages = vec![];
+    decode_into(buf, 0, &mut messages)?;
+    Ok(messages)
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.resize(buf.len() + padded_len(s.len()) - s.len(), 0);
+}
+
+pub fn encode(message: &Message) -> Vec<u8> {
+    let mut buf = vec![];
+    write_str(&mut buf, &message.address);
+    let tags: String = std::iter::once(',')
+        .chain(message.args.iter().map(|arg| match arg {
+            Arg::Int(_) => 'i',
+            Arg::Float(_) => 'f',
+            Arg::Str(_) => 's',
+            Arg::Bool(true) => 'T',
+            Arg::Bool(false) => 'F',
+        }))
+        .collect();
+    write_str(&mut buf, &tags);
+    for arg in &message.args {
+        match arg {
+            Arg::Int(i) => buf.extend_from_slice(&i.to_be_bytes()),
+            Arg::Float(f) => buf.extend_from_slice(&f.to_be_bytes()),
+            Arg::Str(s) => write_str(&mut buf, s),
+            Arg::Bool(_) => {}
+        }
+    }
+    buf
+}
+
+pub fn position_message(pan: f32, tilt: f32) -> Message {
+    Message {
+        address: POSITION_ADDRESS.into(),
+        args: vec![Arg::Float(pan), Arg::Float(tilt)],
+    }
+}
+
+/// `None` for messages that need no action, like button releases.
+pub fn parse_command(message: &Message) -> Result<Option<Command>, OscError> {
+    let bad_args = || OscError::BadArgs(message.address.clone());
+    let floats: Vec<f32> = message
+        .args
+        .iter()
+        .map(Arg::as_f32)
+        .collect::<Option<_>>()
+        .ok_or_else(bad_args)?;
+    let action = message
+        .address
+        .strip_prefix(PREFIX)
+        .and_then(|a| a.strip_prefix('/'))
+        .ok_or_else(|| OscError::UnknownAddress(message.address.clone()))?;
+
+    let command = match (action, floats.as_slice()) {
+        ("pan", [pan]) => Command::Pan(*pan),
+        ("tilt", [tilt]) => Command::Tilt(*tilt),
+        ("moveto", [pan, tilt]) => Command::MoveTo(*pan, *tilt),
+        ("moveby", [pan, tilt]) => Command::MoveBy(*pan, *tilt),
+        ("velocity", [pan, tilt]) => {
+            gimbal::validate_velocity(*pan)
+                .and(gimbal::validate_velocity(*tilt))
+                .map_err(|e| OscError::BadArgs(format!("{}: {e}", message.address)))?;
+            Command::Velocity(*pan, *tilt)
+        }
+        ("drive", [pan, tilt]) => Command::Drive(*pan, *tilt),
+        // buttons send 1 on press and 0 on release
+        ("home" | "stop", [pressed]) if *pressed == 0. => return Ok(None),
+        ("home", [] | [_]) => Command::Home,
+        ("stop", [] | [_]) => Command::Stop,
+        ("pan" | "tilt" | "moveto" | "moveby" | "velocity" | "drive" | "home" | "stop", _) => {
+            return Err(bad_args())
+        }
+        _ => return Err(OscError::UnknownAddress(message.address.clone())),
+    };
+    Ok(Some(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let message = Message {
+            address: "/gimbal/moveto".into(),
+            args: vec![Arg::Float(12.5), Arg::Int(-3)],
+        };
+        let buf = encode(&message);
+        assert_eq!(&buf[..16], b"/gimbal/moveto\0\0");
+        assert_eq!(&buf[16..20], b",fi\0");
+        assert_eq!(buf.len(), 28);
+        assert_eq!(decode(&buf), Ok(vec![message.clone()]));
+        assert_eq!(
+            parse_command(&message),
+            Ok(Some(Command::MoveTo(12.5, -3.)))
+        );
+    }
+
+    #[test]
+    fn test_bundle() {
+        let home = encode(&Message {
+            address: "/gimbal/home".into(),
+            args: vec![],
+        });
+        let pan = encode(&Message {
+            address: "/gimbal/pan".into(),
+            args: vec![Arg::Float(90.)],
+        });
+        let mut buf = BUNDLE_ID.to_vec();
+        buf.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [&home, &pan] {
+            buf.extend((element.len() as u32).to_be_bytes());
+            buf.extend(element);
+        }
+        let commands: Vec<_> = decode(&buf)
+            .unwrap()
+            .iter()
+            .map(|m| parse_command(m).unwrap().unwrap())
+            .collect();
+        assert_eq!(commands, [Command::Home, Command::Pan(90.)]);
+    }
+
+    #[test]
+    fn test_bad_commands() {
+        let message = |address: &str, args| Message {
+            address: String::from(address),
+            args,
+        };
+        assert_eq!(
+            parse_command(&message("/gimbal/pan", vec![])),
+            Err(OscError::BadArgs("/gimbal/pan".into()))
+        );
+        assert!(matches!(
+            parse_command(&message("/other/pan", vec![Arg::Float(1.)])),
+            Err(OscError::UnknownAddress(_))
+        ));
+        // a button release
+        assert_eq!(
+            parse_command(&message("/gimbal/stop", vec![Arg::Float(0.)])),
+            Ok(None)
+        );
+        assert!(matches!(
+            parse_command(&message(
+                "/gimbal/velocity",
+                vec![Arg::Float(10.), Arg::Float(f32::NAN)]
+            )),
+            Err(OscError::BadArgs(_))
+        ));
+        assert!(matches!(
+            parse_command(&message(
+                "/gimbal/velocity",
+                vec![Arg::Int(0), Arg::Int(10)]
+            )),
+            Err(OscError::BadArgs(_))
+        ));
+        assert_eq!(decode(b"/gimbal"), Err(OscError::Truncated));
+    }
+}
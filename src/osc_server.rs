@@ -0,0 +1,131 @@
+use {
+    crate::{
+        cmd::{self, Cmd, CmdQueue},
+        gcode::Gcode,
+        gimbal::Position,
+        osc::{self, Command},
+    },
+    log::{info, warn},
+    std::{
+        net::{SocketAddr, ToSocketAddrs, UdpSocket},
+        sync::Arc,
+        thread,
+        time::Duration,
+    },
+};
+
+const OSC_PORT: &str = env!("OSC_PORT");
+const OSC_FEEDBACK: &str = env!("OSC_FEEDBACK");
+
+const FEEDBACK_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct OscConfig {
+    pub port: u16,
+    /// Where to send `/gimbal/position` updates
+    pub feedback: Option<SocketAddr>,
+}
+
+impl OscConfig {
+    /// `None` when no port is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if OSC_PORT.is_empty() {
+            return Ok(None);
+        }
+        let feedback = match OSC_FEEDBACK {
+            "" => None,
+            target => Some(
+                target
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("OSC_FEEDBACK {target} did not resolve"))?,
+            ),
+        };
+        Ok(Some(Self {
+            port: OSC_PORT.parse()?,
+            feedback,
+        }))
+    }
+}
+
+fn to_cmd(command: Command) -> Cmd {
+    match command {
+        Command::Pan(pan) => Cmd::MoveTo(Some(pan), None),
+        Command::Tilt(tilt) => Cmd::MoveTo(None, Some(tilt)),
+        Command::MoveTo(pan, tilt) => Cmd::MoveTo(Some(pan), Some(tilt)),
+        Command::MoveBy(pan, tilt) => Cmd::MoveBy(Some(pan), Some(tilt)),
+        Command::Velocity(pan, tilt) => {
+            Cmd::ProcessGcode(Gcode::M1SetVelocity(Some(pan), Some(tilt)))
+        }
+        Command::Drive(pan, tilt) => Cmd::Drive(pan, tilt),
+        Command::Home => Cmd::ProcessGcode(Gcode::G28Home),
+        Command::Stop => Cmd::Stop,
+    }
+}
+
+fn start_feedback(target: SocketAddr, position: Arc<Position>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(target)?;
+    info!("osc: sending position to {target}");
+    thread::Builder::new()
+        .name("osc-feedback".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut last = None;
+            loop {
+                let current = position.steps();
+                if last != Some(current) {
+                    let (pan, tilt) = position.degrees();
+                    let message = osc::encode(&osc::position_message(pan, tilt));
+                    // a listener that isn't up yet refuses the datagram.
+                    // retry on the next tick rather than logging at 10hz.
+                    if socket.send(&message).is_ok() {
+                        last = Some(current);
+                    }
+                }
+                thread::sleep(FEEDBACK_INTERVAL);
+            }
+        })?;
+    Ok(())
+}
+
+/// Listens for OSC messages under `/gimbal`, optionally reporting the
+/// position back.
+pub fn start(config: OscConfig, cmds: CmdQueue, position: Arc<Position>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", config.port))?;
+    info!("osc: listening on udp {}", config.port);
+    if let Some(target) = config.feedback {
+        start_feedback(target, position)?;
+    }
+
+    thread::Builder::new()
+        .name("osc".into())
+        .stack_size(6144)
+        .spawn(move || {
+            let mut buf = [0; 1024];
+            loop {
+                let (n, peer) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("osc: {e}");
+                        continue;
+                    }
+                };
+                let messages = match osc::decode(&buf[..n]) {
+                    Ok(messages) => messages,
+                    Err(e) => {
+                        warn!("osc: dropping packet from {peer}: {e:?}");
+                        continue;
+                    }
+                };
+                for message in messages {
+                    match osc::parse_command(&message) {
+                        Ok(Some(Command::Stop)) => cmd::stop(&cmds),
+                        Ok(Some(command)) => cmds.lock().unwrap().push_back(to_cmd(command)),
+                        Ok(None) => {}
+                        Err(e) => warn!("osc: {e:?}"),
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
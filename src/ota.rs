@@ -0,0 +1,134 @@
+use {
+    anyhow::anyhow,
+    embedded_svc::io::{Read, Write},
+    esp_idf_svc::ota::EspOta,
+    log::{info, warn},
+    serde::Serialize,
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+// first byte of every esp32 application image
+const IMAGE_MAGIC: u8 = 0xE9;
+const CHUNK_SIZE: usize = 4096;
+
+static IS_UPDATING: AtomicBool = AtomicBool::new(false);
+/// Read again once the running slot's marked valid, so reporting never opens
+/// the ota partitions that an update needs
+static REFRESHED: Mutex<Option<FirmwareInfo>> = Mutex::new(None);
+
+pub fn is_updating() -> bool {
+    IS_UPDATING.load(Ordering::SeqCst)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FirmwareInfo {
+    pub version: &'static str,
+    pub running_partition: String,
+    pub running_state: String,
+    pub boot_partition: String,
+    pub update_partition: String,
+}
+
+impl FirmwareInfo {
+    pub fn read() -> anyhow::Result<Self> {
+        let ota = EspOta::new()?;
+        let running = ota.get_running_slot()?;
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION"),
+            running_partition: running.label.to_string(),
+            running_state: format!("{:?}", running.state),
+            boot_partition: ota.get_boot_slot()?.label.to_string(),
+            update_partition: ota.get_update_slot()?.label.to_string(),
+        })
+    }
+
+    /// The info as read since `self`, once the running slot was marked
+    /// valid, or `self` until then.
+    pub fn current(&self) -> Self {
+        (REFRESHED.lock().unwrap().clone()).unwrap_or_else(|| self.clone())
+    }
+}
+
+/// Confirms the running image booted fine. Until this is called, a freshly
+/// flashed image is pending verification and the bootloader rolls back to the
+/// previous image on the next reset.
+pub fn mark_running_slot_valid() -> anyhow::Result<()> {
+    EspOta::new()?.mark_running_slot_valid()?;
+    info!("ota: running slot marked valid");
+    match FirmwareInfo::read() {
+        Ok(firmware) => *REFRESHED.lock().unwrap() = Some(firmware),
+        Err(e) => warn!("ota: failed to reread firmware info: {e}"),
+    }
+    Ok(())
+}
+
+/// Resets the updating flag however the update exits.
+struct UpdatingGuard;
+
+impl UpdatingGuard {
+    fn acquire() -> anyhow::Result<Self> {
+        IS_UPDATING
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .map_err(|_| anyhow!("update already in progress"))?;
+        Ok(Self)
+    }
+}
+
+impl Drop for UpdatingGuard {
+    fn drop(&mut self) {
+        IS_UPDATING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Streams a firmware image into the inactive ota partition and selects it
+/// for the next boot. Returns the number of bytes written.
+pub fn update<R>(image: &mut R) -> anyhow::Result<usize>
+where
+    R: Read,
+    R::Error: std::fmt::Debug,
+{
+    let _guard = UpdatingGuard::acquire()?;
+    let mut ota = EspOta::new()?;
+    let mut update = ota.initiate_update()?;
+    let mut buf = [0; CHUNK_SIZE];
+    let mut total = 0;
+
+    loop {
+        let n = match image.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                update.abort()?;
+                return Err(anyhow!("failed reading firmware image: {e:?}"));
+            }
+        };
+        if n == 0 {
+            break;
+        }
+        if total == 0 && buf[0] != IMAGE_MAGIC {
+            update.abort()?;
+            return Err(anyhow!("not an esp32 firmware image"));
+        }
+        if let Err(e) = update.write_all(&buf[..n]) {
+            update.abort()?;
+            return Err(anyhow!("failed writing firmware image: {e:?}"));
+        }
+        total += n;
+    }
+
+    if total == 0 {
+        update.abort()?;
+        return Err(anyhow!("empty firmware image"));
+    }
+
+    // validates the image checksum & sets it as the boot partition
+    update.complete().map_err(|e| {
+        warn!("ota: image rejected: {e}");
+        anyhow!("firmware image failed verification: {e}")
+    })?;
+    info!("ota: wrote {total} bytes, image verified");
+
+    Ok(total)
+}
@@ -0,0 +1,238 @@
+// Panoramas shot as a grid of overlapping frames, top row first, snaking back
+// & forth so the gimbal never swings back across the scene between rows.
+
+use {
+    crate::capture,
+    serde::{Deserialize, Serialize},
+};
+
+pub const MAX_SHOTS: u32 = 10_000;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Panorama {
+    /// Lens field of view, in degrees
+    pub hfov: f32,
+    pub vfov: f32,
+    /// Percent of each frame shared with its neighbours
+    pub overlap: f32,
+    /// Edges of the scene in work coordinates, as (from, to) degrees. A full
+    /// 360° pan wraps around.
+    pub pan: (f32, f32),
+    pub tilt: (f32, f32),
+    /// Seconds to wait after moving, before firing
+    #[serde(default)]
+    pub settle: f32,
+    /// Seconds to leave the camera be after firing
+    #[serde(default)]
+    pub exposure: f32,
+}
+
+/// Frame centres along one axis.
+#[derive(Debug, PartialEq)]
+struct Axis {
+    count: u32,
+    first: f32,
+    step: f32,
+}
+
+impl Axis {
+    fn new(fov: f32, overlap: f32, (from, to): (f32, f32), wraps: bool) -> Self {
+        let span = to - from;
+        let max_step = fov * (1. - overlap / 100.);
+        if wraps {
+            // the last frame overlaps the first, so frames fill the span exactly
+            let count = (span / max_step).ceil() as u32;
+            let step = span / count as f32;
+            return Self {
+                count,
+                first: from + step / 2.,
+                step,
+            };
+        }
+        if span <= fov {
+            return Self {
+                count: 1,
+                first: (from + to) / 2.,
+                step: 0.,
+            };
+        }
+        let count = ((span - fov) / max_step).ceil() as u32 + 1;
+        Self {
+            count,
+            first: from + fov / 2.,
+            step: (span - fov) / (count - 1) as f32,
+        }
+    }
+
+    fn at(&self, i: u32) -> f32 {
+        self.first + i as f32 * self.step
+    }
+}
+
+/// What a panorama will take, before starting it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Preview {
+    pub rows: u32,
+    pub columns: u32,
+    pub shots: u32,
+    pub estimated_secs: f32,
+}
+
+impl Panorama {
+    pub fn validate(&self) -> Result<(), String> {
+        let fovs = [self.hfov, self.vfov];
+        if !fovs.iter().all(|fov| *fov > 0. && *fov <= 180.) {
+            return Err("fields of view are 0 to 180 degrees".into());
+        }
+        if !(0. ..=90.).contains(&self.overlap) {
+            return Err("overlap is 0 to 90 percent".into());
+        }
+        let edges = [self.pan.0, self.pan.1, self.tilt.0, self.tilt.1];
+        if !edges.iter().all(|edge| edge.is_finite()) {
+            return Err("pan & tilt ranges aren't numbers".into());
+        }
+        if self.pan.1 < self.pan.0 || self.tilt.1 < self.tilt.0 {
+            return Err("pan & tilt ranges run from low to high".into());
+        }
+        if self.pan.1 - self.pan.0 > 360. {
+            return Err("pan covers at most 360 degrees".into());
+        }
+        let times = [self.settle, self.exposure];
+        if !times.iter().all(|t| (0. ..=capture::MAX_SECS).contains(t)) {
+            return Err(format!(
+                "settle & exposure are positive seconds, up to {}",
+                capture::MAX_SECS
+            ));
+        }
+        let (columns, rows) = (self.columns(), self.rows());
+        if u64::from(columns) * u64::from(rows) > u64::from(MAX_SHOTS) {
+            return Err(format!(
+                "{columns}x{rows} is more than {MAX_SHOTS} shots, use more overlap or a wider lens"
+            ));
+        }
+        Ok(())
+    }
+
+    fn pan_axis(&self) -> Axis {
+        let wraps = self.pan.1 - self.pan.0 >= 360.;
+        Axis::new(self.hfov, self.overlap, self.pan, wraps)
+    }
+
+    fn tilt_axis(&self) -> Axis {
+        Axis::new(self.vfov, self.overlap, self.tilt, false)
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.pan_axis().count
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.tilt_axis().count
+    }
+
+    pub fn shots(&self) -> u32 {
+        self.columns() * self.rows()
+    }
+
+    /// Where to point for `shot`, counting from 0.
+    pub fn position(&self, shot: u32) -> (f32, f32) {
+        let (pan, tilt) = (self.pan_axis(), self.tilt_axis());
+        let row = shot / pan.count;
+        let column = match row % 2 {
+            0 => shot % pan.count,
+            _ => pan.count - 1 - shot % pan.count,
+        };
+        (pan.at(column), tilt.at(tilt.count - 1 - row))
+    }
+
+    /// Rows, columns & roughly how long it takes at `velocity` degrees per
+    /// second, with `fire_secs` to trigger each shot. Excludes getting to the
+    /// first shot.
+    pub fn preview(&self, velocity: (f32, f32), fire_secs: f32) -> Preview {
+        let shots = self.shots();
+        let moving: f32 = (1..shots)
+            .map(|shot| {
+                let (from, to) = (self.position(shot - 1), self.position(shot));
+                f32::max(
+                    (to.0 - from.0).abs() / velocity.0,
+                    (to.1 - from.1).abs() / velocity.1,
+                )
+            })
+            .sum();
+        Preview {
+            rows: self.rows(),
+            columns: self.columns(),
+            shots,
+            estimated_secs: moving + shots as f32 * (self.settle + fire_secs + self.exposure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panorama() -> Panorama {
+        Panorama {
+            hfov: 40.,
+            vfov: 30.,
+            overlap: 25.,
+            pan: (-60., 60.),
+            tilt: (0., 30.),
+            settle: 1.,
+            exposure: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut panorama = panorama();
+        panorama.validate().unwrap();
+        // 80° between the outer centres in steps of at most 30°
+        assert_eq!((panorama.columns(), panorama.rows()), (4, 1));
+        assert_eq!(panorama.position(0), (-40., 15.));
+        assert_eq!(panorama.position(3), (40., 15.));
+
+        panorama.tilt = (-30., 30.);
+        assert_eq!(panorama.rows(), 3);
+        // the top row left to right, then the next right to left
+        assert_eq!(panorama.position(3), (40., 15.));
+        assert_eq!(panorama.position(4), (40., 0.));
+        assert_eq!(panorama.position(7), (-40., 0.));
+        assert_eq!(panorama.position(11), (40., -15.));
+    }
+
+    #[test]
+    fn test_wraps() {
+        let panorama = Panorama {
+            pan: (0., 360.),
+            ..panorama()
+        };
+        assert_eq!(panorama.columns(), 12);
+        assert_eq!(panorama.position(11), (345., 15.));
+    }
+
+    #[test]
+    fn test_preview() {
+        let panorama = panorama();
+        let preview = panorama.preview((20., 20.), 0.5);
+        assert_eq!(preview.shots, 4);
+        // three 80/3° moves at 20°/s, and 2s per shot
+        assert!((preview.estimated_secs - 12.).abs() < 1e-3);
+
+        let tiny = Panorama {
+            hfov: 1.,
+            vfov: 1.,
+            pan: (0., 360.),
+            tilt: (-90., 90.),
+            ..panorama
+        };
+        assert!(tiny.validate().is_err());
+
+        let endless = Panorama {
+            exposure: 1e30,
+            ..panorama
+        };
+        assert!(endless.validate().is_err());
+    }
+}
@@ -0,0 +1,320 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Protocol {
+    /// `FF addr cmd1 cmd2 data1 data2 sum`
+    D,
+    /// `A0 addr cmd1 cmd2 data1 data2 AF xor`
+    P,
+}
+
+const D_SYNC: u8 = 0xFF;
+const P_STX: u8 = 0xA0;
+const P_ETX: u8 = 0xAF;
+
+pub const MAX_SPEED: u8 = 0x3F;
+pub const TURBO_SPEED: u8 = 0xFF;
+
+// cmd2 bits
+const RIGHT: u8 = 0x02;
+const LEFT: u8 = 0x04;
+const UP: u8 = 0x08;
+const DOWN: u8 = 0x10;
+
+// extended commands, cmd2 with bit 0 set
+const SET_PRESET: u8 = 0x03;
+const CLEAR_PRESET: u8 = 0x05;
+const GOTO_PRESET: u8 = 0x07;
+const QUERY_PAN: u8 = 0x51;
+const QUERY_TILT: u8 = 0x53;
+const PAN_POSITION: u8 = 0x59;
+const TILT_POSITION: u8 = 0x5B;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub address: u8,
+    pub cmd1: u8,
+    pub cmd2: u8,
+    pub data1: u8,
+    pub data2: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Signed speeds in -1.0..=1.0 of full speed. Both zero is a stop.
+    Move {
+        pan: f32,
+        tilt: f32,
+    },
+    PresetSet(u8),
+    PresetClear(u8),
+    PresetCall(u8),
+    QueryPan,
+    QueryTilt,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PelcoError {
+    Checksum,
+    Unsupported(Frame),
+}
+
+impl Protocol {
+    pub fn frame_len(&self) -> usize {
+        match self {
+            Protocol::D => 7,
+            Protocol::P => 8,
+        }
+    }
+
+    fn is_start(&self, byte: u8) -> bool {
+        match self {
+            Protocol::D => byte == D_SYNC,
+            Protocol::P => byte == P_STX,
+        }
+    }
+}
+
+/// Decodes one complete frame. Pelco-P addresses are zero based on the wire
+/// and reported one based, like Pelco-D.
+pub fn decode(protocol: Protocol, bytes: &[u8]) -> Result<Frame, PelcoError> {
+    let frame = |b: &[u8]| Frame {
+        address: b[1],
+        cmd1: b[2],
+        cmd2: b[3],
+        data1: b[4],
+        data2: b[5],
+    };
+    match protocol {
+        Protocol::D => {
+            let sum = bytes[1..6].iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+            if bytes[0] != D_SYNC || sum != bytes[6] {
+                return Err(PelcoError::Checksum);
+            }
+            Ok(frame(bytes))
+        }
+        Protocol::P => {
+            let xor = bytes[..7].iter().fold(0u8, |acc, b| acc ^ b);
+            if bytes[0] != P_STX || bytes[6] != P_ETX || xor != bytes[7] {
+                return Err(PelcoError::Checksum);
+            }
+            let mut frame = frame(bytes);
+            frame.address = frame.address.wrapping_add(1);
+            Ok(frame)
+        }
+    }
+}
+
+pub fn encode(protocol: Protocol, frame: &Frame) -> Vec<u8> {
+    match protocol {
+        Protocol::D => {
+            let mut bytes = vec![
+                D_SYNC,
+                frame.address,
+                frame.cmd1,
+                frame.cmd2,
+                frame.data1,
+                frame.data2,
+            ];
+            bytes.push(bytes[1..].iter().fold(0u8, |acc, b| acc.wrapping_add(*b)));
+            bytes
+        }
+        Protocol::P => {
+            let mut bytes = vec![
+                P_STX,
+                frame.address.wrapping_sub(1),
+                frame.cmd1,
+                frame.cmd2,
+                frame.data1,
+                frame.data2,
+                P_ETX,
+            ];
+            bytes.push(bytes.iter().fold(0u8, |acc, b| acc ^ b));
+            bytes
+        }
+    }
+}
+
+fn speed(raw: u8) -> f32 {
+    match raw {
+        TURBO_SPEED => 1.,
+        raw => f32::from(raw.min(MAX_SPEED)) / f32::from(MAX_SPEED),
+    }
+}
+
+pub fn parse_command(frame: &Frame) -> Result<Command, PelcoError> {
+    if frame.cmd2 & 0x01 == 0 {
+        let pan = match frame.cmd2 & (LEFT | RIGHT) {
+            RIGHT => speed(frame.data1),
+            LEFT => -speed(frame.data1),
+            _ => 0.,
+        };
+        let tilt = match frame.cmd2 & (UP | DOWN) {
+            UP => speed(frame.data2),
+            DOWN => -speed(frame.data2),
+            _ => 0.,
+        };
+        return Ok(Command::Move { pan, tilt });
+    }
+    match frame.cmd2 {
+        SET_PRESET => Ok(Command::PresetSet(frame.data2)),
+        CLEAR_PRESET => Ok(Command::PresetClear(frame.data2)),
+        GOTO_PRESET => Ok(Command::PresetCall(frame.data2)),
+        QUERY_PAN => Ok(Command::QueryPan),
+        QUERY_TILT => Ok(Command::QueryTilt),
+        _ => Err(PelcoError::Unsupported(*frame)),
+    }
+}
+
+/// Position in hundredths of a degree, wrapped into 0..36000. Tilt is
+/// reported downward positive, as Pelco heads do.
+fn hundredths(degrees: f32) -> u16 {
+    ((degrees * 100.).round() as i32).rem_euclid(36000) as u16
+}
+
+pub fn pan_position_response(protocol: Protocol, address: u8, degrees: f32) -> Vec<u8> {
+    let [msb, lsb] = hundredths(degrees).to_be_bytes();
+    encode(
+        protocol,
+        &Frame {
+            address,
+            cmd1: 0,
+            cmd2: PAN_POSITION,
+            data1: msb,
+            data2: lsb,
+        },
+    )
+}
+
+pub fn tilt_position_response(protocol: Protocol, address: u8, degrees: f32) -> Vec<u8> {
+    let [msb, lsb] = hundredths(-degrees).to_be_bytes();
+    encode(
+        protocol,
+        &Frame {
+            address,
+            cmd1: 0,
+            cmd2: TILT_POSITION,
+            data1: msb,
+            data2: lsb,
+        },
+    )
+}
+
+/// Reassembles frames from a byte stream, resyncing on the start byte after
+/// noise or a bad checksum.
+pub struct FrameReader {
+    protocol: Protocol,
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            buf: Vec::with_capacity(8),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame, PelcoError>> {
+        if self.buf.is_empty() && !self.protocol.is_start(byte) {
+            return None;
+        }
+        self.buf.push(byte);
+        if self.buf.len() < self.protocol.frame_len() {
+            return None;
+        }
+        let res = decode(self.protocol, &self.buf);
+        match res {
+            Ok(_) => self.buf.clear(),
+            // the start byte may have been noise, with a real frame begun
+            // somewhere in what followed it
+            Err(_) => {
+                let next = (self.buf[1..].iter())
+                    .position(|byte| self.protocol.is_start(*byte))
+                    .map_or(self.buf.len(), |at| at + 1);
+                self.buf.drain(..next);
+            }
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pelco_d_pan_right() {
+        // address 1, pan right at speed 0x20
+        let bytes = [0xFF, 0x01, 0x00, 0x02, 0x20, 0x00, 0x23];
+        let frame = decode(Protocol::D, &bytes).unwrap();
+        assert_eq!(frame.address, 1);
+        assert_eq!(
+            parse_command(&frame),
+            Ok(Command::Move {
+                pan: 32. / 63.,
+                tilt: 0.
+            })
+        );
+        assert_eq!(encode(Protocol::D, &frame), bytes);
+    }
+
+    #[test]
+    fn test_pelco_d_bad_checksum() {
+        let bytes = [0xFF, 0x01, 0x00, 0x02, 0x20, 0x00, 0x24];
+        assert_eq!(decode(Protocol::D, &bytes), Err(PelcoError::Checksum));
+    }
+
+    #[test]
+    fn test_pelco_p_goto_preset() {
+        let frame = Frame {
+            address: 1,
+            cmd1: 0,
+            cmd2: GOTO_PRESET,
+            data1: 0,
+            data2: 3,
+        };
+        let bytes = encode(Protocol::P, &frame);
+        assert_eq!(bytes, [0xA0, 0x00, 0x00, 0x07, 0x00, 0x03, 0xAF, 0x0B]);
+        let decoded = decode(Protocol::P, &bytes).unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(parse_command(&decoded), Ok(Command::PresetCall(3)));
+    }
+
+    #[test]
+    fn test_frame_reader_resyncs() {
+        let mut reader = FrameReader::new(Protocol::D);
+        let stream = [0x00, 0x12, 0xFF, 0x01, 0x00, 0x10, 0x00, 0x3F, 0x50];
+        let frames: Vec<_> = stream.iter().filter_map(|b| reader.push(*b)).collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            parse_command(frames[0].as_ref().unwrap()),
+            Ok(Command::Move { pan: 0., tilt: -1. })
+        );
+    }
+
+    #[test]
+    fn test_frame_reader_resyncs_within_bad_frame() {
+        let mut reader = FrameReader::new(Protocol::D);
+        // a stray start byte, then a good frame its bad checksum swallowed
+        let stream = [0xFF, 0x00, 0xFF, 0x01, 0x00, 0x10, 0x00, 0x3F, 0x50];
+        let frames: Vec<_> = stream.iter().filter_map(|b| reader.push(*b)).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], Err(PelcoError::Checksum));
+        assert_eq!(
+            parse_command(frames[1].as_ref().unwrap()),
+            Ok(Command::Move { pan: 0., tilt: -1. })
+        );
+    }
+
+    #[test]
+    fn test_position_responses() {
+        assert_eq!(
+            pan_position_response(Protocol::D, 1, 90.),
+            [0xFF, 0x01, 0x00, 0x59, 0x23, 0x28, 0xA5]
+        );
+        // 10° up reads as 350°
+        assert_eq!(
+            tilt_position_response(Protocol::D, 1, 10.)[4..6],
+            35000u16.to_be_bytes()
+        );
+    }
+}
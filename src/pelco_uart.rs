@@ -0,0 +1,142 @@
+use {
+    crate::{
+        cmd::{Cmd, CmdQueue},
+        gimbal::{Gimbal, Position, MAX_VELOCITY},
+        pelco::{self, Command, FrameReader, Protocol},
+    },
+    esp_idf_svc::hal::{
+        delay::BLOCK,
+        gpio::{AnyIOPin, InputPin, OutputPin},
+        peripheral::Peripheral,
+        uart::{config::Config, Uart, UartDriver},
+        units::Hertz,
+    },
+    log::{info, warn},
+    std::{
+        sync::{Arc, Mutex},
+        thread,
+    },
+};
+
+const PELCO_PROTOCOL: &str = env!("PELCO_PROTOCOL");
+const PELCO_ADDRESS: &str = env!("PELCO_ADDRESS");
+const PELCO_BAUD: &str = env!("PELCO_BAUD");
+
+pub struct PelcoConfig {
+    pub protocol: Protocol,
+    pub address: u8,
+    pub baud: u32,
+}
+
+impl PelcoConfig {
+    /// `None` when no protocol is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let protocol = match PELCO_PROTOCOL.to_ascii_lowercase().as_str() {
+            "" => return Ok(None),
+            "d" => Protocol::D,
+            "p" => Protocol::P,
+            other => return Err(anyhow::anyhow!("unknown PELCO_PROTOCOL {other}")),
+        };
+        Ok(Some(Self {
+            protocol,
+            address: PELCO_ADDRESS.parse()?,
+            baud: PELCO_BAUD.parse()?,
+        }))
+    }
+}
+
+fn handle(
+    command: Command,
+    config: &PelcoConfig,
+    cmds: &CmdQueue,
+    position: &Position,
+) -> Option<Vec<u8>> {
+    let enqueue = |cmd| {
+        cmds.lock().unwrap().push_back(cmd);
+        None
+    };
+    match command {
+        Command::Move { pan, tilt } => enqueue(Cmd::Drive(pan * MAX_VELOCITY, tilt * MAX_VELOCITY)),
+        Command::PresetSet(id) => enqueue(Cmd::SetPreset(id.to_string(), None)),
+        Command::PresetClear(id) => enqueue(Cmd::ClearPreset(id.to_string())),
+        Command::PresetCall(id) => enqueue(Cmd::RecallPreset(id.to_string())),
+        Command::QueryPan => {
+            let (pan, _) = position.degrees();
+            Some(pelco::pan_position_response(
+                config.protocol,
+                config.address,
+                pan,
+            ))
+        }
+        Command::QueryTilt => {
+            let (_, tilt) = position.degrees();
+            Some(pelco::tilt_position_response(
+                config.protocol,
+                config.address,
+                tilt,
+            ))
+        }
+    }
+}
+
+/// Runs a Pelco receiver on a uart, typically wired to an RS-485 transceiver
+/// with automatic direction control.
+pub fn start<UART: Uart>(
+    uart: impl Peripheral<P = UART> + 'static,
+    tx: impl Peripheral<P = impl OutputPin> + 'static,
+    rx: impl Peripheral<P = impl InputPin> + 'static,
+    config: PelcoConfig,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+) -> anyhow::Result<()> {
+    let uart_config = Config::default().baudrate(Hertz(config.baud));
+    let driver = UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &uart_config,
+    )?;
+    let position = gimbal_arc.lock().unwrap().position();
+    info!(
+        "pelco: listening for pelco-{:?} address {} at {} baud",
+        config.protocol, config.address, config.baud
+    );
+
+    thread::Builder::new()
+        .name("pelco".into())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut reader = FrameReader::new(config.protocol);
+            let mut buf = [0; 32];
+            loop {
+                let n = match driver.read(&mut buf, BLOCK) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("pelco: read failed: {e}");
+                        continue;
+                    }
+                };
+                for frame in buf[..n].iter().filter_map(|b| reader.push(*b)) {
+                    let command = match frame {
+                        Ok(frame) if frame.address != config.address => continue,
+                        Ok(frame) => pelco::parse_command(&frame),
+                        Err(e) => Err(e),
+                    };
+                    match command {
+                        Ok(command) => {
+                            if let Some(reply) = handle(command, &config, &cmds, &position) {
+                                if let Err(e) = driver.write(&reply) {
+                                    warn!("pelco: write failed: {e}");
+                                }
+                            }
+                        }
+                        Err(e) => warn!("pelco: {e:?}"),
+                    }
+                }
+            }
+        })?;
+
+    Ok(())
+}
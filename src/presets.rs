@@ -0,0 +1,112 @@
+// Named positions operators keep returning to. Numeric names are shared with
+// the protocols that only know preset numbers, like VISCA & Pelco.
+
+use {
+    crate::gimbal,
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// Keeps the stored presets well within one nvs value
+pub const MAX_PRESETS: usize = 32;
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Preset {
+    /// Machine degrees, so presets stay put when work offsets change
+    pub pan: f32,
+    pub tilt: f32,
+    /// Degrees per second for both axes when recalled. `None` uses the
+    /// current velocities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+}
+
+pub type Presets = BTreeMap<String, Preset>;
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("names are 1 to {MAX_NAME_LEN} characters"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "invalid name {name}, use letters, digits, '-', '_' or '.'"
+        ));
+    }
+    Ok(())
+}
+
+/// Adds or replaces a preset, keeping within `MAX_PRESETS`.
+pub fn insert(presets: &mut Presets, name: &str, preset: Preset) -> Result<(), String> {
+    validate_name(name)?;
+    if presets.len() >= MAX_PRESETS && !presets.contains_key(name) {
+        return Err(format!("no room for more than {MAX_PRESETS} presets"));
+    }
+    if let Some(speed) = preset.speed {
+        gimbal::validate_velocity(speed).map_err(|e| format!("preset {name} speed: {e}"))?;
+    }
+    presets.insert(name.to_owned(), preset);
+    Ok(())
+}
+
+/// Checks presets from outside, like a restored backup, against the same
+/// rules as `insert`.
+pub fn validate(presets: &Presets) -> Result<(), String> {
+    let mut checked = Presets::new();
+    for (name, preset) in presets {
+        insert(&mut checked, name, *preset)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(pan: f32) -> Preset {
+        Preset {
+            pan,
+            tilt: 0.,
+            speed: None,
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut presets = Presets::new();
+        insert(&mut presets, "stage", preset(10.)).unwrap();
+        insert(&mut presets, "stage", preset(20.)).unwrap();
+        assert_eq!(presets["stage"].pan, 20.);
+        assert!(insert(&mut presets, "", preset(0.)).is_err());
+        assert!(insert(&mut presets, "stage left", preset(0.)).is_err());
+        let fast = Preset {
+            speed: Some(0.),
+            ..preset(0.)
+        };
+        assert!(insert(&mut presets, "fast", fast).is_err());
+        for speed in [f32::NAN, f32::INFINITY, 1000.] {
+            let fast = Preset {
+                speed: Some(speed),
+                ..preset(0.)
+            };
+            assert!(insert(&mut presets, "fast", fast).is_err());
+        }
+    }
+
+    #[test]
+    fn test_max_presets() {
+        let mut presets = Presets::new();
+        for id in 0..MAX_PRESETS {
+            insert(&mut presets, &id.to_string(), preset(0.)).unwrap();
+        }
+        assert!(insert(&mut presets, "one-more", preset(0.)).is_err());
+        // replacing one is fine
+        insert(&mut presets, "0", preset(5.)).unwrap();
+        assert!(validate(&presets).is_ok());
+        presets.insert("one-more".into(), preset(0.));
+        assert!(validate(&presets).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// Following satellite passes as an antenna rotator does: waiting where the next
+// pass rises, then following it across the sky. Each pass is planned whole, so
+// pan never has to unwind mid pass through the cables' stop, flipping tilt
+// over the top when that's the only way round.
+
+use {
+    crate::{
+        geo::Site,
+        kinematics,
+        satellite::{Pass, Satellite},
+    },
+    serde::{Deserialize, Serialize},
+};
+
+/// Seconds of orbit searched for the next pass at a time, so no one call
+/// holds things up for long
+const SEARCH_WINDOW: f64 = 1800.;
+/// Seconds ahead to give up searching
+const SEARCH_AHEAD: f64 = 7. * 86_400.;
+/// Seconds between samples when planning a pass
+const PLAN_STEP: f64 = 10.;
+/// Degrees pan turns either side of work zero, unless told otherwise
+pub const DEFAULT_WRAP: f32 = 180.;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Options {
+    /// Degrees above the horizon a pass starts & ends at
+    pub horizon: f32,
+    /// Degrees pan can turn either side of work zero before the cables stop
+    /// it
+    pub wrap: f32,
+    /// Tilt may go past straight up, following the pass from behind
+    pub flip: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            horizon: 0.,
+            wrap: DEFAULT_WRAP,
+            flip: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-5. ..=60.).contains(&self.horizon) {
+            return Err("horizon is -5 to 60 degrees".into());
+        }
+        validate_wrap(self.wrap)
+    }
+}
+
+/// Checks how far pan may turn either side of work zero, in degrees.
+pub fn validate_wrap(wrap: f32) -> Result<(), String> {
+    if !(180. ..=720.).contains(&wrap) {
+        return Err("wrap is 180 to 720 degrees".into());
+    }
+    Ok(())
+}
+
+/// The turn of `pan` nearest `from`, unwound by whole turns while it's past
+/// `wrap` either side of zero, so following something round & round swings
+/// back before the cables' stop.
+pub fn unwind(pan: f32, from: f32, wrap: f32) -> f32 {
+    let mut pan = kinematics::nearest_turn(pan, from);
+    while pan.abs() > wrap {
+        pan -= 360. * pan.signum();
+    }
+    pan
+}
+
+/// How to follow a pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Planned {
+    pass: Pass,
+    flipped: bool,
+    /// Pan last aimed at, to carry on from
+    pan: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Rotator {
+    pub satellite: Satellite,
+    pub site: Site,
+    pub options: Options,
+    planned: Option<Planned>,
+    /// Unix seconds searched up to without finding a pass
+    searched: f64,
+}
+
+/// Reported by `/api/satellite`.
+#[derive(Serialize)]
+pub struct Status<'a> {
+    pub name: &'a str,
+    /// The pass being followed or waited for
+    pub pass: Option<Pass>,
+}
+
+/// The pan, of those equivalent to `pans[0]`, that follows `pans` furthest
+/// from the wrap's ends, if any keeps within `wrap` of zero. Prefers staying
+/// close to `from`.
+fn fit(pans: &[f32], wrap: f32, from: f32) -> Option<f32> {
+    let first = *pans.first()?;
+    let (mut low, mut high, mut pan) = (first, first, first);
+    for next in &pans[1..] {
+        pan = kinematics::nearest_turn(*next, pan);
+        (low, high) = (low.min(pan), high.max(pan));
+    }
+    (-2..=2)
+        .map(|turns| turns as f32 * 360.)
+        .filter(|shift| low + shift >= -wrap && high + shift <= wrap)
+        .map(|shift| first + shift)
+        .min_by(|a, b| (a - from).abs().total_cmp(&(b - from).abs()))
+}
+
+impl Rotator {
+    pub fn new(satellite: Satellite, site: Site, options: Options) -> Self {
+        Self {
+            satellite,
+            site,
+            options,
+            planned: None,
+            searched: f64::MIN,
+        }
+    }
+
+    pub fn status(&self) -> Status<'_> {
+        Status {
+            name: &self.satellite.name,
+            pass: self.planned.map(|planned| planned.pass),
+        }
+    }
+
+    /// Pan & tilt facing the satellite at unix seconds `t`, flipped over the
+    /// top or not. Pan is within ±180°.
+    fn pan_tilt(&self, t: f64, flipped: bool) -> Option<(f32, f32)> {
+        let look = self.satellite.look(&self.site, t).ok()?;
+        Some(match flipped {
+            false => self.site.pan_tilt(look.azimuth, look.elevation),
+            true => self
+                .site
+                .pan_tilt(look.azimuth + 180., 180. - look.elevation),
+        })
+    }
+
+    fn plan(&self, pass: Pass, from: f32) -> Planned {
+        let steps = ((pass.los - pass.aos) / PLAN_STEP).ceil() as u32;
+        let times = (0..steps)
+            .map(|step| pass.aos + f64::from(step) * PLAN_STEP)
+            .chain([pass.los]);
+        let pans = |flipped| {
+            times
+                .clone()
+                .filter_map(|t| self.pan_tilt(t, flipped))
+                .map(|(pan, _)| pan)
+                .collect::<Vec<_>>()
+        };
+        let wrap = self.options.wrap;
+        let straight = pans(false);
+        if let Some(pan) = fit(&straight, wrap, from) {
+            return Planned {
+                pass,
+                flipped: false,
+                pan,
+            };
+        }
+        if self.options.flip {
+            if let Some(pan) = fit(&pans(true), wrap, from) {
+                return Planned {
+                    pass,
+                    flipped: true,
+                    pan,
+                };
+            }
+        }
+        // no way round, so start as close as can be & unwind when it's needed
+        let first = straight.first().copied().unwrap_or_default();
+        Planned {
+            pass,
+            flipped: false,
+            pan: kinematics::nearest_turn(first, from).clamp(-wrap, wrap),
+        }
+    }
+
+    /// Where to aim at unix seconds `now`, with pan at `pan`: along the pass
+    /// in progress, or where the next one rises. `None` while searching for
+    /// a pass, or when none rises within a week.
+    pub fn aim(&mut self, now: f64, pan: f32) -> Option<(f32, f32)> {
+        if self.planned.is_some_and(|planned| now > planned.pass.los) {
+            self.planned = None;
+        }
+        if self.planned.is_none() {
+            self.searched = self.searched.max(now);
+            if self.searched > now + SEARCH_AHEAD {
+                return None;
+            }
+            let until = self.searched + SEARCH_WINDOW;
+            let horizon = f64::from(self.options.horizon);
+            let pass = self
+                .satellite
+                .next_pass(&self.site, horizon, self.searched, until);
+            match pass {
+                Some(pass) => self.planned = Some(self.plan(pass, pan)),
+                None => {
+                    self.searched = until;
+                    return None;
+                }
+            }
+        }
+
+        let planned = self.planned?;
+        if now < planned.pass.aos {
+            let (_, tilt) = self.pan_tilt(planned.pass.aos, planned.flipped)?;
+            return Some((planned.pan, tilt));
+        }
+        let (pan, tilt) = self.pan_tilt(now, planned.flipped)?;
+        let pan = unwind(pan, planned.pan, self.options.wrap);
+        self.planned = Some(Planned { pan, ..planned });
+        Some((pan, tilt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::geo::Geo};
+
+    #[test]
+    fn test_fit() {
+        // a pass through north, behind a gimbal facing south
+        let pans = [170., -180., -170.];
+        assert_eq!(fit(&pans, 180., 0.), None);
+        assert_eq!(fit(&[-10., 0., 10.], 180., 0.), Some(-10.));
+        // with room to spare, whichever way round is nearer
+        assert_eq!(fit(&pans, 270., 0.), Some(170.));
+        assert_eq!(fit(&pans, 270., -100.), Some(-190.));
+    }
+
+    #[test]
+    fn test_unwind() {
+        assert_eq!(unwind(-170., 170., 270.), 190.);
+        assert_eq!(unwind(-170., 170., 180.), -170.);
+        // from well past the wrap, however many turns it takes
+        assert_eq!(unwind(10., 720., 180.), 10.);
+    }
+
+    #[test]
+    fn test_follow() {
+        let satellite = Satellite::from_tle(
+            "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537",
+        )
+        .unwrap();
+        let site = Site {
+            position: Geo {
+                lat: 51.5,
+                lon: -0.13,
+                alt: 20.,
+            },
+            heading: 0.,
+            level: 0.,
+        };
+        let now = satellite.epoch;
+        let mut rotator = Rotator::new(satellite, site, Options::default());
+        let mut waiting = None;
+        for minutes in 0..24 * 60 {
+            waiting = rotator.aim(now + f64::from(minutes) * 60., 0.);
+            if waiting.is_some() {
+                break;
+            }
+        }
+        let (pan, tilt) = waiting.unwrap();
+        let pass = rotator.status().pass.unwrap();
+        assert!(tilt.abs() < 0.5);
+        assert!((pan - kinematics::wrap(pass.aos_azimuth as f32)).abs() < 1e-3);
+
+        let (_, tilt) = rotator.aim(pass.max_at, pan).unwrap();
+        assert!((f64::from(tilt) - pass.max_elevation).abs() < 0.01);
+        // after the pass it moves on to the next
+        rotator.aim(pass.los + 1., pan);
+        assert!(rotator
+            .status()
+            .pass
+            .map_or(true, |next| next.aos > pass.los));
+    }
+}
@@ -0,0 +1,196 @@
+// hamlib's rotctld text protocol. Azimuth is pan, elevation is tilt.
+
+// hamlib error codes, as reported in `RPRT -n`
+pub const RIG_EINVAL: i32 = 1;
+pub const RIG_ENIMPL: i32 = 4;
+
+// directions for `M`
+const MOVE_UP: i32 = 2;
+const MOVE_DOWN: i32 = 4;
+const MOVE_LEFT: i32 = 8;
+const MOVE_RIGHT: i32 = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    GetPos,
+    SetPos(f32, f32),
+    Stop,
+    Park,
+    Reset,
+    /// Signed pan & tilt speed, as a fraction of full speed
+    Move(f32, f32),
+    GetInfo,
+    DumpState,
+    Quit,
+}
+
+impl Command {
+    fn long_name(&self) -> &'static str {
+        match self {
+            Command::GetPos => "get_pos",
+            Command::SetPos(..) => "set_pos",
+            Command::Stop => "stop",
+            Command::Park => "park",
+            Command::Reset => "reset",
+            Command::Move(..) => "move",
+            Command::GetInfo => "get_info",
+            Command::DumpState => "dump_state",
+            Command::Quit => "quit",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub command: Command,
+    /// Record separator of an extended (`+`, `;`, `|`, `,`) request
+    pub extended: Option<char>,
+    args: String,
+}
+
+pub enum Reply<'a> {
+    Ok,
+    Error(i32),
+    Position(f32, f32),
+    Info(&'a str),
+    DumpState(Limits),
+}
+
+pub struct Limits {
+    pub min_az: f32,
+    pub max_az: f32,
+    pub min_el: f32,
+    pub max_el: f32,
+}
+
+fn arg<T: std::str::FromStr>(arg: Option<&str>) -> Result<T, i32> {
+    arg.and_then(|a| a.parse().ok()).ok_or(RIG_EINVAL)
+}
+
+/// Parses one line. Errors are hamlib error codes.
+pub fn parse(line: &str) -> Result<Request, i32> {
+    let line = line.trim();
+    let (extended, line) = match line.chars().next() {
+        Some('+') => (Some('\n'), &line[1..]),
+        Some(sep @ (';' | '|' | ',')) => (Some(sep), &line[1..]),
+        _ => (None, line),
+    };
+    let mut parts = line.split_whitespace();
+    let name = parts.next().ok_or(RIG_EINVAL)?;
+    let args: Vec<&str> = parts.collect();
+    let mut args_iter = args.iter().copied();
+
+    let command = match name {
+        "p" | "\\get_pos" => Command::GetPos,
+        "P" | "\\set_pos" => Command::SetPos(arg(args_iter.next())?, arg(args_iter.next())?),
+        "S" | "\\stop" => Command::Stop,
+        "K" | "\\park" => Command::Park,
+        "R" | "\\reset" => Command::Reset,
+        "M" | "\\move" => {
+            let direction: i32 = arg(args_iter.next())?;
+            let speed: i32 = arg(args_iter.next())?;
+            let speed = speed.clamp(1, 100) as f32 / 100.;
+            match direction {
+                MOVE_UP => Command::Move(0., speed),
+                MOVE_DOWN => Command::Move(0., -speed),
+                MOVE_LEFT => Command::Move(-speed, 0.),
+                MOVE_RIGHT => Command::Move(speed, 0.),
+                _ => return Err(RIG_EINVAL),
+            }
+        }
+        "_" | "\\get_info" => Command::GetInfo,
+        "\\dump_state" => Command::DumpState,
+        "q" | "Q" | "\\quit" => Command::Quit,
+        _ => return Err(RIG_ENIMPL),
+    };
+
+    Ok(Request {
+        command,
+        extended,
+        args: args.join(" "),
+    })
+}
+
+/// Formats the reply to a request, in the request's (plain or extended) style.
+pub fn format(request: &Request, reply: Reply) -> String {
+    let code = match reply {
+        Reply::Error(code) => -code,
+        _ => 0,
+    };
+    let Some(sep) = request.extended else {
+        return match reply {
+            Reply::Ok | Reply::Error(_) => format!("RPRT {code}\n"),
+            Reply::Position(az, el) => format!("{az:.6}\n{el:.6}\n"),
+            Reply::Info(info) => format!("{info}\n"),
+            Reply::DumpState(limits) => format_dump_state(&limits, '\n'),
+        };
+    };
+
+    let mut out = format!("{}:", request.command.long_name());
+    if !request.args.is_empty() {
+        out.push_str(&format!(" {}", request.args));
+    }
+    out.push(sep);
+    match reply {
+        Reply::Position(az, el) => {
+            out.push_str(&format!("Azimuth: {az:.6}{sep}Elevation: {el:.6}{sep}"));
+        }
+        Reply::Info(info) => out.push_str(&format!("Info: {info}{sep}")),
+        Reply::DumpState(limits) => out.push_str(&format_dump_state(&limits, sep)),
+        Reply::Ok | Reply::Error(_) => {}
+    }
+    out.push_str(&format!("RPRT {code}\n"));
+    out
+}
+
+fn format_dump_state(limits: &Limits, sep: char) -> String {
+    format!(
+        "1{sep}2{sep}min_az={:.6}{sep}max_az={:.6}{sep}min_el={:.6}{sep}max_el={:.6}{sep}south_zero=0{sep}",
+        limits.min_az, limits.max_az, limits.min_el, limits.max_el
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("p").unwrap().command, Command::GetPos);
+        assert_eq!(
+            parse("P 180.5 45").unwrap().command,
+            Command::SetPos(180.5, 45.)
+        );
+        assert_eq!(
+            parse("\\move 8 50").unwrap().command,
+            Command::Move(-0.5, 0.)
+        );
+        assert_eq!(parse("P 180"), Err(RIG_EINVAL));
+        assert_eq!(parse("Z"), Err(RIG_ENIMPL));
+    }
+
+    #[test]
+    fn test_plain_replies() {
+        let get = parse("p").unwrap();
+        assert_eq!(
+            format(&get, Reply::Position(180., 45.5)),
+            "180.000000\n45.500000\n"
+        );
+        let set = parse("P 10 20").unwrap();
+        assert_eq!(format(&set, Reply::Ok), "RPRT 0\n");
+        assert_eq!(format(&set, Reply::Error(RIG_EINVAL)), "RPRT -1\n");
+    }
+
+    #[test]
+    fn test_extended_replies() {
+        let get = parse("+p").unwrap();
+        assert_eq!(
+            format(&get, Reply::Position(1., 2.)),
+            "get_pos:\nAzimuth: 1.000000\nElevation: 2.000000\nRPRT 0\n"
+        );
+        let set = parse("+\\set_pos 10 20").unwrap();
+        assert_eq!(format(&set, Reply::Ok), "set_pos: 10 20\nRPRT 0\n");
+        let stop = parse(";S").unwrap();
+        assert_eq!(format(&stop, Reply::Ok), "stop:;RPRT 0\n");
+    }
+}
@@ -0,0 +1,149 @@
+use {
+    crate::{
+        cmd::{self, Cmd, CmdQueue},
+        gcode::Gcode,
+        gimbal::{Gimbal, Position, MAX_VELOCITY},
+        rotator,
+        rotctld::{self, Command, Limits, Reply, Request},
+    },
+    log::{info, warn},
+    std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread,
+    },
+};
+
+const ROTCTLD_PORT: &str = env!("ROTCTLD_PORT");
+
+const INFO: &str = "gimbal-motion";
+/// Degrees pan turns either side of work zero, as satellite tracking does by
+/// default
+const WRAP: f32 = 180.;
+// pan=azimuth and tilt=elevation, in work degrees, as far as the head turns
+const LIMITS: Limits = Limits {
+    min_az: -WRAP,
+    max_az: WRAP,
+    min_el: -90.,
+    max_el: 90.,
+};
+
+fn is_faulted(gimbal_arc: &Mutex<Gimbal>) -> bool {
+    gimbal_arc
+        .try_lock()
+        .map(|g| g.last_error_message.is_some())
+        .unwrap_or(false)
+}
+
+fn execute(
+    request: &Request,
+    cmds: &CmdQueue,
+    gimbal_arc: &Mutex<Gimbal>,
+    position: &Position,
+) -> Reply<'static> {
+    let enqueue = |cmd| {
+        if is_faulted(gimbal_arc) {
+            return Reply::Error(rotctld::RIG_EINVAL);
+        }
+        cmds.lock().unwrap().push_back(cmd);
+        Reply::Ok
+    };
+    match request.command {
+        Command::GetPos => {
+            let (az, el) = position.degrees();
+            Reply::Position(az, el)
+        }
+        Command::SetPos(az, el) => {
+            if !az.is_finite() || !(LIMITS.min_el..=LIMITS.max_el).contains(&el) {
+                return Reply::Error(rotctld::RIG_EINVAL);
+            }
+            // clients may count azimuth 0 to 360, or past it, so turn the
+            // short way to it, unwinding rather than passing the cables' stop
+            let pan = rotator::unwind(az, position.degrees().0, WRAP);
+            enqueue(Cmd::MoveTo(Some(pan), Some(el)))
+        }
+        Command::Stop => {
+            cmd::stop(cmds);
+            Reply::Ok
+        }
+        Command::Park => enqueue(Cmd::MoveTo(Some(0.), Some(0.))),
+        Command::Reset => enqueue(Cmd::ProcessGcode(Gcode::G28Home)),
+        Command::Move(pan, tilt) => enqueue(Cmd::Drive(pan * MAX_VELOCITY, tilt * MAX_VELOCITY)),
+        Command::GetInfo => Reply::Info(INFO),
+        Command::DumpState => Reply::DumpState(LIMITS),
+        Command::Quit => Reply::Ok,
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+    position: Arc<Position>,
+) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match rotctld::parse(&line) {
+            Ok(request) => request,
+            Err(code) => {
+                writer.write_all(format!("RPRT -{code}\n").as_bytes())?;
+                continue;
+            }
+        };
+        let reply = execute(&request, &cmds, &gimbal_arc, &position);
+        writer.write_all(rotctld::format(&request, reply).as_bytes())?;
+        if request.command == Command::Quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// `None` when no port is configured.
+pub fn port_from_env() -> anyhow::Result<Option<u16>> {
+    match ROTCTLD_PORT {
+        "" => Ok(None),
+        port => Ok(Some(port.parse()?)),
+    }
+}
+
+/// Serves hamlib's rotctld protocol, so rotator clients like Gpredict can
+/// point the gimbal.
+pub fn start(port: u16, cmds: CmdQueue, gimbal_arc: Arc<Mutex<Gimbal>>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    info!("rotctld: listening on tcp {port}");
+    let position = gimbal_arc.lock().unwrap().position();
+    thread::Builder::new()
+        .name("rotctld".into())
+        .stack_size(4096)
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("rotctld: accept failed: {e}");
+                        continue;
+                    }
+                };
+                let (cmds, gimbal_arc, position) =
+                    (cmds.clone(), gimbal_arc.clone(), position.clone());
+                let spawned = thread::Builder::new()
+                    .name("rotctld-client".into())
+                    .stack_size(6144)
+                    .spawn(move || {
+                        if let Err(e) = serve(stream, cmds, gimbal_arc, position) {
+                            warn!("rotctld: client dropped: {e}");
+                        }
+                    });
+                if let Err(e) = spawned {
+                    warn!("rotctld: failed to spawn client thread: {e}");
+                }
+            }
+        })?;
+    Ok(())
+}
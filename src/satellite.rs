@@ -0,0 +1,642 @@
+// Orbits from two-line element sets, propagated with SGP4 as in Vallado et al.
+// "Revisiting Spacetrack Report #3" (2006), & the passes they make over a
+// site. Only near earth orbits, those taking under 225 minutes, are handled;
+// the deep space terms that geostationary & navigation satellites need aren't.
+
+use {
+    crate::geo::{Look, Site},
+    serde::Serialize,
+    std::f64::consts::TAU,
+};
+
+// WGS72, which element sets are fitted with
+const RADIUS_KM: f64 = 6378.135;
+const MU: f64 = 398_600.8;
+const J2: f64 = 0.001_082_616;
+const J3: f64 = -0.000_002_538_81;
+const J4: f64 = -0.000_001_655_97;
+const J3OJ2: f64 = J3 / J2;
+const X2O3: f64 = 2. / 3.;
+
+/// Seconds between looks when searching for a pass
+const STEP: f64 = 30.;
+const UNIX_JULIAN_DAY: f64 = 2_440_587.5;
+
+fn xke() -> f64 {
+    60. / (RADIUS_KM * RADIUS_KM * RADIUS_KM / MU).sqrt()
+}
+
+/// A satellite's elements & the terms SGP4 derives from them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Satellite {
+    pub name: String,
+    /// Unix seconds the elements are for
+    pub epoch: f64,
+    bstar: f64,
+    ecco: f64,
+    inclo: f64,
+    nodeo: f64,
+    argpo: f64,
+    mo: f64,
+    no: f64,
+    isimp: bool,
+    aycof: f64,
+    con41: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    argpdot: f64,
+    omgcof: f64,
+    sinmao: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    mdot: f64,
+    nodedot: f64,
+    xlcof: f64,
+    xmcof: f64,
+    nodecf: f64,
+}
+
+/// The digits of `line` from `from` to `to`, counting columns from 1 as the
+/// format does.
+fn field(line: &str, from: usize, to: usize) -> Result<&str, String> {
+    line.get(from - 1..to)
+        .map(str::trim)
+        .ok_or_else(|| format!("line too short for columns {from}-{to}"))
+}
+
+fn number(line: &str, from: usize, to: usize) -> Result<f64, String> {
+    let field = field(line, from, to)?;
+    field
+        .parse()
+        .map_err(|_| format!("columns {from}-{to} aren't a number: {field}"))
+}
+
+/// A number written with an assumed leading decimal point & an exponent,
+/// e.g. ` 12345-3` for 0.12345e-3.
+fn exponential(line: &str, from: usize, to: usize) -> Result<f64, String> {
+    let field = field(line, from, to)?;
+    let (mantissa, exponent) = field
+        .get(..field.len().saturating_sub(2))
+        .zip(field.get(field.len().saturating_sub(2)..))
+        .ok_or_else(|| format!("columns {from}-{to} aren't a number: {field}"))?;
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => (-1., digits),
+        None => (1., mantissa.trim_start_matches('+')),
+    };
+    let parsed = format!("0.{}e{}", digits.trim(), exponent)
+        .parse::<f64>()
+        .map_err(|_| format!("columns {from}-{to} aren't a number: {field}"))?;
+    Ok(sign * parsed)
+}
+
+fn checksum(line: &str) -> Result<(), String> {
+    let sum: u32 = (line.chars().take(68))
+        .map(|c| match c {
+            '-' => 1,
+            c => c.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+    match line.chars().nth(68).and_then(|c| c.to_digit(10)) {
+        Some(check) if check == sum % 10 => Ok(()),
+        _ => Err(format!("bad checksum on line {}", &line[..1])),
+    }
+}
+
+/// Unix seconds at the start of `year`.
+fn year_start(year: i32) -> f64 {
+    let leap = |y: i32| (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let days: i32 = match year >= 1970 {
+        true => (1970..year).map(|y| if leap(y) { 366 } else { 365 }).sum(),
+        false => -(year..1970)
+            .map(|y| if leap(y) { 366 } else { 365 })
+            .sum::<i32>(),
+    };
+    f64::from(days) * 86_400.
+}
+
+/// Greenwich mean sidereal time, in radians, at unix seconds `unix`.
+pub fn gmst(unix: f64) -> f64 {
+    let tut1 = (unix / 86_400. + UNIX_JULIAN_DAY - 2_451_545.) / 36_525.;
+    let seconds = -6.2e-6 * tut1 * tut1 * tut1
+        + 0.093_104 * tut1 * tut1
+        + (876_600. * 3600. + 8_640_184.812_866) * tut1
+        + 67_310.548_41;
+    (seconds.to_radians() / 240.).rem_euclid(TAU)
+}
+
+impl Satellite {
+    /// Reads an element set, with or without a name line before it.
+    pub fn from_tle(tle: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = (tle.lines().map(str::trim_end))
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let (name, line1, line2) = match lines[..] {
+            [line1, line2] => ("", line1, line2),
+            [name, line1, line2] => (name.trim(), line1, line2),
+            _ => return Err("an element set is 2 lines, or 3 with a name".into()),
+        };
+        if !line1.starts_with("1 ") || !line2.starts_with("2 ") {
+            return Err("element set lines start with 1 & 2".into());
+        }
+        checksum(line1)?;
+        checksum(line2)?;
+        if field(line1, 3, 7)? != field(line2, 3, 7)? {
+            return Err("element set lines are for different satellites".into());
+        }
+
+        let year = number(line1, 19, 20)? as i32;
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let day = number(line1, 21, 32)?;
+        let epoch = year_start(year) + (day - 1.) * 86_400.;
+        let bstar = exponential(line1, 54, 61)?;
+        let inclo = number(line2, 9, 16)?.to_radians();
+        let nodeo = number(line2, 18, 25)?.to_radians();
+        let ecco = format!("0.{}", field(line2, 27, 33)?)
+            .parse::<f64>()
+            .map_err(|_| "eccentricity isn't a number")?;
+        let argpo = number(line2, 35, 42)?.to_radians();
+        let mo = number(line2, 44, 51)?.to_radians();
+        // revolutions per day, to radians per minute
+        let no_kozai = number(line2, 53, 63)? * TAU / 1440.;
+        if no_kozai <= 0. || !(0. ..1.).contains(&ecco) {
+            return Err("elements aren't an orbit".into());
+        }
+        let name = match name {
+            "" => field(line1, 3, 7)?.to_owned(),
+            name => name.strip_prefix("0 ").unwrap_or(name).to_owned(),
+        };
+        Self::init(name, epoch, bstar, ecco, inclo, nodeo, argpo, mo, no_kozai)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn init(
+        name: String,
+        epoch: f64,
+        bstar: f64,
+        ecco: f64,
+        inclo: f64,
+        nodeo: f64,
+        argpo: f64,
+        mo: f64,
+        no_kozai: f64,
+    ) -> Result<Self, String> {
+        let xke = xke();
+        let ss = 78. / RADIUS_KM + 1.;
+        let qzms2t = ((120. - 78.) / RADIUS_KM).powi(4);
+
+        // undo the kozai mean motion
+        let eccsq = ecco * ecco;
+        let omeosq = 1. - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let ak = (xke / no_kozai).powf(X2O3);
+        let d1 = 0.75 * J2 * (3. * cosio2 - 1.) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1. - del * del - del * (1. / 3. + 134. * del * del / 81.));
+        let del = d1 / (adel * adel);
+        let no = no_kozai / (1. + del);
+
+        let ao = (xke / no).powf(X2O3);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1. - 5. * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1. - ecco);
+
+        if TAU / no >= 225. {
+            return Err("deep space orbits, of 225 minutes or more, aren't supported".into());
+        }
+        let isimp = rp < 220. / RADIUS_KM + 1.;
+        let (mut sfour, mut qzms24) = (ss, qzms2t);
+        let perige = (rp - 1.) * RADIUS_KM;
+        if perige < 156. {
+            sfour = if perige < 98. { 20. } else { perige - 78. };
+            qzms24 = ((120. - sfour) / RADIUS_KM).powi(4);
+            sfour = sfour / RADIUS_KM + 1.;
+        }
+        let pinvsq = 1. / posq;
+        let tsi = 1. / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1. - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * no
+            * (ao * (1. + 1.5 * etasq + eeta * (4. + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8. + 3. * etasq * (8. + etasq)));
+        let cc1 = bstar * cc2;
+        let cc3 = match ecco > 1e-4 {
+            true => -2. * coef * tsi * J3OJ2 * no * sinio / ecco,
+            false => 0.,
+        };
+        let x1mth2 = 1. - cosio2;
+        let cc4 = 2.
+            * no
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2. + 0.5 * etasq) + ecco * (0.5 + 2. * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3. * con41 * (1. - 2. * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75 * x1mth2 * (2. * etasq - eeta * (1. + etasq)) * (2. * argpo).cos()));
+        let cc5 = 2. * coef1 * ao * omeosq * (1. + 2.75 * (etasq + eeta) + eeta * etasq);
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no;
+        let mdot = no
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13. - 78. * cosio2 + 137. * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7. - 114. * cosio2 + 395. * cosio4)
+            + temp3 * (3. - 36. * cosio2 + 49. * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot =
+            xhdot1 + (0.5 * temp2 * (4. - 19. * cosio2) + 2. * temp3 * (3. - 7. * cosio2)) * cosio;
+        let omgcof = bstar * cc3 * argpo.cos();
+        let xmcof = match ecco > 1e-4 {
+            true => -X2O3 * coef * bstar / eeta,
+            false => 0.,
+        };
+        let nodecf = 3.5 * omeosq * xhdot1 * cc1;
+        let t2cof = 1.5 * cc1;
+        // avoid dividing by zero for 180° inclinations
+        let xlcof =
+            -0.25 * J3OJ2 * sinio * (3. + 5. * cosio) / f64::max((1. + cosio).abs(), 1.5e-12);
+        let aycof = -0.5 * J3OJ2 * sinio;
+        let delmo = (1. + eta * mo.cos()).powi(3);
+        let sinmao = mo.sin();
+        let x7thm1 = 7. * cosio2 - 1.;
+
+        let (mut d2, mut d3, mut d4) = (0., 0., 0.);
+        let (mut t3cof, mut t4cof, mut t5cof) = (0., 0., 0.);
+        if !isimp {
+            let cc1sq = cc1 * cc1;
+            d2 = 4. * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.;
+            d3 = (17. * ao + sfour) * temp;
+            d4 = 0.5 * temp * ao * tsi * (221. * ao + 31. * sfour) * cc1;
+            t3cof = d2 + 2. * cc1sq;
+            t4cof = 0.25 * (3. * d3 + cc1 * (12. * d2 + 10. * cc1sq));
+            t5cof =
+                0.2 * (3. * d4 + 12. * cc1 * d3 + 6. * d2 * d2 + 15. * cc1sq * (2. * d2 + cc1sq));
+        }
+
+        Ok(Self {
+            name,
+            epoch,
+            bstar,
+            ecco,
+            inclo,
+            nodeo,
+            argpo,
+            mo,
+            no,
+            isimp,
+            aycof,
+            con41,
+            cc1,
+            cc4,
+            cc5,
+            d2,
+            d3,
+            d4,
+            delmo,
+            eta,
+            argpdot,
+            omgcof,
+            sinmao,
+            t2cof,
+            t3cof,
+            t4cof,
+            t5cof,
+            x1mth2,
+            x7thm1,
+            mdot,
+            nodedot,
+            xlcof,
+            xmcof,
+            nodecf,
+        })
+    }
+
+    /// Kilometres from the Earth's centre in the true equator, mean equinox
+    /// frame, `t` minutes after the epoch.
+    pub fn teme(&self, t: f64) -> Result<[f64; 3], String> {
+        let xke = xke();
+        let xmdf = self.mo + self.mdot * t;
+        let argpdf = self.argpo + self.argpdot * t;
+        let nodedf = self.nodeo + self.nodedot * t;
+        let t2 = t * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1. - self.cc1 * t;
+        let mut tempe = self.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+        if !self.isimp {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1. + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa -= self.d2 * t2 + self.d3 * t3 + self.d4 * t4;
+            tempe += self.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let am = (xke / self.no).powf(X2O3) * tempa * tempa;
+        let nm = xke / am.powf(1.5);
+        let mut em = self.ecco - tempe;
+        if !(-0.001..1.).contains(&em) || !nm.is_finite() {
+            return Err("orbit has decayed".into());
+        }
+        em = em.max(1e-6);
+        mm += self.no * templ;
+        let xlm = mm + argpm + nodem;
+        nodem %= TAU;
+        argpm %= TAU;
+        let xlm = xlm % TAU;
+        let mp = (xlm - argpm - nodem) % TAU;
+        let (sinip, cosip) = self.inclo.sin_cos();
+
+        // long period periodics
+        let axnl = em * argpm.cos();
+        let temp = 1. / (am * (1. - em * em));
+        let aynl = em * argpm.sin() + temp * self.aycof;
+        let xl = mp + argpm + nodem + temp * self.xlcof * axnl;
+
+        // kepler's equation
+        let u = (xl - nodem) % TAU;
+        let mut eo1 = u;
+        let (mut sineo1, mut coseo1) = (0., 0.);
+        for _ in 0..10 {
+            (sineo1, coseo1) = eo1.sin_cos();
+            let step =
+                (u - aynl * coseo1 + axnl * sineo1 - eo1) / (1. - coseo1 * axnl - sineo1 * aynl);
+            eo1 += step.clamp(-0.95, 0.95);
+            if step.abs() < 1e-12 {
+                break;
+            }
+        }
+
+        // short period periodics
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1. - el2);
+        if pl < 0. {
+            return Err("orbit has decayed".into());
+        }
+        let rl = am * (1. - ecose);
+        let betal = (1. - el2).sqrt();
+        let temp = esine / (1. + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1. - 2. * sinu * sinu;
+        let temp = 1. / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let mrt = rl * (1. - 1.5 * temp2 * betal * self.con41) + 0.5 * temp1 * self.x1mth2 * cos2u;
+        if mrt < 1. {
+            return Err("orbit has decayed".into());
+        }
+        let su = su - 0.25 * temp2 * self.x7thm1 * sin2u;
+        let xnode = nodem + 1.5 * temp2 * cosip * sin2u;
+        let xinc = self.inclo + 1.5 * temp2 * cosip * sinip * cos2u;
+
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let r = mrt * RADIUS_KM;
+        Ok([
+            r * (xmx * sinsu + cnod * cossu),
+            r * (xmy * sinsu + snod * cossu),
+            r * sini * sinsu,
+        ])
+    }
+
+    /// Metres from the Earth's centre, turning with it, at unix seconds
+    /// `unix`. Polar motion is left out.
+    pub fn ecef(&self, unix: f64) -> Result<[f64; 3], String> {
+        let [x, y, z] = self.teme((unix - self.epoch) / 60.)?;
+        let (sin, cos) = gmst(unix).sin_cos();
+        Ok([
+            (cos * x + sin * y) * 1000.,
+            (cos * y - sin * x) * 1000.,
+            z * 1000.,
+        ])
+    }
+
+    /// Where the satellite is seen from `site` at unix seconds `unix`.
+    pub fn look(&self, site: &Site, unix: f64) -> Result<Look, String> {
+        Ok(site.look_ecef(self.ecef(unix)?))
+    }
+}
+
+/// A pass over the site, above the horizon it was searched with.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Pass {
+    /// Unix seconds of acquisition & loss of signal
+    pub aos: f64,
+    pub los: f64,
+    /// Degrees
+    pub aos_azimuth: f64,
+    pub los_azimuth: f64,
+    pub max_elevation: f64,
+    /// Unix seconds
+    pub max_at: f64,
+}
+
+impl Satellite {
+    fn elevation(&self, site: &Site, unix: f64) -> f64 {
+        // a decayed orbit never rises
+        self.look(site, unix).map_or(-90., |look| look.elevation)
+    }
+
+    /// When, between `below` & `above` seconds, the satellite crosses
+    /// `horizon`, to the second.
+    fn crossing(&self, site: &Site, horizon: f64, mut below: f64, mut above: f64) -> f64 {
+        while (above - below).abs() > 1. {
+            let mid = (below + above) / 2.;
+            match self.elevation(site, mid) > horizon {
+                true => above = mid,
+                false => below = mid,
+            }
+        }
+        above
+    }
+
+    /// The first pass over `site` above `horizon` degrees that's in progress
+    /// at `from` or starts by `until`, both unix seconds.
+    pub fn next_pass(&self, site: &Site, horizon: f64, from: f64, until: f64) -> Option<Pass> {
+        let above = |t| self.elevation(site, t) > horizon;
+        let aos = match above(from) {
+            // already up, so look back for when it rose
+            true => {
+                let mut t = from;
+                while above(t - STEP) && from - t < 86_400. {
+                    t -= STEP;
+                }
+                self.crossing(site, horizon, t - STEP, t)
+            }
+            false => {
+                let mut t = from;
+                while !above(t + STEP) {
+                    t += STEP;
+                    if t > until {
+                        return None;
+                    }
+                }
+                self.crossing(site, horizon, t, t + STEP)
+            }
+        };
+        let (mut t, mut max_at, mut max_elevation) = (aos, aos, horizon);
+        loop {
+            let elevation = self.elevation(site, t + STEP);
+            if elevation <= horizon {
+                break;
+            }
+            t += STEP;
+            if elevation > max_elevation {
+                (max_at, max_elevation) = (t, elevation);
+            }
+        }
+        let los = self.crossing(site, horizon, t + STEP, t);
+        // narrow in on the highest point between the samples around it
+        let (mut low, mut high) = (f64::max(aos, max_at - STEP), f64::min(los, max_at + STEP));
+        while high - low > 1. {
+            let (a, b) = (low + (high - low) / 3., high - (high - low) / 3.);
+            match self.elevation(site, a) < self.elevation(site, b) {
+                true => low = a,
+                false => high = b,
+            }
+        }
+        let max_at = (low + high) / 2.;
+        let azimuth = |t| self.look(site, t).map_or(0., |look| look.azimuth);
+        Some(Pass {
+            aos,
+            los,
+            aos_azimuth: azimuth(aos),
+            los_azimuth: azimuth(los),
+            max_elevation: self.elevation(site, max_at),
+            max_at,
+        })
+    }
+
+    /// Up to `count` passes over `site` above `horizon` degrees, from `from`
+    /// to `until`, in unix seconds.
+    pub fn passes(
+        &self,
+        site: &Site,
+        horizon: f64,
+        from: f64,
+        until: f64,
+        count: usize,
+    ) -> Vec<Pass> {
+        let mut passes = Vec::new();
+        let mut t = from;
+        while passes.len() < count {
+            let Some(pass) = self.next_pass(site, horizon, t, until) else {
+                break;
+            };
+            t = pass.los + STEP;
+            passes.push(pass);
+        }
+        passes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::geo::Geo};
+
+    // the first of the verification cases published with the 2006 report
+    const VANGUARD: &str = "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753
+2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667";
+
+    const ISS: &str = "ISS (ZARYA)
+1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+
+    fn assert_km(actual: [f64; 3], expected: [f64; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-3, "{actual:?} isn't {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_sgp4() {
+        let satellite = Satellite::from_tle(VANGUARD).unwrap();
+        assert_eq!(satellite.name, "00005");
+        assert_km(
+            satellite.teme(0.).unwrap(),
+            [7022.46529266, -1400.08296755, 0.03995155],
+        );
+        assert_km(
+            satellite.teme(360.).unwrap(),
+            [-7154.03120202, -3783.17682504, -3536.19412294],
+        );
+    }
+
+    #[test]
+    fn test_tle() {
+        let satellite = Satellite::from_tle(ISS).unwrap();
+        assert_eq!(satellite.name, "ISS (ZARYA)");
+        // 2008, day 264.51782528
+        assert!((satellite.epoch - 1_221_913_540.1).abs() < 0.1);
+        let corrupted = ISS.replace("51.6416", "51.6417");
+        assert!(Satellite::from_tle(&corrupted).is_err());
+        assert!(Satellite::from_tle("1 2\n3").is_err());
+    }
+
+    #[test]
+    fn test_passes() {
+        let satellite = Satellite::from_tle(ISS).unwrap();
+        let site = Site {
+            position: Geo {
+                lat: 51.5,
+                lon: -0.13,
+                alt: 20.,
+            },
+            heading: 0.,
+            level: 0.,
+        };
+        let from = satellite.epoch;
+        let passes = satellite.passes(&site, 0., from, from + 86_400., 10);
+        assert!(!passes.is_empty());
+        for pass in &passes {
+            assert!(pass.aos < pass.max_at && pass.max_at < pass.los);
+            assert!(pass.los - pass.aos < 15. * 60.);
+            assert!(satellite.elevation(&site, pass.aos).abs() < 0.5);
+            assert!(pass.max_elevation > 0. && pass.max_elevation <= 90.);
+        }
+        // starting mid pass finds the same one
+        let pass = passes[0];
+        let during = satellite
+            .next_pass(&site, 0., pass.max_at, pass.max_at)
+            .unwrap();
+        assert!((during.aos - pass.aos).abs() < 2.);
+    }
+}
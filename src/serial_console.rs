@@ -0,0 +1,151 @@
+use {
+    crate::{
+        cmd::CmdQueue,
+        gcode_session::GcodeSession,
+        gcode_stream::{self, LineReader},
+        gimbal::Gimbal,
+    },
+    esp_idf_svc::{
+        hal::{
+            delay::TickType,
+            gpio::{AnyIOPin, InputPin, OutputPin},
+            peripheral::Peripheral,
+            uart::{config::Config, Uart, UartDriver},
+            units::Hertz,
+        },
+        log::EspLogger,
+        sys::{esp_log_level_set, esp_log_level_t_ESP_LOG_NONE},
+    },
+    log::{info, warn, Log, Metadata, Record},
+    std::{
+        sync::{
+            mpsc::{sync_channel, SyncSender},
+            Arc, Mutex,
+        },
+        thread,
+    },
+};
+
+const SERIAL_BAUD: &str = env!("SERIAL_BAUD");
+
+const MAX_LINE_LEN: usize = 96;
+/// Longest the console waits on input before writing out log lines
+const POLL_MS: u64 = 50;
+/// Log lines held for the console before more are dropped
+const MAX_ECHOED: usize = 32;
+
+/// Where log lines go while the console's running, for it to write between
+/// replies
+static ECHO: Mutex<Option<SyncSender<String>>> = Mutex::new(None);
+static LOGGER: ConsoleLogger = ConsoleLogger(EspLogger::new());
+
+/// Logs through ESP-IDF until the console starts, then as Marlin `echo:`
+/// lines between its replies, which hosts show without mistaking them for
+/// one.
+struct ConsoleLogger(EspLogger);
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        match ECHO.lock().unwrap().as_ref() {
+            Some(tx) if self.enabled(record.metadata()) => {
+                let line = format!(
+                    "echo:{} {}: {}\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+                // a full queue means the host's not reading
+                let _ = tx.try_send(line);
+            }
+            Some(_) => {}
+            None => self.0.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+/// Installs the logger, in place of `EspLogger::initialize_default`.
+pub fn initialize_logger() {
+    log::set_logger(&LOGGER)
+        .map(|()| LOGGER.0.initialize())
+        .unwrap();
+}
+
+/// `None` when no baud is configured.
+pub fn baud_from_env() -> anyhow::Result<Option<u32>> {
+    match SERIAL_BAUD {
+        "" => Ok(None),
+        baud => Ok(Some(baud.parse()?)),
+    }
+}
+
+/// Runs a g-code console on the usb-serial uart, answering each line Marlin
+/// style so hosts like Pronterface can stream programs. Logs share the port
+/// as `echo:` lines, and ESP-IDF's own are silenced, as they'd land mid
+/// reply.
+pub fn start<UART: Uart>(
+    uart: impl Peripheral<P = UART> + 'static,
+    tx: impl Peripheral<P = impl OutputPin> + 'static,
+    rx: impl Peripheral<P = impl InputPin> + 'static,
+    baud: u32,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+) -> anyhow::Result<()> {
+    let uart_config = Config::default().baudrate(Hertz(baud));
+    let driver = UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &uart_config,
+    )?;
+    info!("serial: g-code console at {baud} baud");
+    let (echo_tx, echo_rx) = sync_channel(MAX_ECHOED);
+    *ECHO.lock().unwrap() = Some(echo_tx);
+    // SAFETY: the tag is a nul terminated string
+    unsafe { esp_log_level_set(b"*\0".as_ptr().cast(), esp_log_level_t_ESP_LOG_NONE) };
+
+    thread::Builder::new()
+        .name("serial-console".into())
+        .stack_size(6144)
+        .spawn(move || {
+            let write = |response: &str| {
+                if let Err(e) = driver.write(response.as_bytes()) {
+                    warn!("serial: write failed: {e}");
+                }
+            };
+            let mut session = GcodeSession::new(cmds, gimbal_arc);
+            // marlin greets with `start`, which some hosts wait for
+            write("start\n");
+            let mut reader = LineReader::new(MAX_LINE_LEN);
+            let mut buf = [0; 64];
+            loop {
+                for line in echo_rx.try_iter() {
+                    write(&line);
+                }
+                let n = match driver.read(&mut buf, TickType::new_millis(POLL_MS).ticks()) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("serial: read failed: {e}");
+                        continue;
+                    }
+                };
+                for received in buf[..n].iter().filter_map(|b| reader.push(*b)) {
+                    let response = match received {
+                        Ok(line) => session.handle(&line, true, || write(gcode_stream::BUSY)),
+                        Err(response) => response,
+                    };
+                    write(&response);
+                }
+            }
+        })?;
+    Ok(())
+}
@@ -1,64 +1,283 @@
 use {
-    crate::{cmd::Cmd, gcode::GcodeParser, gimbal::Gimbal, server_response::Response},
-    embedded_svc::{http::Headers, io::Write, ipv4::IpInfo},
-    esp_idf_svc::http::{server::EspHttpServer, Method},
-    log::info,
-    serde_json,
-    std::{
-        collections::VecDeque,
-        sync::{Arc, Mutex},
+    crate::{
+        auth::Auth,
+        capture::{self, Capture},
+        clock,
+        cmd::{Cmd, CmdQueue, Target},
+        ephemeris::{self, Body},
+        gcode::{Gcode, GcodeParser},
+        gcode_session::Reporter,
+        geo::{Geo, Look},
+        gimbal::{self, Gimbal},
+        onvif,
+        onvif_server::Onvif,
+        ota::{self, FirmwareInfo},
+        panorama::Panorama,
+        presets::{self, Preset},
+        rotator::{self, Rotator},
+        satellite::Satellite,
+        server_response::Response,
+        settings::{Backup, Settings, Store},
+        state::{Moving, State},
+        takes::{self, Take},
+        teach,
+        timeline::{Control, Playback, Timeline},
+        web::{self, Asset},
+    },
+    embedded_svc::{
+        http::{
+            server::{HandlerResult, Request},
+            Headers,
+        },
+        io::{Read, Write},
+        ipv4::IpInfo,
     },
+    esp_idf_svc::{
+        hal::delay::FreeRtos,
+        http::{
+            server::{EspHttpConnection, EspHttpServer},
+            Method,
+        },
+    },
+    log::{info, warn},
+    serde::de::DeserializeOwned,
+    serde_json,
+    std::sync::{Arc, Mutex, TryLockError},
 };
 
+const API_ROUTES: &[&str] = &[
+    "/api/state",
+    "/api/restart",
+    "/api/gcode",
+    "/api/ota",
+    "/api/config",
+    "/api/presets",
+    "/api/presets/recall",
+    "/api/backup",
+    "/api/timeline",
+    "/api/timeline/control",
+    "/api/timelapse",
+    "/api/panorama",
+    "/api/panorama/preview",
+    "/api/capture/control",
+    "/api/timelapse/control",
+    "/api/takes",
+    "/api/takes/record",
+    "/api/takes/stop",
+    "/api/takes/play",
+    "/api/take",
+    "/api/look_at",
+    "/api/point_at",
+    "/api/time",
+    "/api/satellite",
+    "/api/satellite/passes",
+    "/api/sky",
+];
+const MAX_BODY_LEN: usize = 4096;
+/// Room for a full take as csv
+const MAX_TAKE_LEN: usize = 128 * 1024;
+/// Furthest ahead passes are predicted, in hours, as each day takes a while
+const MAX_PASS_HOURS: f64 = 48.;
+const MAX_PASSES: usize = 20;
+
+type Req<'a, 'b> = Request<&'a mut EspHttpConnection<'b>>;
+
 #[derive(serde::Deserialize, serde::Serialize)]
 struct PostGcode {
     pub gcode: String,
 }
 
+#[derive(serde::Deserialize)]
+struct PutPreset {
+    name: String,
+    /// Machine degrees. Leaving both out stores the current position.
+    pan: Option<f32>,
+    tilt: Option<f32>,
+    speed: Option<f32>,
+}
+
+#[derive(serde::Deserialize)]
+struct PresetName {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct PlayTake {
+    name: String,
+    #[serde(default = "default_speed")]
+    speed: f32,
+    #[serde(default)]
+    reverse: bool,
+}
+
+fn default_speed() -> f32 {
+    1.
+}
+
+#[derive(serde::Deserialize)]
+struct LookAt {
+    target: [f32; 3],
+    /// Keep following the target as more points arrive
+    #[serde(default)]
+    track: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct PointAt {
+    #[serde(flatten)]
+    target: Geo,
+    #[serde(default)]
+    track: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct SetTime {
+    /// Seconds since 1970
+    unix: f64,
+}
+
+#[derive(serde::Deserialize)]
+struct TrackSatellite {
+    /// Two line element set, optionally after a name line
+    tle: String,
+    #[serde(flatten)]
+    options: rotator::Options,
+}
+
+#[derive(serde::Deserialize)]
+struct TrackBody {
+    body: Body,
+    #[serde(flatten)]
+    options: ephemeris::Options,
+}
+
+#[derive(serde::Serialize)]
+struct SkyReport {
+    /// What's being followed
+    tracking: Option<ephemeris::Status>,
+    /// Where each is seen from the site, once it & the clock are set
+    sun: Option<Look>,
+    moon: Option<Look>,
+}
+
+#[derive(serde::Serialize)]
+struct TakeList<'a> {
+    recording: Option<&'a str>,
+    takes: Vec<takes::Summary<'a>>,
+}
+
 pub fn start(
     ip_info: IpInfo,
-    state: Arc<Mutex<VecDeque<Cmd>>>,
+    state: CmdQueue,
     gimbal_arc: Arc<Mutex<Gimbal>>,
+    store_arc: Arc<Mutex<Store>>,
+    firmware: FirmwareInfo,
 ) -> anyhow::Result<EspHttpServer<'static>> {
     let ip = ip_info.ip;
     info!("starting server at {ip}");
     let location = std::format!("https://cdaringe.github.io/gimbal-gui?gimbal_url={ip}");
 
+    let auth = Arc::new(Auth::from_env());
+    if !auth.is_enabled() {
+        warn!("no API_TOKEN or API_USER configured, api is open to the network");
+    }
+
     let server_configuration = esp_idf_svc::http::server::Configuration {
         stack_size: 10240,
+        max_uri_handlers: 80,
         ..Default::default()
     };
 
     let mut server = EspHttpServer::new(&server_configuration)?;
 
-    server.fn_handler("/", Method::Get, move |req| {
-        let conn = req.connection().unwrap_or("unknown");
-        info!("handling req from connection: {conn}");
-        let mut response = req.into_response(301, None, &[("Location", &location)])?;
-        response.flush()?;
-        Ok(())
-    })?;
+    for asset in web::ASSETS {
+        let location = location.clone();
+        let store = store_arc.clone();
+        server.fn_handler(asset.uri, Method::Get, move |req| {
+            let conn = req.connection().unwrap_or("unknown");
+            info!("handling req from connection: {conn}");
+            if asset.uri == "/" && store.lock()?.settings().gui_redirect {
+                return redirect(req, &location);
+            }
+            serve_asset(req, asset)
+        })?;
+    }
+
+    server.fn_handler("/gui", Method::Get, move |req| redirect(req, &location))?;
 
+    match Onvif::new(
+        ip,
+        state.clone(),
+        gimbal_arc.clone(),
+        auth.clone(),
+        firmware.version.to_owned(),
+    ) {
+        Ok(onvif) => {
+            let onvif = Arc::new(onvif);
+            for path in [onvif::DEVICE_PATH, onvif::MEDIA_PATH, onvif::PTZ_PATH] {
+                let onvif = onvif.clone();
+                let auth = auth.clone();
+                server.fn_handler(path, Method::Post, move |mut req| {
+                    let body = read_body(&mut req)?;
+                    let body = String::from_utf8_lossy(&body);
+                    match onvif.handle(&body, req.header("Authorization")) {
+                        Some((code, xml)) => soap(req, code, &xml, None),
+                        None => {
+                            let fault = onvif::Fault::sender("ter:NotAuthorized", "unauthorized");
+                            soap(req, 401, &onvif::fault(&fault), Some(auth.challenge()))
+                        }
+                    }
+                })?;
+            }
+            if let Err(e) = onvif.start_discovery() {
+                warn!("onvif: discovery unavailable: {e}");
+            }
+        }
+        Err(e) => warn!("onvif: unavailable: {e}"),
+    }
+
+    for route in API_ROUTES {
+        let auth = auth.clone();
+        server.fn_handler(route, Method::Options, move |req| preflight(req, &auth))?;
+    }
+
+    let state_auth = auth.clone();
+    let state_gimbal = gimbal_arc.clone();
+    let state_position = gimbal_arc.lock().unwrap().position();
     server.fn_handler("/api/state", Method::Get, move |req| {
-        let mut response = req.into_response(
-            200,
-            Some("Ok"),
-            &[
-                ("Access-Control-Allow-Origin", "*"),
-                ("content-type", "application/json"),
-            ],
-        )?;
-        write!(response, "{}", &Response::ok(&*gimbal_arc.lock()?).json()?)?;
-        response.flush()?;
-        Ok(())
+        let auth = &state_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let firmware = firmware.current();
+        // answers straight away mid move, homing included, rather than after it
+        let payload = match state_gimbal.try_lock() {
+            Ok(gimbal) => Response::ok(State::new(&gimbal, &firmware)).json()?,
+            Err(TryLockError::WouldBlock) => {
+                Response::ok(Moving::new(&state_position, &firmware)).json()?
+            }
+            Err(TryLockError::Poisoned(e)) => Err(e)?,
+        };
+        respond(req, auth, 200, "Ok", &payload)
     })?;
 
-    server.fn_handler("/api/restart", Method::Get, move |_req| {
+    let restart_auth = auth.clone();
+    server.fn_handler("/api/restart", Method::Get, move |req| {
+        if !restart_auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, &restart_auth);
+        }
         esp_idf_svc::hal::reset::restart();
         Ok(())
     })?;
 
+    let gcode_auth = auth.clone();
+    let gcode_cmds = state.clone();
+    let reporter = Reporter::new(&gimbal_arc.lock().unwrap());
     server.fn_handler("/api/gcode", Method::Post, move |mut req| {
+        let auth = &gcode_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
         let json_str = {
             let mut buf = [0; 256];
             req.read(&mut buf)?;
@@ -69,23 +288,854 @@ pub fn start(
 
         let body: PostGcode = serde_json::from_str(&json_str)?;
 
-        let (code, message, payload) = match GcodeParser::of_str(&body.gcode) {
-            Ok(gcode) => {
-                state.lock()?.push_back(Cmd::ProcessGcode(gcode));
+        let gcode = GcodeParser::of_str(&body.gcode);
+        // reports are answered in the response body rather than queued
+        let report = gcode.as_ref().ok().and_then(|gcode| reporter.report(gcode));
+        let (code, message, payload) = match (gcode, report) {
+            (_, Some(report)) => (200, "ok", Response::ok(report).json()?),
+            (Ok(_), None) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            (Ok(gcode), None) => {
+                gcode_cmds.lock()?.push_back(Cmd::ProcessGcode(gcode));
+                (200, "ok", Response::ok(true).json()?)
+            }
+            (Err(err), _) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let config_auth = auth.clone();
+    let config_store = store_arc.clone();
+    server.fn_handler("/api/config", Method::Get, move |req| {
+        let auth = &config_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let payload = Response::ok(config_store.lock()?.settings()).json()?;
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    let config_auth = auth.clone();
+    let config_cmds = state.clone();
+    let config_gimbal = gimbal_arc.clone();
+    let config_store = store_arc.clone();
+    server.fn_handler("/api/config", Method::Put, move |mut req| {
+        let auth = &config_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let settings = read_json::<Settings>(&mut req).and_then(|settings| {
+            (settings.validate())
+                .map(|()| settings)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match settings {
+            Ok(settings) => {
+                config_store.lock()?.set_settings(&settings)?;
+                {
+                    let mut gimbal = config_gimbal.lock()?;
+                    gimbal.set_mount(settings.mount);
+                    gimbal.set_site(settings.site);
+                }
+                config_cmds
+                    .lock()?
+                    .push_back(Cmd::ProcessGcode(Gcode::M1SetVelocity(
+                        Some(settings.pan_velocity),
+                        Some(settings.tilt_velocity),
+                    )));
+                (200, "ok", Response::ok(settings).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    // presets have a lock of their own, so they can be edited mid move
+    let (presets_arc, presets_position) = {
+        let gimbal = gimbal_arc.lock().unwrap();
+        (gimbal.presets(), gimbal.position())
+    };
+
+    let presets_auth = auth.clone();
+    let presets = presets_arc.clone();
+    server.fn_handler("/api/presets", Method::Get, move |req| {
+        let auth = &presets_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let payload = Response::ok(&*presets.lock()?).json()?;
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    // presets are edited directly rather than queued, so the response can say
+    // whether they were stored
+    let presets_auth = auth.clone();
+    let presets = presets_arc.clone();
+    let position = presets_position.clone();
+    let presets_store = store_arc.clone();
+    server.fn_handler("/api/presets", Method::Put, move |mut req| {
+        let auth = &presets_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let put = match read_json::<PutPreset>(&mut req) {
+            Ok(put) => put,
+            Err(err) => {
+                let payload = Response::error(err.to_string()).json()?;
+                return respond(req, auth, 400, "bad input", &payload);
+            }
+        };
+        let (pan, tilt) = match (put.pan, put.tilt) {
+            (Some(pan), Some(tilt)) => (pan, tilt),
+            (None, None) => position.machine_degrees(),
+            _ => {
+                let payload = Response::error("set both pan & tilt, or neither").json()?;
+                return respond(req, auth, 400, "bad input", &payload);
+            }
+        };
+        let preset = Preset {
+            pan,
+            tilt,
+            speed: put.speed,
+        };
+        let mut presets = presets.lock()?;
+        let (code, message, payload) = match presets::insert(&mut presets, &put.name, preset) {
+            Ok(()) => {
+                presets_store.lock()?.set_presets(&presets)?;
+                (200, "ok", Response::ok(preset).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let presets_auth = auth.clone();
+    let presets = presets_arc.clone();
+    let presets_store = store_arc.clone();
+    server.fn_handler("/api/presets", Method::Delete, move |mut req| {
+        let auth = &presets_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (code, message, payload) = match read_json::<PresetName>(&mut req) {
+            Ok(PresetName { name }) => {
+                let mut presets = presets.lock()?;
+                match presets.remove(&name) {
+                    Some(preset) => {
+                        presets_store.lock()?.set_presets(&presets)?;
+                        (200, "ok", Response::ok(preset).json()?)
+                    }
+                    None => (
+                        404,
+                        "not found",
+                        Response::error(format!("no preset {name}")).json()?,
+                    ),
+                }
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let recall_auth = auth.clone();
+    let recall_presets = presets_arc.clone();
+    let recall_cmds = state.clone();
+    server.fn_handler("/api/presets/recall", Method::Post, move |mut req| {
+        let auth = &recall_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (code, message, payload) = match read_json::<PresetName>(&mut req) {
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok(PresetName { name }) => {
+                let exists = recall_presets.lock()?.contains_key(&name);
+                if exists {
+                    recall_cmds.lock()?.push_back(Cmd::RecallPreset(name));
+                    (200, "ok", Response::ok(true).json()?)
+                } else {
+                    (
+                        404,
+                        "not found",
+                        Response::error(format!("no preset {name}")).json()?,
+                    )
+                }
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let backup_auth = auth.clone();
+    let backup_store = store_arc.clone();
+    server.fn_handler("/api/backup", Method::Get, move |req| {
+        let auth = &backup_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let payload = Response::ok(backup_store.lock()?.backup()).json()?;
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    let backup_auth = auth.clone();
+    let backup_gimbal = gimbal_arc.clone();
+    let backup_store = store_arc.clone();
+    let backup_cmds = state.clone();
+    server.fn_handler("/api/backup", Method::Put, move |mut req| {
+        let auth = &backup_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let backup = read_json::<Backup>(&mut req).and_then(|backup| {
+            backup
+                .validate()
+                .map(|()| backup)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match backup {
+            Ok(backup) => {
+                backup_store.lock()?.restore(&backup)?;
+                {
+                    let mut gimbal = backup_gimbal.lock()?;
+                    gimbal.set_presets(backup.presets.clone());
+                    gimbal.set_work_offsets(backup.work_offsets.clone());
+                    gimbal.set_mount(backup.settings.mount);
+                    gimbal.set_site(backup.settings.site);
+                }
+                backup_cmds
+                    .lock()?
+                    .push_back(Cmd::ProcessGcode(Gcode::M1SetVelocity(
+                        Some(backup.settings.pan_velocity),
+                        Some(backup.settings.tilt_velocity),
+                    )));
+                (200, "ok", Response::ok(backup).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let timeline_auth = auth.clone();
+    let timeline_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/timeline", Method::Get, move |req| {
+        let auth = &timeline_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let gimbal = timeline_gimbal.lock()?;
+        let payload = Response::ok(gimbal.playback().map(Playback::status)).json()?;
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    let timeline_auth = auth.clone();
+    let timeline_cmds = state.clone();
+    server.fn_handler("/api/timeline", Method::Put, move |mut req| {
+        let auth = &timeline_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let timeline = read_json::<Timeline>(&mut req).and_then(|timeline| {
+            timeline
+                .validate(gimbal::AXIS_LIMITS)
+                .map(|()| timeline)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match timeline {
+            Ok(timeline) => {
+                timeline_cmds.lock()?.push_back(Cmd::LoadTimeline(timeline));
                 (200, "ok", Response::ok(true).json()?)
             }
             Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
         };
-        let mut response = req.into_response(
-            code,
-            Some(message),
-            &[
-                ("Access-Control-Allow-Origin", "*"),
-                ("content-type", "application/json"),
-            ],
-        )?;
-        response.write(payload.as_bytes())?;
-        response.flush()?;
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let timeline_auth = auth.clone();
+    let timeline_cmds = state.clone();
+    server.fn_handler("/api/timeline/control", Method::Post, move |mut req| {
+        let auth = &timeline_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (code, message, payload) = match read_json::<Control>(&mut req) {
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok(control) => {
+                timeline_cmds.lock()?.push_back(Cmd::Timeline(control));
+                (200, "ok", Response::ok(true).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let capture_routes: [(&str, fn(&mut Req) -> anyhow::Result<Capture>); 2] = [
+        ("/api/timelapse", |req| {
+            read_json(req).map(Capture::Timelapse)
+        }),
+        ("/api/panorama", |req| read_json(req).map(Capture::Panorama)),
+    ];
+    for (route, read_capture) in capture_routes {
+        let capture_auth = auth.clone();
+        let capture_gimbal = gimbal_arc.clone();
+        let capture_cmds = state.clone();
+        server.fn_handler(route, Method::Put, move |mut req| {
+            let auth = &capture_auth;
+            if !auth.is_authorized(req.header("Authorization")) {
+                return unauthorized(req, auth);
+            }
+            let capture = read_capture(&mut req).and_then(|capture| {
+                capture
+                    .validate()
+                    .map(|()| capture)
+                    .map_err(anyhow::Error::msg)
+            });
+            let (code, message, payload) = match capture {
+                Ok(_) if !capture_gimbal.lock()?.has_shutter() => (
+                    409,
+                    "no shutter",
+                    Response::error("no camera shutter configured").json()?,
+                ),
+                Ok(_) if ota::is_updating() => (
+                    503,
+                    "updating",
+                    Response::error("firmware update in progress").json()?,
+                ),
+                Ok(capture) => {
+                    capture_cmds.lock()?.push_back(Cmd::StartCapture(capture));
+                    (200, "ok", Response::ok(true).json()?)
+                }
+                Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+            };
+            respond(req, auth, code, message, &payload)
+        })?;
+    }
+
+    let preview_auth = auth.clone();
+    let preview_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/panorama/preview", Method::Post, move |mut req| {
+        let auth = &preview_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let panorama = read_json::<Panorama>(&mut req).and_then(|panorama| {
+            panorama
+                .validate()
+                .map(|()| panorama)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match panorama {
+            Ok(panorama) => {
+                let (velocities, fire_secs) = {
+                    let gimbal = preview_gimbal.lock()?;
+                    (gimbal.velocities(), gimbal.fire_secs())
+                };
+                let preview = panorama.preview(velocities, fire_secs);
+                (200, "ok", Response::ok(preview).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    // served at its timelapse only path too, for clients from before panoramas
+    for path in ["/api/capture/control", "/api/timelapse/control"] {
+        let capture_auth = auth.clone();
+        let capture_cmds = state.clone();
+        server.fn_handler(path, Method::Post, move |mut req| {
+            let auth = &capture_auth;
+            if !auth.is_authorized(req.header("Authorization")) {
+                return unauthorized(req, auth);
+            }
+            let (code, message, payload) = match read_json::<capture::Control>(&mut req) {
+                Ok(_) if ota::is_updating() => (
+                    503,
+                    "updating",
+                    Response::error("firmware update in progress").json()?,
+                ),
+                Ok(control) => {
+                    capture_cmds.lock()?.push_back(Cmd::Capture(control));
+                    (200, "ok", Response::ok(true).json()?)
+                }
+                Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+            };
+            respond(req, auth, code, message, &payload)
+        })?;
+    }
+
+    let look_auth = auth.clone();
+    let look_cmds = state.clone();
+    server.fn_handler("/api/look_at", Method::Post, move |mut req| {
+        let auth = &look_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (code, message, payload) = match read_json::<LookAt>(&mut req) {
+            Ok(look) if !look.target.iter().all(|v| v.is_finite()) => (
+                400,
+                "bad input",
+                Response::error("target must be numbers").json()?,
+            ),
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok(LookAt { target, track }) => {
+                aim(&look_cmds, Target::Point(target), track);
+                (200, "ok", Response::ok(true).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let point_auth = auth.clone();
+    let point_gimbal = gimbal_arc.clone();
+    let point_cmds = state.clone();
+    server.fn_handler("/api/point_at", Method::Post, move |mut req| {
+        let auth = &point_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let point = read_json::<PointAt>(&mut req).and_then(|point| {
+            (point.target.validate())
+                .map(|()| point)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match point {
+            Ok(_) if point_gimbal.lock()?.site().is_none() => (
+                409,
+                "no site",
+                Response::error("no site configured").json()?,
+            ),
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok(PointAt { target, track }) => {
+                aim(&point_cmds, Target::Geo(target), track);
+                (200, "ok", Response::ok(true).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let time_auth = auth.clone();
+    server.fn_handler("/api/time", Method::Get, move |req| {
+        let auth = &time_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        respond(req, auth, 200, "Ok", &Response::ok(clock::now()).json()?)
+    })?;
+
+    let set_time_auth = auth.clone();
+    server.fn_handler("/api/time", Method::Put, move |mut req| {
+        let auth = &set_time_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let set = read_json::<SetTime>(&mut req).and_then(|time| clock::set(time.unix));
+        let (code, message, payload) = match set {
+            Ok(()) => (200, "ok", Response::ok(clock::now()).json()?),
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let satellite_auth = auth.clone();
+    let satellite_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/satellite", Method::Get, move |req| {
+        let auth = &satellite_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let payload = {
+            let gimbal = satellite_gimbal.lock()?;
+            Response::ok(gimbal.rotator().map(Rotator::status)).json()?
+        };
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    let track_auth = auth.clone();
+    let track_gimbal = gimbal_arc.clone();
+    let track_cmds = state.clone();
+    server.fn_handler("/api/satellite", Method::Put, move |mut req| {
+        let auth = &track_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let track = read_json::<TrackSatellite>(&mut req).and_then(|track| {
+            track.options.validate().map_err(anyhow::Error::msg)?;
+            let satellite = Satellite::from_tle(&track.tle).map_err(anyhow::Error::msg)?;
+            Ok((satellite, track.options))
+        });
+        let (code, message, payload) = match track {
+            Ok(_) if track_gimbal.lock()?.site().is_none() => (
+                409,
+                "no site",
+                Response::error("no site configured").json()?,
+            ),
+            Ok(_) if clock::now().is_none() => {
+                (409, "no time", Response::error("clock not set").json()?)
+            }
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok((satellite, options)) => {
+                let name = satellite.name.clone();
+                (track_cmds.lock()?).push_back(Cmd::TrackSatellite(Box::new(satellite), options));
+                (200, "ok", Response::ok(name).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let passes_auth = auth.clone();
+    let passes_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/satellite/passes", Method::Get, move |req| {
+        let auth = &passes_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let Some(hours) = pass_hours(req.uri()) else {
+            let payload = Response::error(format!("hours is 0 to {MAX_PASS_HOURS}")).json()?;
+            return respond(req, auth, 400, "bad input", &payload);
+        };
+        // predicted on a copy, so the gimbal carries on tracking meanwhile
+        let rotator = passes_gimbal.lock()?.rotator().cloned();
+        let (code, message, payload) = match (rotator, clock::now()) {
+            (None, _) => (
+                404,
+                "not found",
+                Response::error("no satellite loaded").json()?,
+            ),
+            (_, None) => (409, "no time", Response::error("clock not set").json()?),
+            (
+                Some(Rotator {
+                    satellite,
+                    site,
+                    options,
+                    ..
+                }),
+                Some(now),
+            ) => {
+                let horizon = f64::from(options.horizon);
+                let until = now + hours * 3600.;
+                let passes = satellite.passes(&site, horizon, now, until, MAX_PASSES);
+                (200, "Ok", Response::ok(passes).json()?)
+            }
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    // predicts for a satellite without loading it, so whatever's tracked carries on
+    let predict_auth = auth.clone();
+    let predict_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/satellite/passes", Method::Post, move |mut req| {
+        let auth = &predict_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let Some(hours) = pass_hours(req.uri()) else {
+            let payload = Response::error(format!("hours is 0 to {MAX_PASS_HOURS}")).json()?;
+            return respond(req, auth, 400, "bad input", &payload);
+        };
+        let predict = read_json::<TrackSatellite>(&mut req).and_then(|predict| {
+            predict.options.validate().map_err(anyhow::Error::msg)?;
+            let satellite = Satellite::from_tle(&predict.tle).map_err(anyhow::Error::msg)?;
+            Ok((satellite, predict.options))
+        });
+        let site = predict_gimbal.lock()?.site();
+        let (code, message, payload) = match (predict, site, clock::now()) {
+            (Err(err), _, _) => (400, "bad input", Response::error(err.to_string()).json()?),
+            (_, None, _) => (
+                409,
+                "no site",
+                Response::error("no site configured").json()?,
+            ),
+            (_, _, None) => (409, "no time", Response::error("clock not set").json()?),
+            (Ok((satellite, options)), Some(site), Some(now)) => {
+                let horizon = f64::from(options.horizon);
+                let until = now + hours * 3600.;
+                let passes = satellite.passes(&site, horizon, now, until, MAX_PASSES);
+                (200, "Ok", Response::ok(passes).json()?)
+            }
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let sky_auth = auth.clone();
+    let sky_gimbal = gimbal_arc.clone();
+    server.fn_handler("/api/sky", Method::Get, move |req| {
+        let auth = &sky_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (site, sky) = {
+            let gimbal = sky_gimbal.lock()?;
+            (gimbal.site(), gimbal.sky().copied())
+        };
+        let now = clock::now();
+        let look = |body: Body| Some(body.look(&site?, now?));
+        let report = SkyReport {
+            tracking: sky.zip(now).map(|(sky, now)| sky.status(now)),
+            sun: look(Body::Sun),
+            moon: look(Body::Moon),
+        };
+        respond(req, auth, 200, "Ok", &Response::ok(report).json()?)
+    })?;
+
+    let body_auth = auth.clone();
+    let body_gimbal = gimbal_arc.clone();
+    let body_cmds = state.clone();
+    server.fn_handler("/api/sky", Method::Put, move |mut req| {
+        let auth = &body_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let track = read_json::<TrackBody>(&mut req).and_then(|track| {
+            (track.options.validate())
+                .map(|()| track)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match track {
+            Ok(_) if body_gimbal.lock()?.site().is_none() => (
+                409,
+                "no site",
+                Response::error("no site configured").json()?,
+            ),
+            Ok(_) if clock::now().is_none() => {
+                (409, "no time", Response::error("clock not set").json()?)
+            }
+            Ok(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            Ok(TrackBody { body, options }) => {
+                body_cmds.lock()?.push_back(Cmd::TrackBody(body, options));
+                (200, "ok", Response::ok(true).json()?)
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    // takes live in memory, edited directly like presets
+    let teach = teach::start(gimbal_arc.lock().unwrap().position())?;
+
+    let takes_auth = auth.clone();
+    let takes_teach = teach.clone();
+    server.fn_handler("/api/takes", Method::Get, move |req| {
+        let auth = &takes_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let payload = {
+            let teach = takes_teach.lock()?;
+            Response::ok(TakeList {
+                recording: teach.recording(),
+                takes: teach.takes.list(),
+            })
+            .json()?
+        };
+        respond(req, auth, 200, "Ok", &payload)
+    })?;
+
+    let record_auth = auth.clone();
+    let record_teach = teach.clone();
+    server.fn_handler("/api/takes/record", Method::Post, move |mut req| {
+        let auth = &record_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let name = read_json::<PresetName>(&mut req).and_then(|PresetName { name }| {
+            presets::validate_name(&name)
+                .map(|()| name)
+                .map_err(anyhow::Error::msg)
+        });
+        let (code, message, payload) = match name {
+            Ok(name) => match record_teach.lock()?.record(&name) {
+                Ok(()) => (200, "ok", Response::ok(true).json()?),
+                Err(err) => (409, "conflict", Response::error(err).json()?),
+            },
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let stop_auth = auth.clone();
+    let stop_teach = teach.clone();
+    server.fn_handler("/api/takes/stop", Method::Post, move |req| {
+        let auth = &stop_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let (code, message, payload) = match stop_teach.lock()?.stop() {
+            Ok(recorded) => (200, "ok", Response::ok(recorded).json()?),
+            Err(err) => (409, "conflict", Response::error(err).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let play_auth = auth.clone();
+    let play_teach = teach.clone();
+    let play_cmds = state.clone();
+    server.fn_handler("/api/takes/play", Method::Post, move |mut req| {
+        let auth = &play_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let play = match read_json::<PlayTake>(&mut req) {
+            Ok(play) => match takes::validate_speed(play.speed) {
+                Ok(()) => play,
+                Err(err) => {
+                    let payload = Response::error(err).json()?;
+                    return respond(req, auth, 400, "bad input", &payload);
+                }
+            },
+            Err(err) => {
+                let payload = Response::error(err.to_string()).json()?;
+                return respond(req, auth, 400, "bad input", &payload);
+            }
+        };
+        let timeline = (play_teach.lock()?.takes.get(&play.name))
+            .map(|take| take.timeline(play.speed, play.reverse));
+        let (code, message, payload) = match timeline {
+            None => (
+                404,
+                "not found",
+                Response::error(format!("no take {}", play.name)).json()?,
+            ),
+            Some(_) if ota::is_updating() => (
+                503,
+                "updating",
+                Response::error("firmware update in progress").json()?,
+            ),
+            // held to the same limits as any timeline
+            Some(timeline) => match timeline.check_limits(gimbal::AXIS_LIMITS) {
+                Ok(()) => {
+                    let mut cmds = play_cmds.lock()?;
+                    cmds.push_back(Cmd::LoadTimeline(timeline));
+                    cmds.push_back(Cmd::Timeline(Control::Play));
+                    (200, "ok", Response::ok(true).json()?)
+                }
+                Err(err) => (400, "bad input", Response::error(err).json()?),
+            },
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let take_auth = auth.clone();
+    let take_teach = teach.clone();
+    server.fn_handler("/api/take", Method::Get, move |req| {
+        let auth = &take_auth;
+        if !auth.can_read(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let name = query(req.uri(), "name").unwrap_or_default().to_owned();
+        let csv = query(req.uri(), "format") == Some("csv");
+        // rendered before responding, so recording isn't held up by the client
+        let (code, message, content_type, payload) = match take_teach.lock()?.takes.get(&name) {
+            Some(take) if csv => (200, "Ok", "text/csv", take.to_csv()),
+            Some(take) => (200, "Ok", "application/json", Response::ok(take).json()?),
+            None => (
+                404,
+                "not found",
+                "application/json",
+                Response::error(format!("no take {name}")).json()?,
+            ),
+        };
+        respond_as(req, auth, code, message, content_type, &payload)
+    })?;
+
+    let take_auth = auth.clone();
+    let take_teach = teach.clone();
+    server.fn_handler("/api/take", Method::Put, move |mut req| {
+        let auth = &take_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let name = query(req.uri(), "name").unwrap_or_default().to_owned();
+        let csv = req
+            .header("Content-Type")
+            .is_some_and(|content_type| content_type.starts_with("text/csv"));
+        let take = read_body_max(&mut req, MAX_TAKE_LEN).and_then(|body| match csv {
+            true => Take::from_csv(&String::from_utf8_lossy(&body)).map_err(anyhow::Error::msg),
+            false => Ok(serde_json::from_slice::<Take>(&body)?),
+        });
+        let (code, message, payload) = match take {
+            Ok(take) => {
+                let samples = take.samples.len();
+                match take_teach.lock()?.takes.insert(&name, take) {
+                    Ok(()) => (200, "ok", Response::ok(samples).json()?),
+                    Err(err) => (400, "bad input", Response::error(err).json()?),
+                }
+            }
+            Err(err) => (400, "bad input", Response::error(err.to_string()).json()?),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let take_auth = auth.clone();
+    let take_teach = teach.clone();
+    server.fn_handler("/api/take", Method::Delete, move |req| {
+        let auth = &take_auth;
+        if !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+        let name = query(req.uri(), "name").unwrap_or_default().to_owned();
+        let removed = take_teach.lock()?.takes.remove(&name);
+        let (code, message, payload) = match removed {
+            Some(_) => (200, "ok", Response::ok(true).json()?),
+            None => (
+                404,
+                "not found",
+                Response::error(format!("no take {name}")).json()?,
+            ),
+        };
+        respond(req, auth, code, message, &payload)
+    })?;
+
+    let ota_auth = auth.clone();
+    server.fn_handler("/api/ota", Method::Post, move |mut req| {
+        let auth = &ota_auth;
+        // never allow an open firmware upload, even when the rest of the api is
+        if !auth.is_enabled() || !auth.is_authorized(req.header("Authorization")) {
+            return unauthorized(req, auth);
+        }
+
+        let (code, message, payload) = match ota::update(&mut req) {
+            Ok(bytes) => (200, "ok", Response::ok(bytes).json()?),
+            Err(err) => {
+                warn!("ota: update failed: {err}");
+                (400, "bad input", Response::error(err.to_string()).json()?)
+            }
+        };
+        respond(req, auth, code, message, &payload)?;
+
+        if code == 200 {
+            info!("ota: rebooting into new image");
+            // let the response reach the client before going down
+            FreeRtos::delay_ms(1000);
+            esp_idf_svc::hal::reset::restart();
+        }
         Ok(())
     })?;
 
@@ -104,3 +1154,160 @@ pub fn start(
 
     Ok(server)
 }
+
+/// Queues aiming at `target`, or following it. A target not yet followed is
+/// stale, so it's replaced rather than letting a fast stream back up.
+fn aim(cmds: &CmdQueue, target: Target, track: bool) {
+    let mut cmds = cmds.lock().unwrap();
+    match (track, cmds.back_mut()) {
+        (true, Some(Cmd::Track(queued))) => *queued = target,
+        (true, _) => cmds.push_back(Cmd::Track(target)),
+        (false, _) => cmds.push_back(Cmd::LookAt(target)),
+    }
+}
+
+fn read_json<T: DeserializeOwned>(req: &mut Req) -> anyhow::Result<T> {
+    Ok(serde_json::from_slice(&read_body(req)?)?)
+}
+
+fn read_body(req: &mut Req) -> anyhow::Result<Vec<u8>> {
+    read_body_max(req, MAX_BODY_LEN)
+}
+
+fn read_body_max(req: &mut Req, max_len: usize) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut buf = [0; 512];
+    loop {
+        let n = req.read(&mut buf).map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..n]);
+        if body.len() > max_len {
+            return Err(anyhow::anyhow!("body exceeds {max_len} bytes"));
+        }
+    }
+    Ok(body)
+}
+
+fn serve_asset(req: Req, asset: &Asset) -> HandlerResult {
+    if req.header("If-None-Match") == Some(asset.etag) {
+        req.into_response(
+            304,
+            Some("Not Modified"),
+            &[("ETag", asset.etag), ("Cache-Control", web::CACHE_CONTROL)],
+        )?
+        .flush()?;
+        return Ok(());
+    }
+    let mut response = req.into_response(
+        200,
+        Some("Ok"),
+        &[
+            ("content-type", asset.content_type),
+            ("Content-Encoding", "gzip"),
+            ("Cache-Control", web::CACHE_CONTROL),
+            ("ETag", asset.etag),
+        ],
+    )?;
+    response.write_all(asset.body)?;
+    response.flush()?;
+    Ok(())
+}
+
+fn redirect(req: Req, location: &str) -> HandlerResult {
+    req.into_response(301, None, &[("Location", location)])?
+        .flush()?;
+    Ok(())
+}
+
+/// The value of `key` in the uri's query string.
+fn query<'a>(uri: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = uri.split_once('?')?;
+    (query.split('&')).find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+/// How far ahead to predict passes, from `?hours=`, 24 by default.
+fn pass_hours(uri: &str) -> Option<f64> {
+    let hours = query(uri, "hours")
+        .map_or(Ok(24.), str::parse::<f64>)
+        .ok()?;
+    (0. ..=MAX_PASS_HOURS).contains(&hours).then_some(hours)
+}
+
+fn respond(req: Req, auth: &Auth, code: u16, message: &str, payload: &str) -> HandlerResult {
+    respond_as(req, auth, code, message, "application/json", payload)
+}
+
+fn respond_as(
+    req: Req,
+    auth: &Auth,
+    code: u16,
+    message: &str,
+    content_type: &str,
+    payload: &str,
+) -> HandlerResult {
+    let origin = auth.allowed_origin(req.header("Origin")).map(str::to_owned);
+    let mut headers = vec![("content-type", content_type), ("Vary", "Origin")];
+    if let Some(origin) = origin.as_deref() {
+        headers.push(("Access-Control-Allow-Origin", origin));
+    }
+    let mut response = req.into_response(code, Some(message), &headers)?;
+    response.write_all(payload.as_bytes())?;
+    response.flush()?;
+    Ok(())
+}
+
+fn soap(req: Req, code: u16, xml: &str, challenge: Option<&str>) -> HandlerResult {
+    let mut headers = vec![("content-type", "application/soap+xml; charset=utf-8")];
+    if let Some(challenge) = challenge {
+        headers.push(("WWW-Authenticate", challenge));
+    }
+    let mut response = req.into_response(code, None, &headers)?;
+    response.write_all(xml.as_bytes())?;
+    response.flush()?;
+    Ok(())
+}
+
+fn unauthorized(req: Req, auth: &Auth) -> HandlerResult {
+    let origin = auth.allowed_origin(req.header("Origin")).map(str::to_owned);
+    let mut headers = vec![
+        ("content-type", "application/json"),
+        ("Vary", "Origin"),
+        ("WWW-Authenticate", auth.challenge()),
+    ];
+    if let Some(origin) = origin.as_deref() {
+        headers.push(("Access-Control-Allow-Origin", origin));
+    }
+    let mut response = req.into_response(401, Some("unauthorized"), &headers)?;
+    response.write_all(Response::error("unauthorized").json()?.as_bytes())?;
+    response.flush()?;
+    Ok(())
+}
+
+fn preflight(req: Req, auth: &Auth) -> HandlerResult {
+    let Some(origin) = auth.allowed_origin(req.header("Origin")).map(str::to_owned) else {
+        req.into_response(403, Some("origin not allowed"), &[("Vary", "Origin")])?
+            .flush()?;
+        return Ok(());
+    };
+    req.into_response(
+        204,
+        Some("No Content"),
+        &[
+            ("Access-Control-Allow-Origin", &origin),
+            (
+                "Access-Control-Allow-Methods",
+                "GET, POST, PUT, DELETE, OPTIONS",
+            ),
+            (
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type",
+            ),
+            ("Access-Control-Max-Age", "600"),
+            ("Vary", "Origin"),
+        ],
+    )?
+    .flush()?;
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use {
+    crate::{
+        capture::{Bookmark, Capture},
+        coordinates::{self, WorkOffsets},
+        geo::Site,
+        gimbal,
+        kinematics::Mount,
+        presets::{self, Presets},
+    },
+    esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    log::warn,
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+};
+
+const NAMESPACE: &str = "gimbal";
+const SETTINGS_KEY: &str = "settings";
+const WORK_OFFSETS_KEY: &str = "work_offsets";
+const PRESETS_KEY: &str = "presets";
+const CAPTURE_KEY: &str = "capture";
+const BOOKMARK_KEY: &str = "capture_at";
+const MAX_VALUE_LEN: usize = 4000;
+/// Timelapses with many bezier waypoints run past `MAX_VALUE_LEN`. They're
+/// written once a job, so the size is no bother
+const MAX_CAPTURE_LEN: usize = 8000;
+
+/// User tunable settings, persisted across reboots.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub pan_velocity: f32,
+    pub tilt_velocity: f32,
+    /// Redirect `/` to the hosted gimbal-gui instead of serving the built in ui
+    pub gui_redirect: bool,
+    /// Where the gimbal is, for aiming at points
+    pub mount: Mount,
+    /// Where the gimbal is on Earth, for aiming at places
+    pub site: Option<Site>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            pan_velocity: 30.,
+            tilt_velocity: 30.,
+            gui_redirect: false,
+            mount: Mount::default(),
+            site: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        gimbal::validate_velocity(self.pan_velocity)?;
+        gimbal::validate_velocity(self.tilt_velocity)?;
+        self.mount.validate()?;
+        self.site.as_ref().map_or(Ok(()), Site::validate)
+    }
+
+    /// Settings stored before they were all checked, with whichever fields
+    /// are invalid back at their defaults & the rest kept.
+    fn sanitized(self) -> Self {
+        let defaults = Settings::default();
+        let checked = |name, velocity, default| match gimbal::validate_velocity(velocity) {
+            Ok(()) => velocity,
+            Err(e) => {
+                warn!("resetting {name}: {e}");
+                default
+            }
+        };
+        let mount = match self.mount.validate() {
+            Ok(()) => self.mount,
+            Err(e) => {
+                warn!("resetting mount: {e}");
+                defaults.mount
+            }
+        };
+        let site = self.site.filter(|site| match site.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("forgetting site: {e}");
+                false
+            }
+        });
+        Settings {
+            pan_velocity: checked("pan_velocity", self.pan_velocity, defaults.pan_velocity),
+            tilt_velocity: checked("tilt_velocity", self.tilt_velocity, defaults.tilt_velocity),
+            mount,
+            site,
+            ..self
+        }
+    }
+}
+
+/// Everything a user configures, for moving it to another gimbal or
+/// restoring it after a flash erase.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Backup {
+    pub settings: Settings,
+    pub presets: Presets,
+    pub work_offsets: WorkOffsets,
+}
+
+impl Backup {
+    pub fn validate(&self) -> Result<(), String> {
+        self.settings.validate()?;
+        if self.work_offsets.active >= coordinates::SYSTEMS {
+            return Err(format!(
+                "no work coordinate system {}",
+                self.work_offsets.active
+            ));
+        }
+        presets::validate(&self.presets)
+    }
+}
+
+/// JSON values stored in nvs.
+pub struct Store {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Store {
+    pub fn new(partition: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.get_within(key, MAX_VALUE_LEN)
+    }
+
+    fn get_within<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        max_len: usize,
+    ) -> anyhow::Result<Option<T>> {
+        let mut buf = vec![0; max_len];
+        match self.nvs.get_raw(key, &mut buf)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+        self.set_within(key, value, MAX_VALUE_LEN)
+    }
+
+    fn set_within<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+        max_len: usize,
+    ) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec(value)?;
+        if bytes.len() > max_len {
+            return Err(anyhow::anyhow!(
+                "{key} is too large to store ({} bytes)",
+                bytes.len()
+            ));
+        }
+        self.nvs.set_raw(key, &bytes)?;
+        Ok(())
+    }
+
+    pub fn settings(&self) -> Settings {
+        let settings: Settings = self
+            .get(SETTINGS_KEY)
+            .unwrap_or_else(|e| {
+                warn!("discarding unreadable settings: {e}");
+                None
+            })
+            .unwrap_or_default();
+        settings.sanitized()
+    }
+
+    pub fn set_settings(&mut self, settings: &Settings) -> anyhow::Result<()> {
+        self.set(SETTINGS_KEY, settings)
+    }
+
+    pub fn work_offsets(&self) -> WorkOffsets {
+        self.get(WORK_OFFSETS_KEY)
+            .unwrap_or_else(|e| {
+                warn!("discarding unreadable work offsets: {e}");
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn set_work_offsets(&mut self, work_offsets: &WorkOffsets) -> anyhow::Result<()> {
+        self.set(WORK_OFFSETS_KEY, work_offsets)
+    }
+
+    pub fn presets(&self) -> Presets {
+        self.get(PRESETS_KEY)
+            .unwrap_or_else(|e| {
+                warn!("discarding unreadable presets: {e}");
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn set_presets(&mut self, presets: &Presets) -> anyhow::Result<()> {
+        self.set(PRESETS_KEY, presets)
+    }
+
+    /// The capture job that was running, if any, & how far it got.
+    pub fn capture(&self) -> Option<(Capture, Bookmark)> {
+        let capture = self
+            .get_within(CAPTURE_KEY, MAX_CAPTURE_LEN)
+            .unwrap_or_else(|e| {
+                warn!("discarding unreadable capture: {e}");
+                None
+            })?;
+        let bookmark = self
+            .get(BOOKMARK_KEY)
+            .unwrap_or_else(|e| {
+                warn!("starting capture over, its progress is unreadable: {e}");
+                None
+            })
+            .unwrap_or_default();
+        Some((capture, bookmark))
+    }
+
+    /// Saves a capture job as it starts, or forgets it given `None`.
+    pub fn set_capture(&mut self, capture: Option<&Capture>) -> anyhow::Result<()> {
+        match capture {
+            Some(capture) => {
+                self.set_within(CAPTURE_KEY, capture, MAX_CAPTURE_LEN)?;
+                self.set_bookmark(&Bookmark::default())
+            }
+            None => {
+                self.nvs.remove(CAPTURE_KEY)?;
+                self.nvs.remove(BOOKMARK_KEY)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Saves how far the capture job got.
+    pub fn set_bookmark(&mut self, bookmark: &Bookmark) -> anyhow::Result<()> {
+        self.set(BOOKMARK_KEY, bookmark)
+    }
+
+    pub fn backup(&self) -> Backup {
+        Backup {
+            settings: self.settings(),
+            presets: self.presets(),
+            work_offsets: self.work_offsets(),
+        }
+    }
+
+    pub fn restore(&mut self, backup: &Backup) -> anyhow::Result<()> {
+        self.set_settings(&backup.settings)?;
+        self.set_presets(&backup.presets)?;
+        self.set_work_offsets(&backup.work_offsets)
+    }
+}
@@ -0,0 +1,99 @@
+use {
+    crate::gimbal_pins::OutPin,
+    esp_idf_svc::hal::{
+        delay::FreeRtos,
+        gpio::{AnyOutputPin, PinDriver},
+    },
+    log::info,
+    std::time::Duration,
+};
+
+const SHUTTER_GPIO: &str = env!("SHUTTER_GPIO");
+const FOCUS_GPIO: &str = env!("FOCUS_GPIO");
+const SHUTTER_PULSE_MS: &str = env!("SHUTTER_PULSE_MS");
+const FOCUS_MS: &str = env!("FOCUS_MS");
+
+pub struct ShutterConfig {
+    pub shutter_gpio: i32,
+    pub focus_gpio: Option<i32>,
+    /// How long the shutter line is held
+    pub pulse: Duration,
+    /// How long focus is held before the shutter fires
+    pub focus: Duration,
+}
+
+impl ShutterConfig {
+    /// `None` when no shutter gpio is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if SHUTTER_GPIO.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            shutter_gpio: SHUTTER_GPIO.parse()?,
+            focus_gpio: match FOCUS_GPIO {
+                "" => None,
+                gpio => Some(gpio.parse()?),
+            },
+            pulse: Duration::from_millis(SHUTTER_PULSE_MS.parse()?),
+            focus: Duration::from_millis(FOCUS_MS.parse()?),
+        }))
+    }
+}
+
+/// A camera's remote release, driven through optocouplers that close the
+/// shutter & focus contacts while their gpio is high.
+pub struct Shutter {
+    shutter: OutPin,
+    focus: Option<OutPin>,
+    pulse: Duration,
+    focus_time: Duration,
+}
+
+fn out_pin(gpio: i32) -> anyhow::Result<OutPin> {
+    // the gpio is chosen at build time, so the caller keeps it free of the
+    // other peripherals
+    let pin = unsafe { AnyOutputPin::new(gpio) };
+    let mut pin = OutPin {
+        pd: PinDriver::output(pin)?,
+    };
+    pin.low();
+    Ok(pin)
+}
+
+impl Shutter {
+    pub fn new(config: &ShutterConfig) -> anyhow::Result<Self> {
+        info!(
+            "shutter: gpio {}, focus gpio {:?}",
+            config.shutter_gpio, config.focus_gpio
+        );
+        Ok(Self {
+            shutter: out_pin(config.shutter_gpio)?,
+            focus: config.focus_gpio.map(out_pin).transpose()?,
+            pulse: config.pulse,
+            focus_time: config.focus,
+        })
+    }
+
+    /// How long `fire` blocks for.
+    pub fn duration(&self) -> Duration {
+        match self.focus {
+            Some(_) => self.focus_time + self.pulse,
+            None => self.pulse,
+        }
+    }
+
+    /// Focuses, if there's a focus line, then takes a picture. Blocks until
+    /// the shutter line is released.
+    pub fn fire(&mut self) {
+        if let Some(focus) = self.focus.as_mut() {
+            focus.high();
+            FreeRtos::delay_ms(self.focus_time.as_millis() as u32);
+        }
+        self.shutter.high();
+        FreeRtos::delay_ms(self.pulse.as_millis() as u32);
+        self.shutter.low();
+        if let Some(focus) = self.focus.as_mut() {
+            focus.low();
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use {
+    crate::{
+        capture::{Job, Progress},
+        gimbal::{Gimbal, Position},
+        ota::{self, FirmwareInfo},
+    },
+    serde::Serialize,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    Fault,
+    Homing,
+    /// Locked mid move, so only the position is known
+    Moving,
+    Unreferenced,
+    Updating,
+    Idle,
+}
+
+/// Snapshot of the gimbal as reported to clients.
+#[derive(Serialize)]
+pub struct State<'a> {
+    #[serde(flatten)]
+    pub gimbal: &'a Gimbal,
+    /// In the active work coordinate system
+    pub pos_degrees: (f32, f32),
+    pub machine_pos_degrees: (f32, f32),
+    pub lifecycle: Lifecycle,
+    pub firmware: &'a FirmwareInfo,
+    pub is_updating: bool,
+    /// The timelapse or panorama being shot
+    pub capture: Option<Progress>,
+    /// `capture` while it's a timelapse, where it was reported before
+    /// panoramas
+    pub timelapse: Option<Progress>,
+}
+
+impl<'a> State<'a> {
+    pub fn new(gimbal: &'a Gimbal, firmware: &'a FirmwareInfo) -> Self {
+        let is_updating = ota::is_updating();
+        let lifecycle = if gimbal.last_error_message.is_some() {
+            Lifecycle::Fault
+        } else if is_updating {
+            Lifecycle::Updating
+        } else if gimbal.is_homing() {
+            Lifecycle::Homing
+        } else if !gimbal.is_home_referenced() {
+            Lifecycle::Unreferenced
+        } else {
+            Lifecycle::Idle
+        };
+        let capture = gimbal.capture().map(Job::progress);
+        let timelapse = capture.clone().filter(|p| p.kind == "timelapse");
+        Self {
+            gimbal,
+            pos_degrees: gimbal.pos_degrees(),
+            machine_pos_degrees: gimbal.machine_pos_degrees(),
+            lifecycle,
+            firmware,
+            is_updating,
+            capture,
+            timelapse,
+        }
+    }
+}
+
+/// What's known while the gimbal is locked mid move, read from its position.
+#[derive(Serialize)]
+pub struct Moving<'a> {
+    pub pos_degrees: (f32, f32),
+    pub machine_pos_degrees: (f32, f32),
+    pub lifecycle: Lifecycle,
+    pub firmware: &'a FirmwareInfo,
+    pub is_updating: bool,
+    /// As the locked gimbal reports it, for clients from before `lifecycle`
+    pub is_homing: bool,
+}
+
+impl<'a> Moving<'a> {
+    pub fn new(position: &Position, firmware: &'a FirmwareInfo) -> Self {
+        let is_updating = ota::is_updating();
+        let lifecycle = if is_updating {
+            Lifecycle::Updating
+        } else if position.is_homing() {
+            Lifecycle::Homing
+        } else {
+            Lifecycle::Moving
+        };
+        Self {
+            pos_degrees: position.degrees(),
+            machine_pos_degrees: position.machine_degrees(),
+            lifecycle,
+            firmware,
+            is_updating,
+            is_homing: position.is_homing(),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// Recorded performances: the position sampled while an operator moves the
+// gimbal by hand, to be played back as a timeline.
+
+use {
+    crate::{
+        presets,
+        timeline::{self, Easing, Keyframe, Timeline},
+    },
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, fmt::Write, time::Duration},
+};
+
+/// How often the position is recorded
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+pub const MAX_TAKES: usize = 8;
+/// Samples kept in memory across all takes, 4 minutes at the sample rate
+pub const MAX_SAMPLES: usize = 4800;
+/// Slowest & fastest a take plays back, as multiples of how it was recorded
+pub const MIN_SPEED: f32 = 0.1;
+pub const MAX_SPEED: f32 = 10.;
+
+/// Checks a playback speed multiple.
+pub fn validate_speed(speed: f32) -> Result<(), String> {
+    if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        return Err(format!("speed is {MIN_SPEED} to {MAX_SPEED} times"));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Sample {
+    /// Seconds from the start of the take
+    pub t: f32,
+    /// Degrees in the active work coordinate system
+    pub pan: f32,
+    pub tilt: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Take {
+    pub samples: Vec<Sample>,
+}
+
+impl Take {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.samples.len() < 2 {
+            return Err("a take has at least 2 samples".into());
+        }
+        if self.samples[0].t != 0. {
+            return Err("the first sample must be at t=0".into());
+        }
+        for (i, sample) in self.samples.iter().enumerate() {
+            if ![sample.t, sample.pan, sample.tilt]
+                .iter()
+                .all(|v| v.is_finite())
+            {
+                return Err(format!("sample {i} isn't a number"));
+            }
+            if i > 0 && sample.t <= self.samples[i - 1].t {
+                return Err(format!("sample {i} isn't after sample {}", i - 1));
+            }
+        }
+        if self.duration() > timeline::MAX_DURATION {
+            return Err(format!(
+                "a take lasts up to {} seconds",
+                timeline::MAX_DURATION
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.samples.last().map_or(0., |sample| sample.t)
+    }
+
+    /// The take as a timeline through every sample, `speed` times as fast
+    /// and optionally backwards.
+    pub fn timeline(&self, speed: f32, reverse: bool) -> Timeline {
+        let duration = self.duration();
+        let keyframe = |sample: &Sample, t: f32| Keyframe {
+            t: t / speed,
+            pan: sample.pan,
+            tilt: sample.tilt,
+            easing: Easing::Linear,
+        };
+        let keyframes = match reverse {
+            false => self.samples.iter().map(|s| keyframe(s, s.t)).collect(),
+            true => (self.samples.iter().rev())
+                .map(|s| keyframe(s, duration - s.t))
+                .collect(),
+        };
+        Timeline {
+            keyframes,
+            looping: false,
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("t,pan,tilt\n");
+        for Sample { t, pan, tilt } in &self.samples {
+            let _ = writeln!(csv, "{t:.3},{pan:.3},{tilt:.3}");
+        }
+        csv
+    }
+
+    /// Reads `t,pan,tilt` rows, with or without that header.
+    pub fn from_csv(csv: &str) -> Result<Self, String> {
+        let rows = csv.lines().map(str::trim).filter(|row| !row.is_empty());
+        let samples = rows
+            .enumerate()
+            .filter(|(i, row)| !(*i == 0 && row.starts_with('t')))
+            .map(|(i, row)| {
+                let fields: Vec<f32> = row
+                    .split(',')
+                    .map(|field| field.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("row {} isn't numbers: {row}", i + 1))?;
+                match fields[..] {
+                    [t, pan, tilt] => Ok(Sample { t, pan, tilt }),
+                    _ => Err(format!("row {} isn't t,pan,tilt: {row}", i + 1)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { samples })
+    }
+}
+
+/// Takes by name, listed in name order.
+#[derive(Default)]
+pub struct Takes {
+    takes: BTreeMap<String, Take>,
+}
+
+/// A take in a listing, without its samples.
+#[derive(Serialize)]
+pub struct Summary<'a> {
+    pub name: &'a str,
+    pub duration: f32,
+    pub samples: usize,
+}
+
+impl Takes {
+    pub fn get(&self, name: &str) -> Option<&Take> {
+        self.takes.get(name)
+    }
+
+    pub fn list(&self) -> Vec<Summary<'_>> {
+        (self.takes.iter())
+            .map(|(name, take)| Summary {
+                name,
+                duration: take.duration(),
+                samples: take.samples.len(),
+            })
+            .collect()
+    }
+
+    /// Samples left for recording or uploading, counting any take `replacing`
+    /// would free.
+    pub fn room(&self, replacing: &str) -> usize {
+        let used: usize = (self.takes.iter())
+            .filter(|(name, _)| name.as_str() != replacing)
+            .map(|(_, take)| take.samples.len())
+            .sum();
+        MAX_SAMPLES.saturating_sub(used)
+    }
+
+    /// Adds or replaces a take, within `MAX_TAKES` & `MAX_SAMPLES`.
+    pub fn insert(&mut self, name: &str, take: Take) -> Result<(), String> {
+        presets::validate_name(name)?;
+        take.validate()?;
+        if self.takes.len() >= MAX_TAKES && !self.takes.contains_key(name) {
+            return Err(format!("no room for more than {MAX_TAKES} takes"));
+        }
+        if take.samples.len() > self.room(name) {
+            return Err(format!(
+                "no room for {} more samples, {MAX_SAMPLES} are kept across all takes",
+                take.samples.len()
+            ));
+        }
+        self.takes.insert(name.to_owned(), take);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Take> {
+        self.takes.remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take() -> Take {
+        Take {
+            samples: vec![
+                Sample {
+                    t: 0.,
+                    pan: 0.,
+                    tilt: 0.,
+                },
+                Sample {
+                    t: 1.,
+                    pan: 10.,
+                    tilt: -5.,
+                },
+                Sample {
+                    t: 4.,
+                    pan: 20.,
+                    tilt: 0.,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_timeline() {
+        let timeline = take().timeline(2., true);
+        let t: Vec<_> = timeline.keyframes.iter().map(|k| k.t).collect();
+        assert_eq!(t, [0., 1.5, 2.]);
+        assert_eq!(timeline.keyframes[0].pan, 20.);
+        assert_eq!(timeline.keyframes[2].pan, 0.);
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = take().to_csv();
+        assert!(csv.starts_with("t,pan,tilt\n0.000,0.000,0.000\n"));
+        assert_eq!(Take::from_csv(&csv).unwrap(), take());
+        assert_eq!(Take::from_csv("0,1,2\n\n1,2,3").unwrap().samples.len(), 2);
+        assert!(Take::from_csv("t,pan,tilt\n0,1").is_err());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut takes = Takes::default();
+        takes.insert("sweep", take()).unwrap();
+        assert_eq!(takes.room("other"), MAX_SAMPLES - 3);
+        assert_eq!(takes.room("sweep"), MAX_SAMPLES);
+        assert!(takes.insert("bad name", take()).is_err());
+        let mut endless = take();
+        endless.samples[2].t = 1e12;
+        assert!(takes.insert("endless", endless).is_err());
+        let long = Take {
+            samples: (0..MAX_SAMPLES)
+                .map(|i| Sample {
+                    t: i as f32 * SAMPLE_INTERVAL.as_secs_f32(),
+                    pan: 0.,
+                    tilt: 0.,
+                })
+                .collect(),
+        };
+        assert!(takes.insert("long", long.clone()).is_err());
+        takes.insert("sweep", long).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use {
+    crate::{
+        gimbal::Position,
+        presets,
+        takes::{Sample, Take, Takes, SAMPLE_INTERVAL},
+    },
+    log::{info, warn},
+    serde::Serialize,
+    std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Instant,
+    },
+};
+
+struct Recording {
+    name: String,
+    /// Of the first sample, which is the take's t=0
+    started: Option<Instant>,
+    take: Take,
+    /// Samples it may grow to
+    room: usize,
+}
+
+/// Takes, & the one being recorded, if any.
+#[derive(Default)]
+pub struct Teach {
+    pub takes: Takes,
+    recording: Option<Recording>,
+}
+
+#[derive(Serialize)]
+pub struct Recorded {
+    pub name: String,
+    pub duration: f32,
+    pub samples: usize,
+}
+
+impl Teach {
+    /// Name of the take being recorded.
+    pub fn recording(&self) -> Option<&str> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.name.as_str())
+    }
+
+    /// Starts recording the position as take `name`, replacing any take of
+    /// that name when stopped.
+    pub fn record(&mut self, name: &str) -> Result<(), String> {
+        presets::validate_name(name)?;
+        if let Some(recording) = self.recording() {
+            return Err(format!("already recording {recording}"));
+        }
+        let room = self.takes.room(name);
+        if room < 2 {
+            return Err("no room for more samples, delete a take first".into());
+        }
+        info!("teach: recording {name}");
+        self.recording = Some(Recording {
+            name: name.to_owned(),
+            started: None,
+            take: Take::default(),
+            room,
+        });
+        Ok(())
+    }
+
+    /// Stops recording & keeps the take.
+    pub fn stop(&mut self) -> Result<Recorded, String> {
+        let recording = self.recording.take().ok_or("not recording")?;
+        let Recording { name, take, .. } = recording;
+        let recorded = Recorded {
+            name: name.clone(),
+            duration: take.duration(),
+            samples: take.samples.len(),
+        };
+        self.takes.insert(&name, take)?;
+        info!(
+            "teach: recorded {name}, {} samples over {:.1}s",
+            recorded.samples, recorded.duration
+        );
+        Ok(recorded)
+    }
+
+    fn sample(&mut self, (pan, tilt): (f32, f32)) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        let t = match recording.started {
+            Some(started) => started.elapsed().as_secs_f32(),
+            None => {
+                recording.started = Some(Instant::now());
+                0.
+            }
+        };
+        recording.take.samples.push(Sample { t, pan, tilt });
+        if recording.take.samples.len() >= recording.room {
+            warn!("teach: out of room, stopping");
+            if let Err(e) = self.stop() {
+                warn!("teach: failed to keep take: {e}");
+            }
+        }
+    }
+}
+
+/// Samples the position into the take being recorded, every `SAMPLE_INTERVAL`.
+pub fn start(position: Arc<Position>) -> anyhow::Result<Arc<Mutex<Teach>>> {
+    let teach = Arc::new(Mutex::new(Teach::default()));
+    let sampled = teach.clone();
+    thread::Builder::new()
+        .name("teach".into())
+        .stack_size(3072)
+        .spawn(move || loop {
+            sampled.lock().unwrap().sample(position.degrees());
+            thread::sleep(SAMPLE_INTERVAL);
+        })?;
+    Ok(teach)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_record() {
+        let mut teach = Teach::default();
+        teach.record("sweep").unwrap();
+        assert!(teach.record("other").is_err());
+        teach.sample((0., 0.));
+        thread::sleep(Duration::from_millis(10));
+        teach.sample((10., -5.));
+        let recorded = teach.stop().unwrap();
+        assert_eq!(recorded.samples, 2);
+        assert!(recorded.duration > 0.);
+        assert_eq!(teach.recording(), None);
+        let take = teach.takes.get("sweep").unwrap();
+        assert_eq!(take.samples[0].t, 0.);
+        assert_eq!(take.samples[1].pan, 10.);
+    }
+}
@@ -0,0 +1,139 @@
+// Shoot-move-shoot timelapses, moving a little between frames along eased
+// waypoints. `capture` shoots them.
+
+use {
+    crate::{
+        capture,
+        timeline::{self, Easing, Keyframe},
+    },
+    serde::{Deserialize, Serialize},
+};
+
+pub const MAX_FRAMES: u32 = 100_000;
+
+/// Where to point at a given frame.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Waypoint {
+    /// Counting from 0
+    pub frame: u32,
+    /// Degrees in the active work coordinate system
+    pub pan: f32,
+    pub tilt: f32,
+    /// Easing of the frames arriving at this waypoint
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Waypoint {
+    fn keyframe(&self) -> Keyframe {
+        Keyframe {
+            t: self.frame as f32,
+            pan: self.pan,
+            tilt: self.tilt,
+            easing: self.easing,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Timelapse {
+    pub waypoints: Vec<Waypoint>,
+    pub frames: u32,
+    /// Seconds from the start of one frame to the next
+    pub interval: f32,
+    /// Seconds to wait after moving, before firing
+    #[serde(default)]
+    pub settle: f32,
+    /// Seconds to leave the camera be after firing
+    #[serde(default)]
+    pub exposure: f32,
+}
+
+impl Timelapse {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_FRAMES).contains(&self.frames) {
+            return Err(format!("a timelapse has 1 to {MAX_FRAMES} frames"));
+        }
+        if self.waypoints.is_empty() || self.waypoints.len() > timeline::MAX_KEYFRAMES {
+            return Err(format!(
+                "a timelapse has 1 to {} waypoints",
+                timeline::MAX_KEYFRAMES
+            ));
+        }
+        if self.waypoints[0].frame != 0 {
+            return Err("the first waypoint must be at frame 0".into());
+        }
+        let keyframes: Vec<_> = self.waypoints.iter().map(Waypoint::keyframe).collect();
+        timeline::validate_keyframes(&keyframes, "waypoint")?;
+        if self.waypoints.iter().any(|w| w.frame >= self.frames) {
+            return Err(format!("waypoints must be before frame {}", self.frames));
+        }
+        let times = [self.interval, self.settle, self.exposure];
+        if !times.iter().all(|t| (0. ..=capture::MAX_SECS).contains(t)) || self.interval == 0. {
+            return Err(format!(
+                "interval, settle & exposure are positive seconds, up to {}",
+                capture::MAX_SECS
+            ));
+        }
+        if self.settle + self.exposure > self.interval {
+            return Err("settle & exposure don't fit in the interval".into());
+        }
+        Ok(())
+    }
+
+    /// Where to shoot `frame`.
+    pub fn position(&self, frame: u32) -> (f32, f32) {
+        let keyframes: Vec<_> = self.waypoints.iter().map(Waypoint::keyframe).collect();
+        timeline::interpolate(&keyframes, frame as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timelapse() -> Timelapse {
+        Timelapse {
+            waypoints: vec![
+                Waypoint {
+                    frame: 0,
+                    pan: 0.,
+                    tilt: 0.,
+                    easing: Easing::Linear,
+                },
+                Waypoint {
+                    frame: 10,
+                    pan: 100.,
+                    tilt: 10.,
+                    easing: Easing::Linear,
+                },
+            ],
+            frames: 11,
+            interval: 5.,
+            settle: 1.,
+            exposure: 2.,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut timelapse = timelapse();
+        timelapse.validate().unwrap();
+        assert_eq!(timelapse.position(5), (50., 5.));
+        timelapse.frames = 10;
+        assert!(timelapse.validate().is_err());
+        timelapse.frames = 11;
+        timelapse.exposure = 4.5;
+        assert!(timelapse.validate().is_err());
+        timelapse.exposure = 2.;
+        timelapse.interval = 1e30;
+        assert!(timelapse.validate().is_err());
+        timelapse.interval = 5.;
+
+        timelapse.waypoints[1].pan = f32::NAN;
+        assert!(timelapse.validate().is_err());
+        timelapse.waypoints[1].pan = 100.;
+        timelapse.waypoints[1].easing = Easing::Bezier([1.5, 0., 0.5, 1.]);
+        assert!(timelapse.validate().is_err());
+    }
+}
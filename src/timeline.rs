@@ -0,0 +1,347 @@
+// Keyframed motion for motion-control shots. Positions are worked out from the
+// keyframes at fixed ticks, so every take steps the motors identically.
+
+use {
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, Instant},
+};
+
+/// Playback advances & is validated in steps of this much time.
+pub const TICK: Duration = Duration::from_millis(20);
+pub const MAX_KEYFRAMES: usize = 64;
+/// Longest timeline, in seconds. Checking limits walks every tick, so this
+/// keeps that to a moment even on the gimbal
+pub const MAX_DURATION: f32 = 3600.;
+/// Bisection rounds when solving a bezier curve, enough for f32
+const BEZIER_ITERATIONS: u32 = 24;
+
+/// How a segment gets from one keyframe to the next.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Cubic, starting slow
+    EaseIn,
+    /// Cubic, arriving slow
+    EaseOut,
+    EaseInOut,
+    /// CSS style cubic bezier handles `[x1, y1, x2, y2]`, x within 0..=1
+    Bezier([f32; 4]),
+}
+
+impl Easing {
+    /// Progress through a segment for `u` of its time, both 0 to 1.
+    pub fn apply(&self, u: f32) -> f32 {
+        match *self {
+            Easing::Linear => u,
+            Easing::EaseIn => u * u * u,
+            Easing::EaseOut => 1. - (1. - u).powi(3),
+            Easing::EaseInOut if u < 0.5 => 4. * u * u * u,
+            Easing::EaseInOut => 1. - (2. - 2. * u).powi(3) / 2.,
+            Easing::Bezier([x1, y1, x2, y2]) => {
+                // x(s) only increases with x handles within 0..=1
+                let (mut lo, mut hi) = (0., 1.);
+                for _ in 0..BEZIER_ITERATIONS {
+                    let s = (lo + hi) / 2.;
+                    if bezier(x1, x2, s) < u {
+                        lo = s;
+                    } else {
+                        hi = s;
+                    }
+                }
+                bezier(y1, y2, (lo + hi) / 2.)
+            }
+        }
+    }
+}
+
+/// One coordinate of a cubic bezier from 0 to 1 with handles `p1` & `p2`.
+fn bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let r = 1. - s;
+    3. * r * r * s * p1 + 3. * r * s * s * p2 + s * s * s
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Keyframe {
+    /// Seconds from the start of the timeline
+    pub t: f32,
+    /// Degrees in the active work coordinate system
+    pub pan: f32,
+    pub tilt: f32,
+    /// Easing of the segment arriving at this keyframe
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Degrees per second
+    pub velocity: f32,
+    /// Degrees per second per second
+    pub acceleration: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Timeline {
+    pub keyframes: Vec<Keyframe>,
+    /// Starts over at the first keyframe when the last is reached
+    #[serde(default, rename = "loop")]
+    pub looping: bool,
+}
+
+/// Checks keyframes are numbers, in order, & eased within bounds, calling
+/// them `noun` in errors. Timelines & anything built on them share it.
+pub fn validate_keyframes(keyframes: &[Keyframe], noun: &str) -> Result<(), String> {
+    for (i, keyframe) in keyframes.iter().enumerate() {
+        if ![keyframe.t, keyframe.pan, keyframe.tilt]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            return Err(format!("{noun} {i} isn't a number"));
+        }
+        if i > 0 && keyframe.t <= keyframes[i - 1].t {
+            return Err(format!("{noun} {i} isn't after {noun} {}", i - 1));
+        }
+        if let Easing::Bezier([x1, _, x2, _]) = keyframe.easing {
+            if !(0. ..=1.).contains(&x1) || !(0. ..=1.).contains(&x2) {
+                return Err(format!("{noun} {i} bezier x handles must be 0 to 1"));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Timeline {
+    /// Checks the keyframes are in order and that playing them never asks
+    /// either axis for more than `limits`.
+    pub fn validate(&self, limits: Limits) -> Result<(), String> {
+        let keyframes = &self.keyframes;
+        if keyframes.len() < 2 || keyframes.len() > MAX_KEYFRAMES {
+            return Err(format!("a timeline has 2 to {MAX_KEYFRAMES} keyframes"));
+        }
+        if keyframes[0].t != 0. {
+            return Err("the first keyframe must be at t=0".into());
+        }
+        validate_keyframes(keyframes, "keyframe")?;
+        self.check_limits(limits)
+    }
+
+    /// Checks playing never asks either axis for more than `limits`.
+    pub fn check_limits(&self, limits: Limits) -> Result<(), String> {
+        // the same ticks playback uses, at rest before the first & after the last
+        if !(0. ..=MAX_DURATION).contains(&self.duration()) {
+            return Err(format!("a timeline lasts up to {MAX_DURATION} seconds"));
+        }
+        let secs = TICK.as_secs_f32();
+        let ticks = self.ticks();
+        let mut velocity = (0., 0.);
+        let mut previous = self.position_at(0);
+        for tick in 1..=ticks.saturating_add(1) {
+            let position = self.position_at(tick.min(ticks));
+            let v = (
+                (position.0 - previous.0) / secs,
+                (position.1 - previous.1) / secs,
+            );
+            let a = ((v.0 - velocity.0) / secs, (v.1 - velocity.1) / secs);
+            let t = tick as f32 * secs;
+            if v.0.abs().max(v.1.abs()) > limits.velocity {
+                return Err(format!(
+                    "too fast at t={t:.2}s: {:.1} deg/s, the limit is {}",
+                    v.0.abs().max(v.1.abs()),
+                    limits.velocity
+                ));
+            }
+            if a.0.abs().max(a.1.abs()) > limits.acceleration {
+                return Err(format!(
+                    "accelerates too hard at t={t:.2}s: {:.0} deg/s², the limit is {}",
+                    a.0.abs().max(a.1.abs()),
+                    limits.acceleration
+                ));
+            }
+            (velocity, previous) = (v, position);
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |keyframe| keyframe.t)
+    }
+
+    /// Ticks to play the whole timeline, rounding the end up.
+    pub fn ticks(&self) -> u32 {
+        (self.duration() / TICK.as_secs_f32()).ceil() as u32
+    }
+
+    /// Where to be `tick` ticks in, holding the last keyframe after the end.
+    pub fn position_at(&self, tick: u32) -> (f32, f32) {
+        interpolate(&self.keyframes, tick as f32 * TICK.as_secs_f32())
+    }
+}
+
+/// The eased position at `t` between keyframes in time order, holding the
+/// first & last outside them.
+pub fn interpolate(keyframes: &[Keyframe], t: f32) -> (f32, f32) {
+    let i = keyframes.partition_point(|keyframe| keyframe.t <= t);
+    if i == keyframes.len() {
+        return keyframes
+            .last()
+            .map_or((0., 0.), |keyframe| (keyframe.pan, keyframe.tilt));
+    }
+    if i == 0 {
+        return (keyframes[0].pan, keyframes[0].tilt);
+    }
+    let (from, to) = (&keyframes[i - 1], &keyframes[i]);
+    let progress = to.easing.apply((t - from.t) / (to.t - from.t));
+    (
+        from.pan + (to.pan - from.pan) * progress,
+        from.tilt + (to.tilt - from.tilt) * progress,
+    )
+}
+
+/// The nearest tick to `t` seconds.
+pub fn tick_at(t: f32) -> u32 {
+    (t.max(0.) / TICK.as_secs_f32()).round() as u32
+}
+
+/// A loaded timeline & how far through it playback is.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    pub timeline: Timeline,
+    pub tick: u32,
+    pub playing: bool,
+    /// When the current tick should finish stepping
+    pub deadline: Instant,
+}
+
+impl Playback {
+    pub fn new(timeline: Timeline) -> Self {
+        Self {
+            timeline,
+            tick: 0,
+            playing: false,
+            deadline: Instant::now(),
+        }
+    }
+
+    pub fn status(&self) -> Status<'_> {
+        Status {
+            keyframes: &self.timeline.keyframes,
+            looping: self.timeline.looping,
+            t: self.tick as f32 * TICK.as_secs_f32(),
+            duration: self.timeline.duration(),
+            playing: self.playing,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Status<'a> {
+    keyframes: &'a [Keyframe],
+    #[serde(rename = "loop")]
+    looping: bool,
+    /// Seconds in
+    t: f32,
+    duration: f32,
+    playing: bool,
+}
+
+/// Playback controls, as posted to `/api/timeline/control`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Control {
+    Play,
+    Pause,
+    /// Moves to `t` seconds in, keeping playing or paused
+    Seek {
+        t: f32,
+    },
+    Loop {
+        #[serde(rename = "loop")]
+        looping: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        velocity: 90.,
+        acceleration: 500.,
+    };
+
+    fn keyframe(t: f32, pan: f32, easing: Easing) -> Keyframe {
+        Keyframe {
+            t,
+            pan,
+            tilt: 0.,
+            easing,
+        }
+    }
+
+    #[test]
+    fn test_easing() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::Bezier([0.42, 0., 0.58, 1.]),
+        ] {
+            assert!(easing.apply(0.).abs() < 1e-4, "{easing:?}");
+            assert!((easing.apply(1.) - 1.).abs() < 1e-4, "{easing:?}");
+        }
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        // linear handles are linear
+        assert!((Easing::Bezier([0.25, 0.25, 0.75, 0.75]).apply(0.3) - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_position_at() {
+        let timeline = Timeline {
+            keyframes: vec![
+                keyframe(0., 0., Easing::Linear),
+                keyframe(4., 40., Easing::Linear),
+                keyframe(10., 40., Easing::EaseInOut),
+            ],
+            looping: false,
+        };
+        assert_eq!(timeline.ticks(), 500);
+        assert_eq!(timeline.position_at(0), (0., 0.));
+        assert_eq!(timeline.position_at(tick_at(2.)), (20., 0.));
+        assert_eq!(timeline.position_at(tick_at(7.)), (40., 0.));
+        assert_eq!(timeline.position_at(1000), (40., 0.));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut timeline = Timeline {
+            keyframes: vec![
+                keyframe(0., 0., Easing::Linear),
+                keyframe(4., 80., Easing::EaseInOut),
+            ],
+            looping: false,
+        };
+        timeline.validate(LIMITS).unwrap();
+
+        // 20 deg/s from a standstill within one tick
+        timeline.keyframes[1].easing = Easing::Linear;
+        assert!(timeline.validate(LIMITS).is_err());
+
+        timeline.keyframes[1] = keyframe(1., 170., Easing::EaseInOut);
+        assert!(timeline.validate(LIMITS).is_err());
+
+        timeline.keyframes[1] = keyframe(0., 10., Easing::EaseInOut);
+        assert!(timeline.validate(LIMITS).is_err());
+
+        // far too long to walk, whatever the earlier segments ask for
+        timeline.keyframes[1] = keyframe(1., 1e6, Easing::Linear);
+        timeline.keyframes.push(keyframe(1e12, 0., Easing::Linear));
+        assert!(timeline.validate(LIMITS).is_err());
+        timeline.keyframes.truncate(1);
+        assert!(timeline.validate(LIMITS).is_err());
+    }
+}
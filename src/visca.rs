@@ -0,0 +1,279 @@
+pub const TYPE_COMMAND: u16 = 0x0100;
+pub const TYPE_INQUIRY: u16 = 0x0110;
+pub const TYPE_REPLY: u16 = 0x0111;
+pub const TYPE_CONTROL: u16 = 0x0200;
+pub const TYPE_CONTROL_REPLY: u16 = 0x0201;
+
+const HEADER_LEN: usize = 8;
+const TERMINATOR: u8 = 0xFF;
+// replies come from the camera at address 1
+const REPLY_ADDRESS: u8 = 0x90;
+// we only ever execute one command at a time, so always report socket 1
+const SOCKET: u8 = 1;
+
+pub const MAX_PAN_SPEED: u8 = 0x18;
+pub const MAX_TILT_SPEED: u8 = 0x14;
+
+pub const ERROR_SYNTAX: u8 = 0x02;
+pub const ERROR_BUFFER_FULL: u8 = 0x03;
+pub const ERROR_CANCELLED: u8 = 0x04;
+pub const ERROR_NOT_EXECUTABLE: u8 = 0x41;
+
+#[derive(Debug, PartialEq)]
+pub struct Packet<'a> {
+    pub payload_type: u16,
+    pub seq: u32,
+    pub payload: &'a [u8],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Negative,
+    Positive,
+    Stop,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Continuous motion. Speeds are raw VISCA speeds, 1..=MAX_*_SPEED.
+    Drive {
+        pan_speed: u8,
+        tilt_speed: u8,
+        pan: Direction,
+        tilt: Direction,
+    },
+    /// Positions are raw signed VISCA position units
+    AbsolutePosition {
+        pan_speed: u8,
+        tilt_speed: u8,
+        pan: i16,
+        tilt: i16,
+    },
+    RelativePosition {
+        pan_speed: u8,
+        tilt_speed: u8,
+        pan: i16,
+        tilt: i16,
+    },
+    Home,
+    Reset,
+    PresetReset(u8),
+    PresetSet(u8),
+    PresetRecall(u8),
+    PositionInquiry,
+    /// Control command resetting the sequence number
+    ResetSequence,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ViscaError {
+    Truncated,
+    BadLength,
+    UnknownPayloadType(u16),
+    Syntax,
+}
+
+pub fn parse_packet(buf: &[u8]) -> Result<Packet<'_>, ViscaError> {
+    if buf.len() < HEADER_LEN {
+        return Err(ViscaError::Truncated);
+    }
+    let payload_type = u16::from_be_bytes([buf[0], buf[1]]);
+    let len = usize::from(u16::from_be_bytes([buf[2], buf[3]]));
+    let seq = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    let payload = buf
+        .get(HEADER_LEN..HEADER_LEN + len)
+        .ok_or(ViscaError::BadLength)?;
+    Ok(Packet {
+        payload_type,
+        seq,
+        payload,
+    })
+}
+
+fn nibbles_i16(bytes: &[u8]) -> i16 {
+    bytes
+        .iter()
+        .fold(0u16, |acc, b| (acc << 4) | u16::from(b & 0x0F)) as i16
+}
+
+fn direction(b: u8) -> Result<Direction, ViscaError> {
+    match b {
+        0x01 => Ok(Direction::Negative),
+        0x02 => Ok(Direction::Positive),
+        0x03 => Ok(Direction::Stop),
+        _ => Err(ViscaError::Syntax),
+    }
+}
+
+pub fn parse_command(packet: &Packet) -> Result<Command, ViscaError> {
+    let p = packet.payload;
+    match packet.payload_type {
+        TYPE_CONTROL => match p {
+            [0x01, ..] => Ok(Command::ResetSequence),
+            _ => Err(ViscaError::Syntax),
+        },
+        TYPE_COMMAND => match p {
+            // for pan, "left" is 01. for tilt, "up" is 01, which is positive
+            // tilt for us, hence the swap.
+            [0x81, 0x01, 0x06, 0x01, vv, ww, pp, tt, TERMINATOR] => Ok(Command::Drive {
+                pan_speed: *vv,
+                tilt_speed: *ww,
+                pan: direction(*pp)?,
+                tilt: match direction(*tt)? {
+                    Direction::Negative => Direction::Positive,
+                    Direction::Positive => Direction::Negative,
+                    Direction::Stop => Direction::Stop,
+                },
+            }),
+            [0x81, 0x01, 0x06, kind @ (0x02 | 0x03), vv, ww, rest @ ..]
+                if rest.len() == 9 && rest[8] == TERMINATOR =>
+            {
+                let pan = nibbles_i16(&rest[0..4]);
+                let tilt = nibbles_i16(&rest[4..8]);
+                let (pan_speed, tilt_speed) = (*vv, *ww);
+                Ok(match kind {
+                    0x02 => Command::AbsolutePosition {
+                        pan_speed,
+                        tilt_speed,
+                        pan,
+                        tilt,
+                    },
+                    _ => Command::RelativePosition {
+                        pan_speed,
+                        tilt_speed,
+                        pan,
+                        tilt,
+                    },
+                })
+            }
+            [0x81, 0x01, 0x06, 0x04, TERMINATOR] => Ok(Command::Home),
+            [0x81, 0x01, 0x06, 0x05, TERMINATOR] => Ok(Command::Reset),
+            [0x81, 0x01, 0x04, 0x3F, action, id, TERMINATOR] => match action {
+                0x00 => Ok(Command::PresetReset(*id)),
+                0x01 => Ok(Command::PresetSet(*id)),
+                0x02 => Ok(Command::PresetRecall(*id)),
+                _ => Err(ViscaError::Syntax),
+            },
+            _ => Err(ViscaError::Syntax),
+        },
+        TYPE_INQUIRY => match p {
+            [0x81, 0x09, 0x06, 0x12, TERMINATOR] => Ok(Command::PositionInquiry),
+            _ => Err(ViscaError::Syntax),
+        },
+        other => Err(ViscaError::UnknownPayloadType(other)),
+    }
+}
+
+pub fn encode(payload_type: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len());
+    buf.extend_from_slice(&payload_type.to_be_bytes());
+    buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&seq.to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+pub fn ack(seq: u32) -> Vec<u8> {
+    encode(TYPE_REPLY, seq, &[REPLY_ADDRESS, 0x40 | SOCKET, TERMINATOR])
+}
+
+pub fn completion(seq: u32) -> Vec<u8> {
+    encode(TYPE_REPLY, seq, &[REPLY_ADDRESS, 0x50 | SOCKET, TERMINATOR])
+}
+
+pub fn error(seq: u32, code: u8) -> Vec<u8> {
+    // syntax & buffer errors are not tied to a socket
+    let socket = match code {
+        ERROR_SYNTAX | ERROR_BUFFER_FULL => 0,
+        _ => SOCKET,
+    };
+    encode(
+        TYPE_REPLY,
+        seq,
+        &[REPLY_ADDRESS, 0x60 | socket, code, TERMINATOR],
+    )
+}
+
+pub fn reset_sequence_reply(seq: u32) -> Vec<u8> {
+    encode(TYPE_CONTROL_REPLY, seq, &[0x01])
+}
+
+pub fn position_reply(seq: u32, pan: i16, tilt: i16) -> Vec<u8> {
+    let mut payload = vec![REPLY_ADDRESS, 0x50];
+    for value in [pan as u16, tilt as u16] {
+        payload.extend((0..4).rev().map(|i| ((value >> (i * 4)) & 0x0F) as u8));
+    }
+    payload.push(TERMINATOR);
+    encode(TYPE_REPLY, seq, &payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(payload: &[u8]) -> Result<Command, ViscaError> {
+        let buf = encode(TYPE_COMMAND, 7, payload);
+        let packet = parse_packet(&buf)?;
+        assert_eq!(packet.seq, 7);
+        parse_command(&packet)
+    }
+
+    #[test]
+    fn test_drive_up_left() {
+        assert_eq!(
+            command(&[0x81, 0x01, 0x06, 0x01, 0x10, 0x08, 0x01, 0x01, 0xFF]),
+            Ok(Command::Drive {
+                pan_speed: 0x10,
+                tilt_speed: 0x08,
+                pan: Direction::Negative,
+                tilt: Direction::Positive,
+            })
+        );
+    }
+
+    #[test]
+    fn test_absolute_position() {
+        assert_eq!(
+            command(&[
+                0x81, 0x01, 0x06, 0x02, 0x18, 0x14, 0x0F, 0x0F, 0x0F, 0x0E, 0x00, 0x01, 0x02, 0x03,
+                0xFF
+            ]),
+            Ok(Command::AbsolutePosition {
+                pan_speed: 0x18,
+                tilt_speed: 0x14,
+                pan: -2,
+                tilt: 0x0123,
+            })
+        );
+    }
+
+    #[test]
+    fn test_preset_and_inquiry() {
+        assert_eq!(
+            command(&[0x81, 0x01, 0x04, 0x3F, 0x02, 0x05, 0xFF]),
+            Ok(Command::PresetRecall(5))
+        );
+        let buf = encode(TYPE_INQUIRY, 1, &[0x81, 0x09, 0x06, 0x12, 0xFF]);
+        assert_eq!(
+            parse_command(&parse_packet(&buf).unwrap()),
+            Ok(Command::PositionInquiry)
+        );
+        assert_eq!(command(&[0x81, 0x01, 0x99, 0xFF]), Err(ViscaError::Syntax));
+    }
+
+    #[test]
+    fn test_replies() {
+        assert_eq!(
+            ack(3),
+            vec![0x01, 0x11, 0x00, 0x03, 0, 0, 0, 3, 0x90, 0x41, 0xFF]
+        );
+        assert_eq!(
+            position_reply(1, -2, 0x0123)[8..],
+            [0x90, 0x50, 0x0F, 0x0F, 0x0F, 0x0E, 0x00, 0x01, 0x02, 0x03, 0xFF]
+        );
+        assert_eq!(
+            error(1, ERROR_SYNTAX)[8..],
+            [0x90, 0x60, ERROR_SYNTAX, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use {
+    crate::{
+        cmd::{Cmd, CmdQueue, Done},
+        gcode::Gcode,
+        gimbal::{Gimbal, Position, MAX_VELOCITY},
+        visca::{self, Command, Direction},
+    },
+    log::{info, warn},
+    std::{
+        io::ErrorKind,
+        net::{SocketAddr, UdpSocket},
+        sync::{
+            mpsc::{channel, Receiver, TryRecvError},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+const VISCA_PORT: &str = env!("VISCA_PORT");
+
+// matches the ±170° pan range of common VISCA heads to ±0x0990
+const UNITS_PER_DEGREE: f32 = 14.4;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_QUEUED: usize = 16;
+
+struct Session {
+    socket: UdpSocket,
+    cmds: CmdQueue,
+    gimbal_arc: Arc<Mutex<Gimbal>>,
+    position: Arc<Position>,
+    /// Replies to the last command, resent if the controller retransmits it
+    last_reply: Option<(SocketAddr, u32, Vec<Vec<u8>>)>,
+    /// Commands acknowledged but waiting on motion to finish
+    awaiting_completion: Vec<Awaiting>,
+}
+
+/// A queued command the controller is owed a completion or error for.
+struct Awaiting {
+    peer: SocketAddr,
+    seq: u32,
+    done: Receiver<Result<(), String>>,
+}
+
+fn speed_to_velocity(speed: u8, max_speed: u8) -> f32 {
+    f32::from(speed.clamp(1, max_speed)) / f32::from(max_speed) * MAX_VELOCITY
+}
+
+fn signed_velocity(speed: u8, max_speed: u8, direction: Direction) -> f32 {
+    match direction {
+        Direction::Negative => -speed_to_velocity(speed, max_speed),
+        Direction::Positive => speed_to_velocity(speed, max_speed),
+        Direction::Stop => 0.,
+    }
+}
+
+fn to_degrees(units: i16) -> f32 {
+    f32::from(units) / UNITS_PER_DEGREE
+}
+
+fn to_units(degrees: f32) -> i16 {
+    (degrees * UNITS_PER_DEGREE).clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16
+}
+
+impl Session {
+    fn send(&self, peer: SocketAddr, reply: &[u8]) {
+        if let Err(e) = self.socket.send_to(reply, peer) {
+            warn!("visca: failed replying to {peer}: {e}");
+        }
+    }
+
+    fn enqueue(&self, cmd: Cmd) {
+        self.cmds.lock().unwrap().push_back(cmd);
+    }
+
+    /// A move at the command's own speeds, leaving the configured velocities
+    /// be.
+    fn paced(pan_speed: u8, tilt_speed: u8, mv: Cmd) -> Cmd {
+        Cmd::AtVelocity(
+            speed_to_velocity(pan_speed, visca::MAX_PAN_SPEED),
+            speed_to_velocity(tilt_speed, visca::MAX_TILT_SPEED),
+            Box::new(mv),
+        )
+    }
+
+    /// Executes a command, returning the replies due right away and, when
+    /// completion must wait for the command to finish, how to hear of it.
+    fn execute(
+        &self,
+        seq: u32,
+        command: Command,
+    ) -> (Vec<Vec<u8>>, Option<Receiver<Result<(), String>>>) {
+        let immediate = |cmd: Cmd| {
+            self.enqueue(cmd);
+            (vec![visca::ack(seq), visca::completion(seq)], None)
+        };
+        let deferred = |cmd: Cmd| {
+            let (tx, rx) = channel();
+            self.enqueue(Cmd::Reply(Box::new(cmd), Done(tx)));
+            (vec![visca::ack(seq)], Some(rx))
+        };
+        match command {
+            Command::ResetSequence => (vec![visca::reset_sequence_reply(seq)], None),
+            Command::PositionInquiry => {
+                let (pan, tilt) = self.position.degrees();
+                (
+                    vec![visca::position_reply(seq, to_units(pan), to_units(tilt))],
+                    None,
+                )
+            }
+            Command::Drive {
+                pan_speed,
+                tilt_speed,
+                pan,
+                tilt,
+            } => immediate(Cmd::Drive(
+                signed_velocity(pan_speed, visca::MAX_PAN_SPEED, pan),
+                signed_velocity(tilt_speed, visca::MAX_TILT_SPEED, tilt),
+            )),
+            Command::AbsolutePosition {
+                pan_speed,
+                tilt_speed,
+                pan,
+                tilt,
+            } => deferred(Self::paced(
+                pan_speed,
+                tilt_speed,
+                Cmd::MoveTo(Some(to_degrees(pan)), Some(to_degrees(tilt))),
+            )),
+            Command::RelativePosition {
+                pan_speed,
+                tilt_speed,
+                pan,
+                tilt,
+            } => deferred(Self::paced(
+                pan_speed,
+                tilt_speed,
+                Cmd::MoveBy(Some(to_degrees(pan)), Some(to_degrees(tilt))),
+            )),
+            Command::Home => deferred(Cmd::MoveTo(Some(0.), Some(0.))),
+            Command::Reset => deferred(Cmd::ProcessGcode(Gcode::G28Home)),
+            Command::PresetSet(id) => deferred(Cmd::SetPreset(id.to_string(), None)),
+            Command::PresetReset(id) => deferred(Cmd::ClearPreset(id.to_string())),
+            Command::PresetRecall(id) => deferred(Cmd::RecallPreset(id.to_string())),
+        }
+    }
+
+    fn handle(&mut self, peer: SocketAddr, buf: &[u8]) {
+        let packet = match visca::parse_packet(buf) {
+            Ok(packet) => packet,
+            Err(e) => {
+                warn!("visca: dropping malformed packet from {peer}: {e:?}");
+                return;
+            }
+        };
+        let seq = packet.seq;
+
+        if let Some((last_peer, last_seq, replies)) = &self.last_reply {
+            if *last_peer == peer && *last_seq == seq && packet.payload_type != visca::TYPE_CONTROL
+            {
+                for reply in replies {
+                    self.send(peer, reply);
+                }
+                return;
+            }
+        }
+
+        let command = match visca::parse_command(&packet) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("visca: {e:?}");
+                self.send(peer, &visca::error(seq, visca::ERROR_SYNTAX));
+                return;
+            }
+        };
+
+        let is_faulted = self
+            .gimbal_arc
+            .try_lock()
+            .map(|g| g.last_error_message.is_some())
+            .unwrap_or(false);
+        let replies = if is_faulted && command != Command::PositionInquiry {
+            vec![visca::error(seq, visca::ERROR_NOT_EXECUTABLE)]
+        } else if self.cmds.lock().unwrap().len() >= MAX_QUEUED {
+            vec![visca::error(seq, visca::ERROR_BUFFER_FULL)]
+        } else {
+            let (replies, done) = self.execute(seq, command);
+            if let Some(done) = done {
+                self.awaiting_completion.push(Awaiting { peer, seq, done });
+            }
+            replies
+        };
+
+        for reply in &replies {
+            self.send(peer, reply);
+        }
+        self.last_reply = Some((peer, seq, replies));
+    }
+
+    fn run(mut self) {
+        let mut buf = [0; 64];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((n, peer)) => self.handle(peer, &buf[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => warn!("visca: {e}"),
+            }
+
+            self.complete();
+        }
+    }
+
+    /// Answers the commands that have finished since last time: completion
+    /// when they went through, an error when they failed or were cleared.
+    fn complete(&mut self) {
+        let mut awaiting = std::mem::take(&mut self.awaiting_completion);
+        awaiting.retain(|Awaiting { peer, seq, done }| {
+            let reply = match done.try_recv() {
+                Err(TryRecvError::Empty) => return true,
+                Ok(Ok(())) => visca::completion(*seq),
+                Ok(Err(e)) => {
+                    warn!("visca: command {seq} failed: {e}");
+                    visca::error(*seq, visca::ERROR_NOT_EXECUTABLE)
+                }
+                Err(TryRecvError::Disconnected) => visca::error(*seq, visca::ERROR_CANCELLED),
+            };
+            self.send(*peer, &reply);
+            false
+        });
+        self.awaiting_completion = awaiting;
+    }
+}
+
+/// `None` when no port is configured.
+pub fn port_from_env() -> anyhow::Result<Option<u16>> {
+    match VISCA_PORT {
+        "" => Ok(None),
+        port => Ok(Some(port.parse()?)),
+    }
+}
+
+/// Listens for VISCA-over-IP on udp `port`, 52381 as standard.
+pub fn start(port: u16, cmds: CmdQueue, gimbal_arc: Arc<Mutex<Gimbal>>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    info!("visca: listening on udp {port}");
+    let position = gimbal_arc.lock().unwrap().position();
+    let session = Session {
+        socket,
+        cmds,
+        gimbal_arc,
+        position,
+        last_reply: None,
+        awaiting_completion: vec![],
+    };
+    thread::Builder::new()
+        .name("visca".into())
+        .stack_size(6144)
+        .spawn(move || session.run())?;
+    Ok(())
+}
@@ -0,0 +1,11 @@
+/// A gzipped static file compiled into the firmware from `web/`.
+pub struct Asset {
+    pub uri: &'static str,
+    pub content_type: &'static str,
+    pub etag: &'static str,
+    pub body: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/web_assets.rs"));
+
+pub const CACHE_CONTROL: &str = "public, max-age=300, must-revalidate";
@@ -14,10 +14,12 @@ use {
 };
 
 use log::info;
-pub fn create_wifi(modem: Modem) -> anyhow::Result<AsyncWifi<EspWifi<'static>>> {
+pub fn create_wifi(
+    modem: Modem,
+    nvs: EspDefaultNvsPartition,
+) -> anyhow::Result<AsyncWifi<EspWifi<'static>>> {
     let sys_loop = EspSystemEventLoop::take()?;
     let timer_service = EspTaskTimerService::new()?;
-    let nvs = EspDefaultNvsPartition::take()?;
 
     let inner_wifi = EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
 